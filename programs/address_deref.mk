@@ -0,0 +1,13 @@
+fn main() -> u8 {
+    let x: u8 = 7;
+    let p: *u8 = &x;
+    let q: *u8 = &*p;
+
+    *(&x) = *(&x) + 1;
+
+    if (q == p) {
+        return *q + *(&x);
+    }
+
+    return 0;
+}
@@ -0,0 +1,20 @@
+struct Packed {
+    a: u8;
+    b: [3]u8;
+    c: u32;
+}
+
+struct Outer {
+    flag: bool;
+    inner: Packed;
+    wide: usize;
+}
+
+fn main() -> u8 {
+    let outer: Outer = Outer { flag: true };
+
+    outer.inner.c = 14;
+    outer.wide = alignof(Packed) + alignof(Outer) + alignof([4]u16) + alignof(*u8);
+
+    return (outer.wide + outer.inner.c as usize) as u8;
+}
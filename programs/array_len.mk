@@ -0,0 +1,6 @@
+fn main() -> u8 {
+    let buf: [2 * 3 - sizeof(u32) % 3]u8 = [1, 2, 3, 4, 5];
+    let words: [sizeof([3]u16) / 2]u16 = [10, 20, 30];
+
+    return buf[4] + words[2] as u8 + sizeof([2 * 8]u8) as u8 % 10;
+}
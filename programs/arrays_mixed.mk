@@ -0,0 +1,10 @@
+fn main() -> u8 {
+	let x: u16 = 300;
+	let small: u8 = 7;
+	let arr: [3]u16 = [x, 1, 2];
+	let mixed: [2]u32 = [small, x];
+	let inferred = [small, x, 5];
+
+	// 300 + 2 + 7 + 5 + (7 + 300) - 500
+	return (arr[0] + arr[2] + inferred[0] + inferred[2] + (mixed[0] + mixed[1]) as u16 - 500) as u8;
+}
@@ -0,0 +1,9 @@
+fn main() -> u8 {
+    let x: u8 = 3;
+
+    assert!(x == 3);
+    print!("x is 3\n");
+    assert!(x == 4);
+
+    return x;
+}
@@ -0,0 +1,26 @@
+let pos: usize = 0;
+
+fn next(input: *u8) -> u8 {
+    let c: u8 = input[pos];
+
+    pos = pos + 1;
+
+    return c;
+}
+
+fn main() -> u8 {
+    let input: [7]u8 = [3, 1, 4, 1, 5, 0, 9];
+    let c: u8;
+    let total: u8 = 0;
+
+    // Reads until the sentinel, `c` holds the last value read
+    while (c = next(&input as *u8)) != 0 {
+        total = total * 2 + c;
+    }
+
+    if (c = next(&input as *u8)) == 9 {
+        return total + c;
+    }
+
+    return 0;
+}
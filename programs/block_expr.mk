@@ -0,0 +1,20 @@
+fn twice(x: u64) -> u64 {
+    return x * 2;
+}
+
+fn main() -> u8 {
+    let a: u64 = 5;
+    let x = {
+        let t = twice(a);
+        t * 2 + 1
+    };
+    let y: u64 = { 3 };
+    let z: u64 = {
+        let a: u64 = 100;
+        { let b: u64 = a + 1; b } + x
+    };
+    {
+        print!(x, " ", y, " ", z, "\n");
+    };
+    return x as u8 + y as u8;
+}
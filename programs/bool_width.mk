@@ -0,0 +1,9 @@
+fn main() -> u8 {
+    let a: i64 = -1;
+    let b: i64 = 2;
+    let c: i32 = 7;
+    let d: i32 = 3;
+    let sum: u64 = (a < b) as u64 + (c < d) as u64 + (d < c) as u64;
+
+    return sum as u8;
+}
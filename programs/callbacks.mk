@@ -0,0 +1,23 @@
+struct Handler {
+    on_value: fn(i32) -> i32;
+}
+
+fn negate(x: i32) -> i32 {
+    return -x;
+}
+
+fn add_one(x: i32) -> i32 {
+    return x + 1;
+}
+
+fn apply(cb: fn(i32) -> i32, x: i32) -> i32 {
+    return cb(x);
+}
+
+fn main() -> u8 {
+    let cb: fn(i32) -> i32 = negate;
+    let same: *fn(i32) -> i32 = cb;
+    let handler: Handler = Handler { on_value: &add_one };
+
+    return (apply(cb, -20) + same(-1) + apply(handler.on_value, 2)) as u8;
+}
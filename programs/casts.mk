@@ -0,0 +1,14 @@
+fn main() -> u8 {
+	let arr: [4]i32 = [1, 2, 3, 4];
+	let arr_ptr: *i32 = arr as *i32;
+	let void_ptr: *void = arr_ptr as *void;
+	let addr: usize = void_ptr as usize;
+	let byte_ptr: *u8 = addr as *u8;
+	let int_ptr: *i32 = void_ptr as *i32;
+	let inferred = [5i32, 6, 7];
+	let inferred_ptr: *i32 = inferred as *i32;
+	let big: u64 = 300;
+
+	// Reads through each of the casts, `byte_ptr[4]` is the low byte of `arr[1]`
+	return big as u8 + 1 as u8 + arr_ptr[2] as u8 + *int_ptr as u8 + byte_ptr[4] + inferred_ptr[1] as u8;
+}
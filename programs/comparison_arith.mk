@@ -0,0 +1,13 @@
+fn main() -> u8 {
+    let a: i32 = 1;
+    let b: i32 = 2;
+    let c: i64 = -3;
+    let d: i64 = 4;
+    let count: u64 = (a < b) as u64 + (c < d) as u64;
+
+    if count == 2 {
+        return (a > b) as u8 + (c < d) as u8 + 40;
+    }
+
+    return 0;
+}
@@ -0,0 +1,26 @@
+fn main() -> u8 {
+    let neg: i8 = -1;
+    let small: u16 = 5;
+    let max: u64 = 18446744073709551615;
+    let same: bool = 1 == 1;
+    let result: u8 = 0;
+
+    // `neg` is promoted to u16, where it's 65535
+    if (neg < small) {
+        result = result + 1;
+    }
+    if max > 1 {
+        result = result + 2;
+    }
+    if same == true {
+        result = result + 4;
+    }
+    if 1 < 2 {
+        result = result + 8;
+    }
+    if neg < 0 {
+        result = result + 16;
+    }
+
+    return result;
+}
@@ -0,0 +1,30 @@
+fn main() -> u8 {
+    let k: i32 = 4;
+    let x: i32 = k * k;
+    let p: *i32 = &x;
+
+    // `x` is changed through a pointer
+    *p = x + 1;
+
+    let y: i32 = x - k;
+
+    if y == 13 {
+        k = 10;
+    }
+
+    let z: i32 = k + 1;
+    let n: u8 = 250;
+    let m: u8 = n + 10;
+    let neg: i32 = -7;
+    let q: i32 = neg / 2 + neg % 4;
+    let s: u32 = (1 << 4) >> 2;
+    let sum: i32 = 0;
+
+    for let i: i32 = 0; i < 3; i = i + 1 {
+        sum = sum + k;
+        k = 1;
+    }
+
+    // 13 + 11 - 6 + 12 + 4 + 4
+    return (y + z + q + sum) as u8 + m + s as u8;
+}
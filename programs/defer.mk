@@ -0,0 +1,57 @@
+let trace: u32;
+
+fn record(step: u32) -> void {
+    trace = trace * 10 + step;
+}
+
+fn early_return(early: bool) -> u32 {
+    defer record(1);
+    defer record(2);
+
+    if early == true {
+        defer record(3);
+
+        // The value is taken before the deferred calls run
+        return trace;
+    }
+
+    record(4);
+
+    return trace;
+}
+
+fn looped() -> u32 {
+    defer record(9);
+
+    for let i: u32 = 0; i < 5; i = i + 1 {
+        defer record(i + 1);
+
+        if i == 1 {
+            continue;
+        }
+        if i == 2 {
+            break;
+        }
+    }
+
+    return trace;
+}
+
+fn main() -> u8 {
+    trace = 0;
+    if early_return(true) != 0 || trace != 321 {
+        return 1;
+    }
+
+    trace = 0;
+    if early_return(false) != 4 || trace != 421 {
+        return 2;
+    }
+
+    trace = 0;
+    if looped() != 123 || trace != 1239 {
+        return 3;
+    }
+
+    return 0;
+}
@@ -0,0 +1,15 @@
+fn main() -> u8 {
+    // `idiv` needs the dividend sign-extended to its width, `div` needs it
+    // zero-extended, and unsigned operands with the top bit set would be
+    // negative to `idiv`
+    let a: i32 = -2000000000;
+    let b: i32 = 1000000000;
+    let c: u32 = 4000000000;
+    let d: u32 = 3000000000;
+    let e: u8 = 200;
+    let f: u8 = 150;
+    let i: i16 = -30000;
+    let j: i16 = 7;
+
+    return (a / b + 3) as u8 + (c / d) as u8 + e / f + e % f + (i % j + 5) as u8;
+}
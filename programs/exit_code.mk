@@ -0,0 +1,3 @@
+fn main() -> i32 {
+    return 42;
+}
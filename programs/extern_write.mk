@@ -0,0 +1,5 @@
+extern fn write(fd: i32, buf: *u8, count: usize) -> isize;
+
+fn main() -> u8 {
+    return write(1, "hello\n", 6) as u8;
+}
@@ -0,0 +1,24 @@
+extern noreturn fn exit(status: i32) -> void;
+
+naked fn answer() -> u64 {
+    asm("
+        mov rax, 42
+        ret
+    ");
+}
+
+noreturn fn quit(status: i32) -> void {
+    exit(status);
+}
+
+fn main() -> u8 {
+    let code: u8;
+
+    if answer() == 42 {
+        code = 7;
+    } else {
+        quit(1);
+    }
+
+    quit(code as i32);
+}
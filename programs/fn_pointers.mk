@@ -0,0 +1,18 @@
+fn double(x: u8) -> u8 {
+    return x * 2;
+}
+
+fn apply(f: *fn(u8) -> u8, x: u8) -> u8 {
+    return f(x);
+}
+
+fn main() -> u8 {
+    let f: *fn(u8) -> u8 = &double;
+    let g: *fn(u8) -> u8 = double;
+
+    if (f == g) {
+        return apply(f, 10) + g(11);
+    }
+
+    return 0;
+}
@@ -0,0 +1,21 @@
+fn main() -> u8 {
+    let i: u8 = 0;
+    let sum: u8 = 0;
+
+    loop:
+    if i == 10 {
+        goto done;
+    }
+    i = i + 1;
+    sum = sum + i;
+    goto loop;
+
+    done:
+    print!(sum, "\n");
+
+    goto skip;
+    print!("unreachable\n");
+
+    skip:
+    return i;
+}
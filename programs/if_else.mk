@@ -0,0 +1,25 @@
+fn pick(flag: bool) -> u64 {
+    let base: u64 = 2;
+
+    if flag == true {
+        let big: [8]u64 = [1, 2, 3, 4, 5, 6, 7, 8];
+        let value: u64 = 10;
+
+        return base + value;
+    } else {
+        let other: [8]u64;
+        let value: u64 = 20;
+
+        return base + value;
+    }
+}
+
+fn main() -> u8 {
+    let result: u64 = pick(true) + pick(false);
+
+    if result != 34 {
+        return 1;
+    }
+
+    return 0;
+}
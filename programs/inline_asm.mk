@@ -0,0 +1,5 @@
+fn main() -> u8 {
+    asm("mov rax, 60; mov rdi, 42; syscall");
+
+    return 0;
+}
@@ -0,0 +1,6 @@
+fn main() -> u8 {
+	let a: u8 = 0x10 + 0b11;
+	let b: u8 = 0o7 + 1_0;
+
+	return a + b;
+}
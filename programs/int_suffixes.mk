@@ -0,0 +1,10 @@
+fn main() -> u8 {
+    let big: u64 = 0xffff_ffffu32 as u64 + 1;
+    let wrapped: u8 = 250u8 + 10u8;
+
+    if big == 0x1_0000_0000u64 {
+        return wrapped;
+    }
+
+    return 0;
+}
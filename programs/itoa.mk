@@ -0,0 +1,20 @@
+extern fn write(fd: i32, buf: *u8, count: usize) -> isize;
+
+fn main() -> u8 {
+    static let buf: [24]u8;
+    let small: i8 = -128;
+    let big: u64 = 18446744073709551615;
+
+    let len: usize = itoa!(small, buf as *u8);
+    write(1, buf as *u8, len);
+    print!(" ", len, "\n");
+
+    len = itoa!(big, buf as *u8);
+    write(1, buf as *u8, len);
+    print!(" ", len, "\n");
+
+    len = itoa!(0, buf as *u8);
+    print!(buf as *u8, " ", len, " ", -9223372036854775807 - 1, "\n");
+
+    return 0;
+}
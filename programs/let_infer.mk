@@ -0,0 +1,13 @@
+fn main() -> u8 {
+    let a: u8 = 40;
+    let b = a + 2;
+    // Nothing constrains the literal, so `c` is an `i32` and the sum wraps around
+    let c = 2147483647;
+    let d = c + 1;
+
+    if d < 0 {
+        print!("wrapped\n");
+    }
+
+    return b;
+}
@@ -0,0 +1,11 @@
+struct Node {
+    value: i32;
+    next: *Node;
+}
+
+fn main() -> u8 {
+    let tail: Node = Node { value: 2, next: NULL };
+    let head: Node = Node { value: 40, next: &tail };
+
+    return (head.value + head.next->value) as u8;
+}
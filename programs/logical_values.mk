@@ -0,0 +1,45 @@
+let calls: u8 = 0;
+
+fn yes() -> bool {
+    calls = calls + 1;
+
+    return true;
+}
+
+fn no() -> bool {
+    calls = calls + 1;
+
+    return false;
+}
+
+fn main() -> u8 {
+    let a: i32 = 1;
+    let c: i32 = 2;
+    let r: u8 = 0;
+
+    let b: bool = a < c && c > a;
+    // `&&` binds tighter than `||`
+    let x: bool = no() && yes() || yes();
+    let y: bool = yes() || no() && no();
+    let z: bool = (yes() || no()) && no();
+
+    if b == true {
+        r = r + 1;
+    }
+    if x == true {
+        r = r + 2;
+    }
+    if y == true {
+        r = r + 4;
+    }
+    if z == true {
+        r = r + 8;
+    }
+
+    // Evaluated only for the calls, the right operand is skipped
+    yes() || no();
+    no() && yes();
+
+    // 2 + 1 + 2 + 2 calls
+    return r + calls * 16;
+}
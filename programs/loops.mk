@@ -0,0 +1,38 @@
+fn main() -> u8 {
+    let iterations: u8 = 0;
+    let sum: u32 = 0;
+
+    // `continue` still runs the increment
+    for let i: u32 = 0; i < 10; i = i + 1 {
+        iterations = iterations + 1;
+
+        if i % 2 == 0 {
+            continue;
+        }
+
+        sum = sum + i;
+    }
+
+    let n: u8 = 0;
+
+    while true {
+        n = n + 1;
+
+        if n == 5 {
+            break;
+        }
+    }
+
+    let (a, b) = (0, 1);
+
+    for ; a < 50; {
+        let t: i32 = a + b;
+
+        a = b;
+        b = t;
+    }
+
+    print!(iterations, " ", sum, " ", n, " ", a, "\n");
+
+    return iterations;
+}
@@ -0,0 +1,19 @@
+fn mix(a: i8, b: i16, c: i32, d: i64, e: i8, f: i16, g: i32, h: i64) -> i64 {
+    let x: i64 = 100;
+    let y: i8 = 7;
+    let z: [4]i32 = [1, 2, 3, 4];
+
+    // The last two parameters live in the caller's frame, above the locals
+    g = g + z[3];
+    h = h * 2;
+    x = x - h;
+
+    return x + g + y + a + b + c + d + e + f + z[0];
+}
+
+fn main() -> u8 {
+    let first: i64 = mix(1, 2, 3, 4, 5, 6, 7, 8);
+    let second: i64 = mix(0, 0, 0, 0, 0, 0, 0, 50);
+
+    return (first + second) as u8;
+}
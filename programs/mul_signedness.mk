@@ -0,0 +1,10 @@
+fn main() -> u8 {
+    // Both products fit their type, but not the other signedness, so trapping
+    // on overflow picks `imul` for the first and `mul` for the second
+    let a: i8 = -1;
+    let b: i8 = -1;
+    let c: u8 = 16;
+    let d: u8 = 15;
+
+    return (a * b) as u8 + c * d;
+}
@@ -0,0 +1,9 @@
+fn main() -> u8 {
+    let p: Point = Point { x: 4, y: 5 };
+
+    return helper(&p) as u8;
+}
+
+fn twice(x: i32) -> i32 {
+    return x + x;
+}
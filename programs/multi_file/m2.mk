@@ -0,0 +1,10 @@
+struct Point {
+    x: i32;
+    y: i32;
+}
+
+let bias: i32 = 3;
+
+fn helper(p: *Point) -> i32 {
+    return p->x * p->y + twice(bias);
+}
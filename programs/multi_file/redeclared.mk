@@ -0,0 +1,7 @@
+fn helper(p: *Point) -> i32 {
+    return p->x;
+}
+
+fn twice(x: i32) -> i32 {
+    return x * 2;
+}
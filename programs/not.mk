@@ -0,0 +1,10 @@
+fn main() -> u8 {
+    let mask: u8 = ~0x0f;
+    let empty: bool = !(mask == 0);
+
+    if !empty == true {
+        return 0;
+    }
+
+    return mask;
+}
@@ -0,0 +1,10 @@
+fn main() -> u8 {
+	let a: u8 = 5;
+	let p: *u8 = &a;
+	let q: *u8 = NULL;
+	let is_null: bool = q == NULL;
+	let not_null: bool = NULL != p;
+	let same: bool = p == p;
+
+	return is_null as u8 + not_null as u8 + same as u8 + (a == 5) as u8;
+}
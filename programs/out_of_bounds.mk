@@ -0,0 +1,12 @@
+struct Buffer {
+	items: [4]u8;
+	after: u8;
+}
+
+fn main() -> u8 {
+	let buf: Buffer = Buffer { items: [1, 2, 3, 4], after: 42 };
+	let i: usize = 4;
+
+	// Reads `after` unless bounds are checked
+	return buf.items[i];
+}
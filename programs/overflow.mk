@@ -0,0 +1,6 @@
+fn main() -> u8 {
+    let max: i32 = 2147483647;
+    let wrapped: i32 = max + 3;
+
+    return wrapped as u8;
+}
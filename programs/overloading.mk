@@ -0,0 +1,40 @@
+fn negate(x: i32) -> i32 {
+    return -x;
+}
+
+fn negate(x: i64) -> i64 {
+    return -x;
+}
+
+fn width(x: i32) -> u8 {
+    return 4;
+}
+
+fn width(x: i64) -> u8 {
+    return 8;
+}
+
+fn width(s: *u8) -> u8 {
+    return 1;
+}
+
+fn main() -> u8 {
+    let small: i32 = -20;
+    let big: i64 = -100000;
+
+    print!(negate(small), " ", negate(big), "\n");
+
+    let widths: u8 = width(small) + width(big) * 10;
+
+    return widths + width("str") + width(true);
+}
+
+// Calls can pick an overload declared after them
+fn width(b: bool) -> u8 {
+    return 100;
+}
+
+// Doesn't clash with the label of `width(i32)`
+fn width_i32() -> u8 {
+    return 0;
+}
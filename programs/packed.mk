@@ -0,0 +1,36 @@
+packed struct Header {
+    tag: i8;
+    len: i32;
+    crc: u16;
+}
+
+struct Padded {
+    tag: i8;
+    len: i32;
+    crc: u16;
+}
+
+// Both headers are misaligned after the first byte
+struct Wrapper {
+    kind: u8;
+    a: Header;
+    b: Header;
+}
+
+fn main() -> u8 {
+    let first: Header = Header { tag: 1, len: 100000, crc: 7 };
+    let second: Header = Header { tag: -2, len: -300, crc: 9 };
+    let wrapper: Wrapper = Wrapper { kind: 3, a: first, b: second };
+
+    if wrapper.a.len != 100000 || wrapper.b.len != -300 || wrapper.b.tag != -2 {
+        return 1;
+    }
+    if sizeof(Header) != 7 || sizeof(Padded) != 12 || sizeof(Wrapper) != 15 {
+        return 2;
+    }
+    if alignof(Header) != 1 || alignof(Wrapper) != 1 {
+        return 3;
+    }
+
+    return wrapper.kind + wrapper.a.crc as u8 + wrapper.b.crc as u8;
+}
@@ -0,0 +1,11 @@
+fn main() -> u8 {
+    let arr: [4]u8 = [1, 2, 3, 4];
+    let p: *u8 = arr as *u8;
+
+    *(p + 2) = 5;
+    p[1] = 6;
+    let q: *u8 = &arr[3];
+    *q = 7;
+
+    return arr[2] + *(p + 1) + p[3];
+}
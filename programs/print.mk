@@ -0,0 +1,17 @@
+fn fib(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+
+    return fib(n - 1) + fib(n - 2);
+}
+
+fn main() -> u8 {
+    let answer: u8 = 42;
+    let offset: i16 = -1234;
+
+    print!("answer = ", answer, ", offset = ", offset, "\n");
+    print!("fib(20) = ", fib(20), "\n");
+
+    return answer;
+}
@@ -0,0 +1,9 @@
+fn main() -> u8 {
+	let a: u8 = 200;
+	let b: u16 = 100;
+	let c: u16 = a + b;
+	let d: i8 = 2;
+	let e: i16 = 10;
+
+	return (c - 260) as u8 + (e - d) as u8;
+}
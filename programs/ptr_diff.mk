@@ -0,0 +1,14 @@
+struct Rgb {
+    r: u8;
+    g: u8;
+    b: u8;
+}
+
+fn main() -> u8 {
+    let words: [8]u32 = [0, 1, 2, 3, 4, 5, 6, 7];
+    let colors: [4]Rgb = [Rgb {}, Rgb {}, Rgb {}, Rgb {}];
+    let back: isize = &words[2] - &words[5];
+
+    // Pointers subtract to the number of elements between them
+    return ((&words[5] - &words[2]) + (&colors[3] - &colors[1]) + back + 10) as u8;
+}
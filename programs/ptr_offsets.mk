@@ -0,0 +1,9 @@
+fn main() -> u8 {
+    let arr: [3]u16 = [10, 20, 30];
+    let p: *u16 = &arr[2];
+    let back: i32 = -2;
+    let one: i8 = -1;
+
+    // Signed offsets are sign-extended and scaled like unsigned ones
+    return (*(p + (-1)) + *(p + back) + *(p - 1) + *(p + one)) as u8;
+}
@@ -0,0 +1,11 @@
+let value: u8 = 1;
+
+fn shadow(value: u8) -> u8 {
+    return value;
+}
+
+fn main() -> u8 {
+    let value: u8 = 40;
+
+    return value + shadow(2);
+}
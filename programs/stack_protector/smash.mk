@@ -0,0 +1,20 @@
+// Writes 8 bytes past the end of `buf`, which is right below the canary under
+// `--stack-protector`. Without it the write is undefined behavior, so it's not
+// one of the programs run with every configuration.
+fn smash() -> u8 {
+    let buf: [8]u8 = [0, 0, 0, 0, 0, 0, 0, 0];
+    let i: usize = 0;
+
+    again:
+    if i < 16 {
+        buf[i] = 255;
+        i = i + 1;
+        goto again;
+    }
+
+    return buf[0];
+}
+
+fn main() -> u8 {
+    return smash();
+}
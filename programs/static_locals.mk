@@ -0,0 +1,38 @@
+fn next() -> u8 {
+    static let counter: u8 = 40;
+    static let zeroed: u8;
+    let local: u8 = 2;
+
+    counter = counter + local + zeroed;
+
+    return counter;
+}
+
+fn pick(first: bool) -> u8 {
+    if first == true {
+        static let x: u8 = 1;
+
+        x = x + 1;
+
+        return x;
+    } else {
+        static let x: u8 = 10;
+
+        x = x + 10;
+
+        return x;
+    }
+}
+
+fn main() -> u8 {
+    next();
+    next();
+
+    let last: u8 = next();
+
+    pick(true);
+    pick(false);
+
+    // 46 from the third call, 3 and 30 from the two `x`, each kept apart
+    return last + pick(true) + pick(false);
+}
@@ -0,0 +1,7 @@
+fn twice(x: i32) -> i32 {
+    return x + x;
+}
+
+pub fn add_twice(a: i32, b: i32) -> i32 {
+    return twice(a) + b;
+}
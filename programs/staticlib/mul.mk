@@ -0,0 +1,3 @@
+pub fn mul_add(a: i32, b: i32, c: i32) -> i32 {
+    return add_twice(a * b, c);
+}
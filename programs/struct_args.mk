@@ -0,0 +1,25 @@
+struct Pair {
+    a: u32;
+    b: u64;
+}
+
+struct Big {
+    x: u64;
+    y: u64;
+    z: u8;
+}
+
+fn sum(pair: Pair, c: u8) -> u64 {
+    return pair.a + pair.b + c;
+}
+
+fn big(first: u8, value: Big, last: u8) -> u64 {
+    return value.x + value.y + value.z + first + last;
+}
+
+fn main() -> u8 {
+    let pair: Pair = Pair { a: 10, b: 20 };
+    let value: Big = Big { x: 1, y: 2, z: 3 };
+
+    return (sum(pair, 3) + big(1, value, 2) + sum(Pair { a: 1, b: 2 }, 0)) as u8;
+}
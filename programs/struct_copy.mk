@@ -0,0 +1,30 @@
+struct Three {
+    a: u8;
+    b: u8;
+    c: u8;
+}
+
+struct Seven {
+    a: u8;
+    b: u8;
+    c: u8;
+    d: u8;
+    e: u8;
+    f: u8;
+    g: u8;
+}
+
+fn main() -> u8 {
+    let three: Three = Three { a: 1, b: 2, c: 3 };
+    let three_guard: u8 = 30;
+    let three_copy: Three = three;
+    let seven: Seven = Seven { a: 1, b: 2, c: 3, d: 4, e: 5, f: 6, g: 7 };
+    let seven_guard: u8 = 70;
+    let seven_copy: Seven = seven;
+
+    print!(three_copy.a, three_copy.b, three_copy.c, " ", three_guard, "\n");
+    print!(seven_copy.a, seven_copy.b, seven_copy.c, seven_copy.d, seven_copy.e, seven_copy.f, seven_copy.g);
+    print!(" ", seven_guard, "\n");
+
+    return 0;
+}
@@ -0,0 +1,30 @@
+struct Pair {
+    a: u32;
+    b: u64;
+}
+
+struct Quad {
+    a: u64;
+    b: u64;
+    c: u64;
+    d: u8;
+}
+
+fn make_pair(a: u32, b: u64) -> Pair {
+    return Pair { a: a, b: b };
+}
+
+fn make_quad(a: u64, pair: Pair) -> Quad {
+    let quad: Quad = Quad { a: a, b: pair.a as u64, c: pair.b, d: 4 };
+
+    return quad;
+}
+
+fn main() -> u8 {
+    let pair: Pair = make_pair(1, 2);
+    let quad: Quad = make_quad(3, make_pair(5, 6));
+
+    make_quad(0, pair);
+
+    return (pair.a * 10 + pair.b + quad.a + quad.b + quad.c + quad.d) as u8;
+}
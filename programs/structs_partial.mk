@@ -0,0 +1,18 @@
+struct Point {
+	x: u64;
+	y: u8;
+	z: u16;
+}
+
+fn main() -> u8 {
+	let garbage: Point = Point {
+		x: 255,
+		y: 255,
+		z: 255,
+	};
+	let point: Point = Point {
+		y: 3,
+	};
+
+	return point.x as u8 + point.y + point.z as u8;
+}
@@ -0,0 +1,11 @@
+fn main() -> u8 {
+    let unused: u8 = 1;
+
+    return 0
+}
+
+fn other() -> u8 {
+    let also_unused: u8 = 2;
+
+    return 0;
+}
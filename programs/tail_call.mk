@@ -0,0 +1,13 @@
+// Recurses 10 million times, each call in tail position. Without `-O1` every
+// call takes a stack frame and overflows the stack long before the end.
+fn count(n: u64, acc: u64) -> u64 {
+    if n == 0 {
+        return acc;
+    }
+
+    return count(n - 1, acc + 3);
+}
+
+fn main() -> u8 {
+    return (count(10000000, 0) % 256) as u8;
+}
@@ -0,0 +1,24 @@
+extern fn pthread_create(thread: *u64, attr: *void, start: fn(*void) -> *void, arg: *void) -> i32;
+extern fn pthread_join(thread: u64, ret: **void) -> i32;
+
+thread_local let counter: u64 = 5;
+thread_local let scratch: u32;
+
+fn work(arg: *void) -> *void {
+    counter = counter + 100;
+    scratch = 9;
+
+    return NULL;
+}
+
+fn main() -> u8 {
+    let thread: u64;
+    let p: *u64 = &counter;
+
+    pthread_create(&thread, NULL, work, NULL);
+    pthread_join(thread, NULL);
+    *p = *p + 1;
+
+    // The thread changed its own copies only
+    return (counter + scratch as u64) as u8;
+}
@@ -0,0 +1,18 @@
+fn divmod(a: i32, b: i32) -> (i32, i32) {
+    return (a / b, a % b);
+}
+
+// Too large for rax and rdx, returned through memory
+fn triple(x: u64) -> (u64, u8, u64) {
+    return (x, 2, x * 3);
+}
+
+fn main() -> u8 {
+    let (q, r) = divmod(47, 5);
+    let (a, b, c) = triple(10);
+    let (small, big) = (7, 300);
+
+    print!(q, " ", r, " ", a + b as u64 + c, " ", small + big, "\n");
+
+    return (q * 10 + r) as u8;
+}
@@ -0,0 +1,27 @@
+// Aliases can be used before they're declared and refer to other aliases
+type Word = Half;
+type Half = u16;
+type Byte = u8;
+
+struct Pair {
+    low: Byte;
+    high: Word;
+}
+
+type Pairs = [4]Pair;
+type PairPtr = *Pair;
+
+fn sum(pair: PairPtr) -> Word {
+    return pair->low as Word + pair->high;
+}
+
+fn main() -> u8 {
+    let b: Byte = 3;
+    let pair: Pair = Pair { low: b, high: 4 };
+
+    if sizeof(Word) != sizeof(u16) || sizeof(Pairs) != 4 * sizeof(Pair) {
+        return 1;
+    }
+
+    return sum(&pair) as u8 + sizeof(Pair) as u8;
+}
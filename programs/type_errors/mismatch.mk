@@ -0,0 +1,5 @@
+fn main() -> u8 {
+    let x: u8 = true;
+
+    return x;
+}
@@ -0,0 +1,3 @@
+fn main() -> u8 {
+    return x;
+}
@@ -0,0 +1,19 @@
+union Bits {
+    word: u32;
+    bytes: [4]u8;
+    signed: i32;
+}
+
+struct Tagged {
+    tag: u8;
+    bits: Bits;
+}
+
+fn main() -> u8 {
+    let bits: Bits = Bits { word: 0x01020304 };
+    let tagged: Tagged = Tagged { tag: 1, bits: Bits { signed: -1 } };
+
+    // 4 + 3 + 255 + 4 + 8 wraps around to 18
+    return bits.bytes[0] + bits.bytes[1] + tagged.bits.bytes[3]
+        + sizeof(Bits) as u8 + sizeof(Tagged) as u8;
+}
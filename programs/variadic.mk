@@ -0,0 +1,7 @@
+extern fn printf(fmt: *u8, ...) -> i32;
+
+fn main() -> u8 {
+    let written: i32 = printf("%d %d\n", 4, 2);
+
+    return written as u8;
+}
@@ -0,0 +1,8 @@
+fn garbage() -> u32 {
+    return 12345;
+}
+
+// Exits with 0, not with whatever is left in eax
+fn main() -> void {
+    garbage();
+}
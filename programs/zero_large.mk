@@ -0,0 +1,28 @@
+struct Buf {
+    data: [1027]u8;
+}
+
+struct Guarded {
+    before: u64;
+    buf: Buf;
+    after: u64;
+}
+
+fn sum(buf: *Buf) -> u64 {
+    let total: u64 = 0;
+
+    for let i: usize = 0; i < 1027; i = i + 1 {
+        total = total + buf->data[i] as u64;
+    }
+
+    return total;
+}
+
+fn main() -> u8 {
+    let guarded: Guarded = Guarded { before: 3, after: 4 };
+
+    guarded.buf.data[1026] = 7;
+    guarded.buf = Buf {};
+
+    return (sum(&guarded.buf) + guarded.before + guarded.after) as u8;
+}
@@ -0,0 +1,21 @@
+struct Unit {}
+
+struct Tagged {
+    unit: Unit;
+    value: u8;
+}
+
+let nothing: Unit;
+
+fn take(unit: Unit, value: u8) -> u8 {
+    return value;
+}
+
+fn main() -> u8 {
+    let unit: Unit = Unit {};
+    let tagged: Tagged = Tagged { unit: unit, value: 5 };
+
+    nothing = tagged.unit;
+
+    return take(nothing, tagged.value + sizeof(Unit) as u8 + sizeof(Tagged) as u8);
+}
@@ -0,0 +1,114 @@
+use super::{BinOp, Expr, ExprKind, ExprLit, IntTy, Ty, UintTy, UnOp};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConstEvalError {
+    #[error("expression isn't constant")]
+    NotConst,
+    #[error("size of `{0}` isn't known until its layout is computed")]
+    UnknownSize(Ty),
+    #[error("division by zero")]
+    DivByZero,
+    #[error("constant value overflows")]
+    Overflow,
+}
+
+impl Expr {
+    /// Evaluates an integer constant expression, made of integer literals,
+    /// `sizeof` and arithmetic on them. The value is wide enough to hold any
+    /// result of `u64` and `i64` operands, overflowing ones are an error.
+    pub fn const_eval(&self) -> Result<i128, ConstEvalError> {
+        match &self.kind {
+            ExprKind::Lit(ExprLit::Int(lit)) => Ok(*lit as i128),
+            ExprKind::Lit(ExprLit::UInt(lit) | ExprLit::Suffixed(lit, _)) => Ok(*lit as i128),
+            ExprKind::SizeOf(ty) => Ok(ty.const_size()? as i128),
+            ExprKind::Unary {
+                op: UnOp::Negative,
+                expr,
+            } => expr
+                .const_eval()?
+                .checked_neg()
+                .ok_or(ConstEvalError::Overflow),
+            ExprKind::Binary { op, left, right } => {
+                let (lhs, rhs) = (left.const_eval()?, right.const_eval()?);
+                let value = match op {
+                    BinOp::Add => lhs.checked_add(rhs),
+                    BinOp::Sub => lhs.checked_sub(rhs),
+                    BinOp::Mul => lhs.checked_mul(rhs),
+                    BinOp::Div | BinOp::Rem if rhs == 0 => {
+                        return Err(ConstEvalError::DivByZero);
+                    }
+                    BinOp::Div => lhs.checked_div(rhs),
+                    BinOp::Rem => lhs.checked_rem(rhs),
+                    _ => return Err(ConstEvalError::NotConst),
+                };
+
+                value
+                    .filter(|value| i64::MIN as i128 <= *value && *value <= u64::MAX as i128)
+                    .ok_or(ConstEvalError::Overflow)
+            }
+            _ => Err(ConstEvalError::NotConst),
+        }
+    }
+}
+
+impl Ty {
+    /// Size of a type which doesn't depend on the layout of any struct.
+    fn const_size(&self) -> Result<usize, ConstEvalError> {
+        Ok(match self {
+            Self::Void => 0,
+            Self::Null | Self::Bool => 1,
+            Self::Int(IntTy::I8) | Self::UInt(UintTy::U8) => 1,
+            Self::Int(IntTy::I16) | Self::UInt(UintTy::U16) => 2,
+            Self::Int(IntTy::I32) | Self::UInt(UintTy::U32) => 4,
+            Self::Int(IntTy::I64 | IntTy::Isize)
+            | Self::UInt(UintTy::U64 | UintTy::Usize)
+            | Self::Ptr(..)
+            | Self::Fn(..) => 8,
+            Self::Array { ty, len } => ty
+                .const_size()?
+                .checked_mul(*len)
+                .ok_or(ConstEvalError::Overflow)?,
+            // Like a struct, a tuple is laid out according to the alignment of its elements
+            Self::Ident(_) | Self::Tuple(_) | Self::Infer => {
+                return Err(ConstEvalError::UnknownSize(self.clone()))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConstEvalError;
+    use crate::{diagnostics::Diagnostics, lexer::Lexer, parser::Parser, parser::Precedence};
+
+    fn eval(source: &str) -> Result<i128, ConstEvalError> {
+        let mut diagnostics = Diagnostics::new(source);
+
+        Parser::new(Lexer::new(source), &mut diagnostics)
+            .parse_expr(Precedence::default())
+            .unwrap()
+            .const_eval()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("2 * 8 + 10 / 3 - 7 % 4"), Ok(16));
+        assert_eq!(eval("(1 - 5) * 2"), Ok(-8));
+        assert_eq!(eval("sizeof([3]*u8) + sizeof(u16)"), Ok(26));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("1 / (2 - 2)"), Err(ConstEvalError::DivByZero));
+        assert_eq!(
+            eval("18446744073709551615 * 2"),
+            Err(ConstEvalError::Overflow)
+        );
+        assert_eq!(eval("2 * x"), Err(ConstEvalError::NotConst));
+        assert!(matches!(
+            eval("sizeof(Foo)"),
+            Err(ConstEvalError::UnknownSize(_))
+        ));
+    }
+}
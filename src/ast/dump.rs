@@ -0,0 +1,410 @@
+use super::{Block, Expr, ExprKind, ExprLit, Item, Stmt, Variable};
+use std::fmt::{self, Write};
+
+/// Readable tree representation of the AST, one node per line with children
+/// indented below their parent. Used by `--emit-ast`.
+pub struct Dump<'a>(pub &'a [Item]);
+
+impl fmt::Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut dumper = Dumper { f, indent: 0 };
+
+        for item in self.0 {
+            dumper.item(item)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Dumper<'a, 'f> {
+    f: &'a mut fmt::Formatter<'f>,
+    indent: usize,
+}
+
+impl Dumper<'_, '_> {
+    fn line(&mut self, args: fmt::Arguments) -> fmt::Result {
+        for _ in 0..self.indent {
+            self.f.write_str("  ")?;
+        }
+
+        self.f.write_fmt(args)?;
+        self.f.write_char('\n')
+    }
+
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.indent += 1;
+        let result = f(self);
+        self.indent -= 1;
+
+        result
+    }
+
+    fn item(&mut self, item: &Item) -> fmt::Result {
+        match item {
+            Item::Global(var) => self.variable("global", var),
+            Item::Fn {
+                pub_,
+                naked,
+                noreturn,
+                ret_ty,
+                name,
+                params,
+                variadic,
+                block,
+                ..
+            } => {
+                let mut params = params
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .collect::<Vec<_>>();
+
+                if *variadic {
+                    params.push(String::from("..."));
+                }
+
+                let params = params.join(", ");
+                let pub_ = if *pub_ { "pub " } else { "" };
+                let attrs = [(*naked, "naked "), (*noreturn, "noreturn ")]
+                    .into_iter()
+                    .filter_map(|(set, attr)| set.then_some(attr))
+                    .collect::<String>();
+
+                match block {
+                    Some(block) => {
+                        self.line(format_args!("{pub_}{attrs}fn {name}({params}) -> {ret_ty}"))?;
+                        self.nested(|dumper| dumper.block(block))
+                    }
+                    None => self.line(format_args!(
+                        "extern {attrs}fn {name}({params}) -> {ret_ty};"
+                    )),
+                }
+            }
+            Item::Struct { name, fields, .. } | Item::Union { name, fields, .. } => {
+                let keyword = match item {
+                    Item::Union { .. } => "union",
+                    Item::Struct { packed: true, .. } => "packed struct",
+                    _ => "struct",
+                };
+
+                self.line(format_args!("{keyword} {name}"))?;
+                self.nested(|dumper| {
+                    fields
+                        .iter()
+                        .try_for_each(|(name, ty)| dumper.line(format_args!("{name}: {ty}")))
+                })
+            }
+            Item::TypeAlias { name, ty, .. } => self.line(format_args!("type {name} = {ty}")),
+        }
+    }
+
+    fn variable(&mut self, keyword: &str, var: &Variable) -> fmt::Result {
+        let pub_ = if var.pub_ { "pub " } else { "" };
+        let static_ = if var.static_ { " static" } else { "" };
+        let thread_local = if var.thread_local {
+            "thread_local "
+        } else {
+            ""
+        };
+
+        self.line(format_args!(
+            "{pub_}{thread_local}{keyword}{static_} {}: {}",
+            var.name, var.ty
+        ))?;
+
+        match &var.value {
+            Some(expr) => self.nested(|dumper| dumper.expr(expr)),
+            None => Ok(()),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> fmt::Result {
+        block.stmts.iter().try_for_each(|stmt| self.stmt(stmt))?;
+
+        match &block.expr {
+            Some(expr) => self.labeled_expr("value", expr),
+            None => Ok(()),
+        }
+    }
+
+    fn labeled_block(&mut self, label: &str, block: &Block) -> fmt::Result {
+        self.line(format_args!("{label}"))?;
+        self.nested(|dumper| dumper.block(block))
+    }
+
+    fn labeled_expr(&mut self, label: &str, expr: &Expr) -> fmt::Result {
+        self.line(format_args!("{label}"))?;
+        self.nested(|dumper| dumper.expr(expr))
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> fmt::Result {
+        match stmt {
+            Stmt::Local(var) => self.variable("let", var),
+            Stmt::Item(item) => self.item(item),
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::Return(expr) => {
+                self.line(format_args!("return"))?;
+
+                match expr {
+                    Some(expr) => self.nested(|dumper| dumper.expr(expr)),
+                    None => Ok(()),
+                }
+            }
+            Stmt::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.line(format_args!("if"))?;
+                self.nested(|dumper| {
+                    dumper.labeled_expr("condition", condition)?;
+                    dumper.labeled_block("then", consequence)?;
+
+                    match alternative {
+                        Some(block) => dumper.labeled_block("else", block),
+                        None => Ok(()),
+                    }
+                })
+            }
+            Stmt::While { condition, block } => {
+                self.line(format_args!("while"))?;
+                self.nested(|dumper| {
+                    dumper.labeled_expr("condition", condition)?;
+                    dumper.labeled_block("body", block)
+                })
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                block,
+            } => {
+                self.line(format_args!("for"))?;
+                self.nested(|dumper| {
+                    if let Some(initializer) = initializer {
+                        dumper.line(format_args!("initializer"))?;
+                        dumper.nested(|dumper| dumper.stmt(initializer))?;
+                    }
+                    if let Some(condition) = condition {
+                        dumper.labeled_expr("condition", condition)?;
+                    }
+                    if let Some(increment) = increment {
+                        dumper.labeled_expr("increment", increment)?;
+                    }
+
+                    dumper.labeled_block("body", block)
+                })
+            }
+            Stmt::Continue(_) => self.line(format_args!("continue")),
+            Stmt::Break(_) => self.line(format_args!("break")),
+            Stmt::Destructure { names, value } => {
+                self.line(format_args!(
+                    "let ({})",
+                    names
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))?;
+                self.nested(|dumper| dumper.expr(value))
+            }
+            Stmt::Label(label) => self.line(format_args!("label {label}")),
+            Stmt::Goto(label) => self.line(format_args!("goto {label}")),
+            Stmt::Defer(expr) => self.labeled_expr("defer", expr),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> fmt::Result {
+        match &expr.kind {
+            ExprKind::Binary { op, left, right } => {
+                self.line(format_args!("binary {op:?}"))?;
+                self.nested(|dumper| {
+                    dumper.expr(left)?;
+                    dumper.expr(right)
+                })
+            }
+            ExprKind::Unary { op, expr } => {
+                self.line(format_args!("unary {op:?}"))?;
+                self.nested(|dumper| dumper.expr(expr))
+            }
+            ExprKind::Cast { expr, ty } => {
+                self.line(format_args!("cast {ty}"))?;
+                self.nested(|dumper| dumper.expr(expr))
+            }
+            ExprKind::Lit(lit) => match lit {
+                ExprLit::Int(value) => self.line(format_args!("int {value}")),
+                ExprLit::UInt(value) => self.line(format_args!("uint {value}")),
+                ExprLit::Suffixed(value, ty) => self.line(format_args!("uint {value}{ty}")),
+                ExprLit::Bool(value) => self.line(format_args!("bool {value}")),
+                ExprLit::String(value) => self.line(format_args!("string {value:?}")),
+                ExprLit::Null => self.line(format_args!("null")),
+            },
+            ExprKind::Ident(name) => self.line(format_args!("ident {name}")),
+            ExprKind::Struct { name, fields } => {
+                self.line(format_args!("struct {name}"))?;
+                self.nested(|dumper| {
+                    fields
+                        .iter()
+                        .try_for_each(|(field, expr)| dumper.labeled_expr(field, expr))
+                })
+            }
+            ExprKind::Array(exprs) => {
+                self.line(format_args!("array"))?;
+                self.nested(|dumper| exprs.iter().try_for_each(|expr| dumper.expr(expr)))
+            }
+            ExprKind::Tuple(exprs) => {
+                self.line(format_args!("tuple"))?;
+                self.nested(|dumper| exprs.iter().try_for_each(|expr| dumper.expr(expr)))
+            }
+            ExprKind::Field { expr, field } => {
+                self.line(format_args!("field {field}"))?;
+                self.nested(|dumper| dumper.expr(expr))
+            }
+            ExprKind::StructMethod {
+                expr,
+                method,
+                arguments,
+            } => {
+                self.line(format_args!("method {method}"))?;
+                self.nested(|dumper| {
+                    dumper.expr(expr)?;
+                    arguments.iter().try_for_each(|arg| dumper.expr(arg))
+                })
+            }
+            ExprKind::ArrayAccess { expr, index } => {
+                self.line(format_args!("index"))?;
+                self.nested(|dumper| {
+                    dumper.expr(expr)?;
+                    dumper.expr(index)
+                })
+            }
+            ExprKind::FunctionCall { expr, arguments } => {
+                self.line(format_args!("call"))?;
+                self.nested(|dumper| {
+                    dumper.expr(expr)?;
+                    arguments.iter().try_for_each(|arg| dumper.expr(arg))
+                })
+            }
+            ExprKind::MacroCall { name, tokens } => {
+                let tokens = tokens
+                    .iter()
+                    .map(|token| token.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                self.line(format_args!("macro {name}!({tokens})"))
+            }
+            ExprKind::InlineAsm(code) => self.line(format_args!("asm {code:?}")),
+            ExprKind::AlignOf(ty) => self.line(format_args!("alignof {ty}")),
+            ExprKind::SizeOf(ty) => self.line(format_args!("sizeof {ty}")),
+            ExprKind::Block(block) => self.labeled_block("block", block),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dump;
+    use crate::{diagnostics::Diagnostics, lexer::Lexer, parser::Parser};
+    use indoc::indoc;
+
+    #[test]
+    fn dump_items() {
+        let input = r#"
+            struct Point {
+                x: u8;
+                y: u8;
+            }
+
+            let counter: u32 = 0;
+
+            extern fn printf(fmt: *u8, ...) -> i32;
+
+            fn main() -> u8 {
+                let p: Point = Point { x: 1, y: 2 };
+                let arr: [2]u8 = [3, 4];
+                static let calls: u8;
+
+                if p.x < arr[0] {
+                    printf("%d", 1);
+                } else {
+                    return 0;
+                }
+
+                for let i: u8 = 0; i < 2; i = i + 1 {
+                    continue;
+                }
+
+                return -(p.y as i8) as u8;
+            }
+        "#;
+
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert_eq!(
+            Dump(&items).to_string(),
+            indoc! {r#"
+                struct Point
+                  x: u8
+                  y: u8
+                global counter: u32
+                  uint 0
+                extern fn printf(fmt: *u8, ...) -> i32;
+                fn main() -> u8
+                  let p: Point
+                    struct Point
+                      x
+                        uint 1
+                      y
+                        uint 2
+                  let arr: [2]u8
+                    array
+                      uint 3
+                      uint 4
+                  let static calls: u8
+                  if
+                    condition
+                      binary LessThan
+                        field x
+                          ident p
+                        index
+                          ident arr
+                          uint 0
+                    then
+                      call
+                        ident printf
+                        string "%d"
+                        uint 1
+                    else
+                      return
+                        uint 0
+                  for
+                    initializer
+                      let i: u8
+                        uint 0
+                    condition
+                      binary LessThan
+                        ident i
+                        uint 2
+                    increment
+                      binary Assign
+                        ident i
+                        binary Add
+                          ident i
+                          uint 1
+                    body
+                      continue
+                  return
+                    cast u8
+                      unary Negative
+                        cast i8
+                          field y
+                            ident p
+            "#}
+        );
+    }
+}
@@ -1,3 +1,7 @@
+pub mod const_eval;
+pub mod dump;
+pub mod unused;
+
 use crate::lexer::{span::Span, Token, TokenKind};
 use derive_more::derive::Display;
 use thiserror::Error;
@@ -6,13 +10,22 @@ use thiserror::Error;
 pub struct Variable {
     pub ty: Ty,
     pub name: String,
+    /// Span of the name
+    pub span: Span,
     pub value: Option<Expr>,
+    pub static_: bool,
+    /// Every thread has its own copy, only globals can be `thread_local`
+    pub thread_local: bool,
+    /// Exported from the object file, only globals can be `pub`
+    pub pub_: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub open_brace: Span,
     pub stmts: Vec<Stmt>,
+    /// Trailing expression without a semicolon a block expression evaluates to
+    pub expr: Option<Box<Expr>>,
     pub close_brace: Span,
 }
 
@@ -20,15 +33,42 @@ pub struct Block {
 pub enum Item {
     Global(Variable),
     Fn {
+        pub_: bool,
+        /// Without a prologue or an epilogue, the body is only inline assembly
+        naked: bool,
+        /// Never returns to its caller
+        noreturn: bool,
         ret_ty: Ty,
         name: String,
+        /// Span of the name
+        span: Span,
         params: Vec<(String, Ty)>,
+        variadic: bool,
+        /// `None` for `extern` functions
         block: Option<Block>,
     },
     Struct {
         name: String,
+        /// Span of the name
+        span: Span,
+        fields: Vec<(String, Ty)>,
+        /// `packed struct`, the fields aren't padded to their alignment
+        packed: bool,
+    },
+    /// Like a struct, but all the fields share the same storage
+    Union {
+        name: String,
+        /// Span of the name
+        span: Span,
         fields: Vec<(String, Ty)>,
     },
+    /// `type Name = ty;`, the name can be used wherever `ty` can
+    TypeAlias {
+        name: String,
+        /// Span of the name
+        span: Span,
+        ty: Ty,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -52,8 +92,20 @@ pub enum Stmt {
         increment: Option<Expr>,
         block: Block,
     },
-    Continue,
-    Break,
+    /// `continue;`, the span covers the whole statement
+    Continue(Span),
+    /// `break;`, the span covers the whole statement
+    Break(Span),
+    /// `let (a, b) = value;`, each name is bound to an element of the tuple
+    Destructure {
+        names: Vec<(String, Span)>,
+        value: Expr,
+    },
+    Label(String),
+    Goto(String),
+    /// `defer expr;`, the expression is evaluated when the enclosing block
+    /// exits, after the ones deferred later
+    Defer(Expr),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -84,6 +136,7 @@ pub enum ExprKind {
         fields: Vec<(String, Expr)>,
     },
     Array(Vec<Expr>),
+    Tuple(Vec<Expr>),
     Field {
         expr: Box<Expr>,
         field: String,
@@ -105,12 +158,18 @@ pub enum ExprKind {
         name: String,
         tokens: Vec<Token>,
     },
+    InlineAsm(String),
+    AlignOf(Ty),
+    SizeOf(Ty),
+    Block(Block),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExprLit {
     Int(i64),
     UInt(u64),
+    /// Integer literal with a type suffix, like `10u8`
+    Suffixed(u64, Ty),
     Bool(bool),
     String(String),
     Null,
@@ -155,24 +214,47 @@ pub enum Ty {
     Int(IntTy),
     UInt(UintTy),
     Ident(String),
-    #[display("*{_0}")]
-    Ptr(Box<Ty>),
-    #[display("{ty}[{len}]")]
+    /// A `restrict` pointer promises not to alias any other pointer, which is
+    /// only kept around for now and doesn't change the generated code
+    #[display("*{}{_0}", if *_1 { "restrict " } else { "" })]
+    Ptr(Box<Ty>, bool),
+    #[display("[{len}]{ty}")]
     Array {
         ty: Box<Ty>,
         len: usize,
     },
-    #[display("fn ({}) -> {_1}",
+    #[display("fn({}) -> {_1}",
         _0
             .iter()
             .map(|type_| type_.to_string())
-            .collect::<String>()
+            .collect::<Vec<_>>()
+            .join(", ")
     )]
     Fn(Vec<Ty>, Box<Ty>),
+    #[display("({})",
+        _0
+            .iter()
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    Tuple(Vec<Ty>),
     #[display("infer")]
     Infer,
 }
 
+impl Ty {
+    /// Whether the integer literal `lit`, negated if `negative`, is in range of
+    /// this type.
+    pub fn fits_int_lit(&self, lit: u64, negative: bool) -> bool {
+        match self {
+            Self::Int(ty) => ty.fits_lit(lit, negative),
+            Self::UInt(ty) => ty.fits_lit(lit, negative),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum OpParseError {
     #[error("Failed to parse binary operator from {0}")]
@@ -191,6 +273,7 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    Rem,
     Equal,
     NotEqual,
     LessThan,
@@ -215,6 +298,7 @@ impl TryFrom<&TokenKind> for BinOp {
             TokenKind::Plus => Ok(Self::Add),
             TokenKind::Minus => Ok(Self::Sub),
             TokenKind::Slash => Ok(Self::Div),
+            TokenKind::Percent => Ok(Self::Rem),
             TokenKind::Equal => Ok(Self::Equal),
             TokenKind::NotEqual => Ok(Self::NotEqual),
             TokenKind::LessThan => Ok(Self::LessThan),
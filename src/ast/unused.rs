@@ -0,0 +1,369 @@
+use super::{BinOp, Block, Expr, ExprKind, Item, Stmt};
+use crate::{
+    diagnostics::{Diagnostic, Diagnostics},
+    lexer::{span::Span, TokenKind},
+};
+use std::collections::HashMap;
+
+#[derive(Debug)]
+struct Local {
+    name: String,
+    span: Span,
+    static_: bool,
+    reads: usize,
+    /// Values assigned to the local which were overwritten before any read
+    overwritten: Vec<Span>,
+}
+
+/// Lint warning about locals which are never read and values assigned to a
+/// local which are overwritten before being read. Locals whose name starts
+/// with `_` are not reported.
+pub struct Unused<'a, 'src> {
+    diag: &'a mut Diagnostics<'src>,
+    locals: Vec<Local>,
+    /// Parameters shadow outer names but aren't linted, so they map to `None`
+    scopes: Vec<HashMap<String, Option<usize>>>,
+    /// Last value assigned to a local which hasn't been read yet
+    pending: HashMap<usize, Span>,
+}
+
+impl<'a, 'src> Unused<'a, 'src> {
+    pub fn check(items: &[Item], diag: &'a mut Diagnostics<'src>) {
+        items.iter().for_each(|item| Self::item(item, diag));
+    }
+
+    fn item(item: &Item, diag: &mut Diagnostics) {
+        if let Item::Fn {
+            params,
+            block: Some(block),
+            ..
+        } = item
+        {
+            let mut unused = Unused {
+                diag,
+                locals: Vec::new(),
+                scopes: vec![params
+                    .iter()
+                    .map(|(name, _)| (name.clone(), None))
+                    .collect()],
+                pending: HashMap::new(),
+            };
+
+            unused.block(block);
+            unused.report();
+        }
+    }
+
+    fn report(self) {
+        for local in self.locals {
+            if local.name.starts_with('_') {
+                continue;
+            }
+
+            if local.reads == 0 {
+                self.diag
+                    .warning(Diagnostic::UnusedVariable(local.name), local.span);
+            } else {
+                for span in local.overwritten {
+                    self.diag
+                        .warning(Diagnostic::OverwrittenAssignment(local.name.clone()), span);
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        block.stmts.iter().for_each(|stmt| self.stmt(stmt));
+        if let Some(expr) = &block.expr {
+            self.expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    /// Code that runs conditionally or repeatedly. Values assigned before it
+    /// may or may not be overwritten in it and values assigned in it may or
+    /// may not be read after it, so neither is reported.
+    fn branch(&mut self, f: impl FnOnce(&mut Self)) {
+        let pending = std::mem::take(&mut self.pending);
+        let reads: Vec<_> = self.locals.iter().map(|local| local.reads).collect();
+
+        f(self);
+
+        self.pending = pending
+            .into_iter()
+            .filter(|(id, _)| self.locals[*id].reads == reads[*id])
+            .collect();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Local(var) => {
+                if let Some(expr) = &var.value {
+                    self.expr(expr);
+                }
+
+                let id = self.declare(&var.name, &var.span, var.static_);
+
+                if let Some(expr) = &var.value {
+                    self.write(id, expr.span.clone());
+                }
+            }
+            Stmt::Destructure { names, value } => {
+                self.expr(value);
+
+                for (name, span) in names {
+                    let id = self.declare(name, span, false);
+
+                    self.write(id, value.span.clone());
+                }
+            }
+            // Nested functions are checked on their own
+            Stmt::Item(item) => Self::item(item, self.diag),
+            // Reads of a deferred expression are counted where it's deferred
+            Stmt::Expr(expr) | Stmt::Defer(expr) => self.expr(expr),
+            Stmt::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+
+                // Nothing after it is reachable
+                self.pending.clear();
+            }
+            // A label can be reached from anywhere in the function
+            Stmt::Continue(_) | Stmt::Break(_) | Stmt::Goto(_) | Stmt::Label(_) => {
+                self.pending.clear()
+            }
+            Stmt::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.expr(condition);
+                self.branch(|unused| unused.block(consequence));
+
+                if let Some(alternative) = alternative {
+                    self.branch(|unused| unused.block(alternative));
+                }
+            }
+            Stmt::While { condition, block } => {
+                self.expr(condition);
+                self.branch(|unused| unused.block(block));
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                block,
+            } => {
+                self.scopes.push(HashMap::new());
+
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer);
+                }
+
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+
+                self.branch(|unused| {
+                    unused.block(block);
+
+                    if let Some(increment) = increment {
+                        unused.expr(increment);
+                    }
+                });
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Binary {
+                op: BinOp::Assign,
+                left,
+                right,
+            } => {
+                self.expr(right);
+
+                match &left.kind {
+                    ExprKind::Ident(name) => {
+                        if let Some(id) = self.resolve(name) {
+                            self.write(id, right.span.clone());
+                        }
+                    }
+                    _ => self.expr(left),
+                }
+            }
+            // The right hand side isn't always evaluated
+            ExprKind::Binary {
+                op: BinOp::LogicalAnd | BinOp::LogicalOr,
+                left,
+                right,
+            } => {
+                self.expr(left);
+                self.branch(|unused| unused.expr(right));
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            // Taking the address counts as a read, the local can be read through the pointer
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Field { expr, .. } => self.expr(expr),
+            ExprKind::Ident(name) => self.read(name),
+            ExprKind::Struct { fields, .. } => fields.iter().for_each(|(_, expr)| self.expr(expr)),
+            ExprKind::Array(items) | ExprKind::Tuple(items) => {
+                items.iter().for_each(|item| self.expr(item))
+            }
+            ExprKind::StructMethod {
+                expr, arguments, ..
+            }
+            | ExprKind::FunctionCall { expr, arguments } => {
+                self.expr(expr);
+                arguments.iter().for_each(|argument| self.expr(argument));
+            }
+            ExprKind::ArrayAccess { expr, index } => {
+                self.expr(expr);
+                self.expr(index);
+            }
+            // Macro arguments aren't parsed yet, any identifier among them may be a read
+            ExprKind::MacroCall { tokens, .. } => {
+                for token in tokens {
+                    if let TokenKind::Ident(name) = &token.kind {
+                        self.read(name);
+                    }
+                }
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => (),
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .flatten()
+    }
+
+    fn read(&mut self, name: &str) {
+        if let Some(id) = self.resolve(name) {
+            self.locals[id].reads += 1;
+            self.pending.remove(&id);
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: &Span, static_: bool) -> usize {
+        let id = self.locals.len();
+
+        self.locals.push(Local {
+            name: name.to_owned(),
+            span: span.clone(),
+            static_,
+            reads: 0,
+            overwritten: Vec::new(),
+        });
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_owned(), Some(id));
+
+        id
+    }
+
+    fn write(&mut self, id: usize, span: Span) {
+        // Statics keep their value between calls, so it can be read by the next one
+        if self.locals[id].static_ {
+            return;
+        }
+
+        if let Some(span) = self.pending.insert(id, span) {
+            self.locals[id].overwritten.push(span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Unused;
+    use crate::{diagnostics::Diagnostics, lexer::Lexer, parser::Parser};
+
+    fn warnings(source: &str) -> String {
+        let mut diagnostics = Diagnostics::new(source);
+        let items = Parser::new(Lexer::new(source), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        Unused::check(&items, &mut diagnostics);
+
+        diagnostics.to_string()
+    }
+
+    #[test]
+    fn unused() {
+        let warnings = warnings(
+            "fn main(argc: u8) -> u8 {
+                let a: u8 = 1;
+                let _b: u8 = 2;
+                let c: u8;
+                let d: u8 = 3;
+
+                c = d;
+                if (d == 3) {
+                    let a: u8 = 4;
+
+                    return a;
+                }
+
+                return argc;
+            }",
+        );
+
+        assert_eq!(warnings.matches("unused variable `a`").count(), 1);
+        assert!(warnings.contains(":2:21\n"));
+        assert!(warnings.contains("unused variable `c`"));
+        assert!(!warnings.contains("`_b`"));
+        assert!(!warnings.contains("`d`"));
+    }
+
+    #[test]
+    fn overwritten() {
+        let warnings = warnings(
+            "fn main() -> u8 {
+                let a: u8 = 1;
+                let b: u8 = 2;
+                let c: u8 = 3;
+
+                if (true) {
+                    a = 4;
+                    b = 5;
+                } else {
+                    print!(b);
+                }
+                a = 6;
+                b = 7;
+                c = c + 1;
+
+                return a + b + c;
+            }",
+        );
+
+        assert_eq!(
+            warnings
+                .matches("value assigned to `a` is overwritten before being read")
+                .count(),
+            1
+        );
+        assert!(warnings.contains(":2:29\n"));
+        assert!(!warnings.contains("`b`"));
+        assert!(!warnings.contains("`c`"));
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Whether the generated code was taken from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// No cache directory was given
+    Disabled,
+    /// The code was generated and stored in the cache
+    Miss,
+    /// The code was read from the cache, nothing was compiled
+    Hit,
+}
+
+/// Assembly generated for a translation unit, stored in a directory as one
+/// `<key>.s` file per unit. The key hashes the source code of every file, the
+/// flags affecting code generation and the version and build of the compiler,
+/// so a change to any of them misses the cache.
+#[derive(Debug)]
+pub struct Cache {
+    path: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: &Path, sources: &[String], flags: impl Hash) -> Self {
+        let mut hasher = Fnv1a::new();
+
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        build_id().hash(&mut hasher);
+        sources.hash(&mut hasher);
+        flags.hash(&mut hasher);
+
+        Self {
+            path: dir.join(format!("{:016x}.s", hasher.finish())),
+        }
+    }
+
+    /// Cached assembly, `None` if there's none or it can't be read
+    pub fn get(&self) -> Option<Vec<u8>> {
+        std::fs::read(&self.path).ok()
+    }
+
+    pub fn put(&self, code: &[u8]) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(&self.path, code)
+    }
+}
+
+/// Identifies the build of the running compiler by when its executable was
+/// last modified, `None` if that's unknown. Rebuilding the compiler without
+/// bumping its version changes the key, so code generated by an older build
+/// isn't reused.
+fn build_id() -> Option<SystemTime> {
+    std::env::current_exe()
+        .and_then(|exe| exe.metadata())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// 64 bit FNV-1a. Unlike `DefaultHasher`, its output is specified and doesn't
+/// change between Rust releases, keys stay the same across toolchains.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cache, Fnv1a};
+    use std::{hash::Hasher, path::Path};
+
+    #[test]
+    fn fnv1a() {
+        let tests: [(&[u8], u64); 3] = [
+            (b"", 0xcbf29ce484222325),
+            (b"a", 0xaf63dc4c8601ec8c),
+            (b"foobar", 0x85944171f73967e8),
+        ];
+
+        for (bytes, expected) in tests {
+            let mut hasher = Fnv1a::new();
+
+            hasher.write(bytes);
+            assert_eq!(hasher.finish(), expected, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn key() {
+        let dir = Path::new("cache");
+        let sources = [String::from("fn main() {}")];
+        let key = Cache::new(dir, &sources, 0).path;
+
+        assert_eq!(key, Cache::new(dir, &sources, 0).path);
+        assert_ne!(key, Cache::new(dir, &sources, 1).path);
+        assert_ne!(
+            key,
+            Cache::new(dir, &[String::from("fn main() { }")], 0).path
+        );
+    }
+}
@@ -3,14 +3,26 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("Register was double freed")]
-    DoubleFree,
+    #[error("Register {0} was double freed")]
+    DoubleFree(Register),
+    #[error("Register {0} isn't managed by the allocator")]
+    Unknown(Register),
     #[error("Ran out of registers, whoops!")]
     RanOutOfRegisters,
     #[error("Register {0} is already in use")]
     AlreadyInUse(Register),
 }
 
+/// How long a value kept in a register has to survive, picks the kind of
+/// register which doesn't need saving for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// Dead before the next call, a caller saved register is free to use
+    Temporary,
+    /// Has to survive a call, a callee saved register isn't clobbered by it
+    AcrossCalls,
+}
+
 #[derive(Debug, Clone)]
 pub struct RegisterAllocator {
     registers: Vec<Register>,
@@ -37,6 +49,21 @@ impl RegisterAllocator {
         Err(Error::RanOutOfRegisters)
     }
 
+    /// Allocates a register preferring callee saved ones for values living
+    /// across calls and caller saved ones otherwise, falling back to the other
+    /// kind when the preferred one runs out.
+    pub fn alloc_for(&mut self, size: OperandSize, liveness: Liveness) -> Result<Register, Error> {
+        let prefer_callee_saved = liveness == Liveness::AcrossCalls;
+        let i = (0..self.registers.len())
+            .filter(|i| !self.used.contains(i))
+            .min_by_key(|&i| self.registers[i].callee_saved() != prefer_callee_saved)
+            .ok_or(Error::RanOutOfRegisters)?;
+
+        self.used.push(i);
+
+        Ok(self.registers[i].resize(size))
+    }
+
     pub fn alloc_nth(&mut self, n: usize) -> Result<Register, Error> {
         if self.used.contains(&n) {
             Err(Error::AlreadyInUse(self.registers[n].clone()))
@@ -47,18 +74,22 @@ impl RegisterAllocator {
         }
     }
 
+    /// Frees `r`, which may be any size of an allocated register. Freeing a
+    /// register twice or one the allocator doesn't hand out is an error, going
+    /// on would let two values share a register.
     pub fn free(&mut self, r: Register) -> Result<(), Error> {
-        for (i, register) in self.registers.iter().enumerate() {
-            if &r == register {
-                if let Some(i) = self.used.iter().position(|el| el == &i) {
-                    self.used.remove(i);
-
-                    break;
-                } else {
-                    return Err(Error::DoubleFree);
-                }
-            }
-        }
+        let i = self
+            .registers
+            .iter()
+            .position(|register| register == &r.resize(OperandSize::Qword))
+            .ok_or(Error::Unknown(r))?;
+        let used = self
+            .used
+            .iter()
+            .position(|&used| used == i)
+            .ok_or(Error::DoubleFree(r))?;
+
+        self.used.remove(used);
 
         Ok(())
     }
@@ -72,6 +103,8 @@ impl RegisterAllocator {
     }
 
     pub fn is_used(&self, r: &Register) -> bool {
+        let r = &r.resize(OperandSize::Qword);
+
         match self
             .registers
             .iter()
@@ -87,3 +120,80 @@ impl RegisterAllocator {
         &self.used
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Liveness, RegisterAllocator};
+    use crate::codegen::amd64_asm::{register::Register, OperandSize};
+
+    #[test]
+    fn alloc_for_liveness() {
+        let mut allocator = RegisterAllocator::new(vec![
+            Register::R13,
+            Register::R12,
+            Register::R11,
+            Register::R10,
+        ]);
+
+        assert_eq!(
+            allocator
+                .alloc_for(OperandSize::Qword, Liveness::Temporary)
+                .unwrap(),
+            Register::R11
+        );
+        assert_eq!(
+            allocator
+                .alloc_for(OperandSize::Dword, Liveness::AcrossCalls)
+                .unwrap(),
+            Register::R13d
+        );
+        assert_eq!(
+            allocator
+                .alloc_for(OperandSize::Qword, Liveness::Temporary)
+                .unwrap(),
+            Register::R10
+        );
+        // Caller saved registers ran out
+        assert_eq!(
+            allocator
+                .alloc_for(OperandSize::Qword, Liveness::Temporary)
+                .unwrap(),
+            Register::R12
+        );
+        assert!(allocator
+            .alloc_for(OperandSize::Qword, Liveness::AcrossCalls)
+            .is_err());
+    }
+
+    #[test]
+    fn free_resized() {
+        let mut allocator = RegisterAllocator::new(vec![Register::R15]);
+        let r = allocator.alloc(OperandSize::Byte).unwrap();
+
+        assert_eq!(r, Register::R15b);
+        assert!(allocator.is_used(&r));
+        allocator.free(r).unwrap();
+        assert!(!allocator.is_used(&Register::R15));
+        assert_eq!(allocator.alloc(OperandSize::Dword).unwrap(), Register::R15d);
+    }
+
+    #[test]
+    fn bad_free() {
+        let mut allocator = RegisterAllocator::new(vec![Register::R15, Register::R14]);
+        let r = allocator.alloc(OperandSize::Qword).unwrap();
+        let other = allocator.alloc(OperandSize::Word).unwrap();
+
+        allocator.free(r).unwrap();
+        let err = allocator.free(r).unwrap_err();
+        assert!(matches!(err, Error::DoubleFree(Register::R15)));
+        assert_eq!(err.to_string(), "Register r15 was double freed");
+        assert!(matches!(
+            allocator.free(Register::Eax),
+            Err(Error::Unknown(Register::Eax))
+        ));
+        // The other register is still allocated
+        assert!(allocator.is_used(&other));
+        assert_eq!(allocator.alloc(OperandSize::Qword).unwrap(), Register::R15);
+        assert!(allocator.alloc(OperandSize::Qword).is_err());
+    }
+}
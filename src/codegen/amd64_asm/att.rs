@@ -0,0 +1,312 @@
+//! Renders the Intel syntax assembly the generator emits in AT&T syntax. Only
+//! the instruction forms the generator uses are understood, inline assembly is
+//! kept in Intel syntax by the directives surrounding it.
+
+/// Suffix of the mnemonic operating on the register, `None` for a symbol
+fn register_suffix(name: &str) -> Option<char> {
+    match name {
+        "al" | "bl" | "cl" | "dl" | "sil" | "dil" | "bpl" | "spl" => Some('b'),
+        "ax" | "bx" | "cx" | "dx" | "si" | "di" | "bp" | "sp" => Some('w'),
+        "eax" | "ebx" | "ecx" | "edx" | "esi" | "edi" | "ebp" | "esp" => Some('l'),
+        "rax" | "rbx" | "rcx" | "rdx" | "rsi" | "rdi" | "rbp" | "rsp" => Some('q'),
+        _ => {
+            let rest = name.strip_prefix('r')?;
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+
+            match rest[..digits].parse::<u8>() {
+                Ok(8..=15) => match &rest[digits..] {
+                    "" => Some('q'),
+                    "d" => Some('l'),
+                    "w" => Some('w'),
+                    "b" => Some('b'),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+    }
+}
+
+fn ptr_suffix(size: &str) -> Option<char> {
+    match size {
+        "byte" => Some('b'),
+        "word" => Some('w'),
+        "dword" => Some('l'),
+        "qword" => Some('q'),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    Register(String),
+    Immediate(String),
+    /// Size suffix, if it's told, and the address in AT&T syntax
+    Memory(Option<char>, String),
+    /// A jump or call target
+    Symbol(String),
+}
+
+impl Operand {
+    fn parse(operand: &str) -> Self {
+        if register_suffix(operand).is_some() {
+            return Self::Register(operand.to_owned());
+        }
+        if let Some(label) = operand.strip_prefix("offset ") {
+            return Self::Immediate(label.to_owned());
+        }
+        if operand.parse::<i128>().is_ok() {
+            return Self::Immediate(operand.to_owned());
+        }
+
+        let (size, addr) = match operand.split_once(" ptr ") {
+            Some((size, addr)) => (ptr_suffix(size), addr),
+            None => (None, operand),
+        };
+
+        if let Some(addr) = addr
+            .strip_prefix('[')
+            .and_then(|addr| addr.strip_suffix(']'))
+        {
+            Self::Memory(size, Self::address(addr))
+        } else if let Some(offset) = addr.strip_prefix("fs:") {
+            Self::Memory(size, format!("%fs:{offset}"))
+        } else {
+            Self::Symbol(operand.to_owned())
+        }
+    }
+
+    /// `base + index * scale ± displacement` as `displacement(base,index,scale)`,
+    /// where the displacement may be made of symbols and a number
+    fn address(addr: &str) -> String {
+        let mut registers = Vec::new();
+        let mut scale = None;
+        let mut displacement = String::new();
+        let mut sign = "+";
+        let mut tokens = addr.split(' ');
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "+" | "-" => sign = token,
+                "*" => scale = tokens.next(),
+                _ if token == "rip" || register_suffix(token).is_some() => {
+                    registers.push(format!("%{token}"))
+                }
+                _ => {
+                    if !displacement.is_empty() || sign == "-" {
+                        displacement.push_str(sign);
+                    }
+                    displacement.push_str(token);
+                }
+            }
+        }
+
+        match (registers.as_slice(), scale) {
+            ([], _) => displacement,
+            ([base], _) => format!("{displacement}({base})"),
+            ([base, index], None) => format!("{displacement}({base},{index})"),
+            ([base, index], Some(scale)) => format!("{displacement}({base},{index},{scale})"),
+            _ => unreachable!("invalid address `{addr}`"),
+        }
+    }
+
+    fn suffix(&self) -> Option<char> {
+        match self {
+            Self::Register(name) => register_suffix(name),
+            Self::Memory(size, _) => *size,
+            Self::Immediate(_) | Self::Symbol(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Register(name) => write!(f, "%{name}"),
+            Self::Immediate(value) => write!(f, "${value}"),
+            Self::Memory(_, addr) => write!(f, "{addr}"),
+            Self::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+/// One instruction, without its leading tab and trailing comment
+fn instruction(instruction: &str) -> String {
+    let (mnemonic, operands) = instruction.split_once(' ').unwrap_or((instruction, ""));
+    let operands: Vec<_> = operands
+        .split(", ")
+        .filter(|operand| !operand.is_empty())
+        .map(Operand::parse)
+        .collect();
+
+    let mnemonic = match mnemonic {
+        // Prefixed string instructions tell their size already
+        "rep" => return instruction.to_owned(),
+        "cbw" => "cbtw".to_owned(),
+        "cwd" => "cwtd".to_owned(),
+        "cdq" => "cltd".to_owned(),
+        "cdqe" => "cltq".to_owned(),
+        "cqo" => "cqto".to_owned(),
+        "movzx" | "movsx" | "movsxd" => {
+            let [dest, src] = operands.as_slice() else {
+                unreachable!("`{instruction}` doesn't have two operands");
+            };
+            let (src_size, dest_size) = (src.suffix().unwrap(), dest.suffix().unwrap());
+
+            if mnemonic == "movzx" {
+                format!("movz{src_size}{dest_size}")
+            } else {
+                format!("movs{src_size}{dest_size}")
+            }
+        }
+        _ if mnemonic == "call" || mnemonic.starts_with('j') => {
+            let target = match operands.as_slice() {
+                [target @ Operand::Symbol(_)] => target.to_string(),
+                [target] => format!("*{target}"),
+                _ => unreachable!("`{instruction}` doesn't have a single operand"),
+            };
+
+            return format!("{mnemonic} {target}");
+        }
+        _ if mnemonic.starts_with("set") => mnemonic.to_owned(),
+        _ => match operands.iter().find_map(Operand::suffix) {
+            Some(suffix) => format!("{mnemonic}{suffix}"),
+            None => mnemonic.to_owned(),
+        },
+    };
+
+    let operands: Vec<_> = operands
+        .iter()
+        .rev()
+        .map(|operand| operand.to_string())
+        .collect();
+
+    if operands.is_empty() {
+        mnemonic
+    } else {
+        format!("{mnemonic} {}", operands.join(", "))
+    }
+}
+
+/// Directives whose first argument is a register
+const CFI_REGISTER_DIRECTIVES: [&str; 3] = [".cfi_offset", ".cfi_def_cfa_register", ".cfi_def_cfa"];
+
+/// Rewrites the instructions of `intel` in AT&T syntax, the one `as` reads
+/// unless told otherwise. Labels and directives stay as they are, apart from the
+/// registers of call frame information directives, and so does the code between
+/// `.intel_syntax` and `.att_syntax`.
+pub fn translate(intel: &str) -> String {
+    let mut att = String::with_capacity(intel.len());
+    let mut verbatim = false;
+
+    for line in intel.lines() {
+        let Some(code) = line.strip_prefix('\t') else {
+            att.push_str(line);
+            att.push('\n');
+            continue;
+        };
+
+        if code.starts_with(".intel_syntax") {
+            verbatim = true;
+        } else if code.starts_with(".att_syntax") {
+            verbatim = false;
+        }
+
+        att.push('\t');
+        if verbatim {
+            att.push_str(code);
+        } else if let Some((directive, register)) =
+            CFI_REGISTER_DIRECTIVES.iter().find_map(|directive| {
+                Some((directive, code.strip_prefix(directive)?.strip_prefix(' ')?))
+            })
+        {
+            att.push_str(&format!("{directive} %{register}"));
+        } else if code.starts_with('.') {
+            att.push_str(code);
+        } else {
+            match code.split_once(" # ") {
+                Some((code, comment)) => {
+                    att.push_str(&format!("{} # {comment}", instruction(code)));
+                }
+                None => att.push_str(&instruction(code)),
+            }
+        }
+        att.push('\n');
+    }
+
+    att
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instruction, translate};
+
+    #[test]
+    fn operands() {
+        for (intel, att) in [
+            ("mov rax, 5", "movq $5, %rax"),
+            ("mov eax, -1", "movl $-1, %eax"),
+            ("mov r10, offset .L3", "movq $.L3, %r10"),
+            ("mov byte ptr [rbp - 9], r12b", "movb %r12b, -9(%rbp)"),
+            ("mov qword ptr [rsp], 0", "movq $0, (%rsp)"),
+            ("mov rsi, [rsp + 56]", "movq 56(%rsp), %rsi"),
+            ("lea r15, [r14 + r13 * 8]", "leaq (%r14,%r13,8), %r15"),
+            (
+                "lea rax, [rip + main.buf + 8]",
+                "leaq main.buf+8(%rip), %rax",
+            ),
+            (
+                "mov rax, qword ptr [rip + x@GOTPCREL]",
+                "movq x@GOTPCREL(%rip), %rax",
+            ),
+            ("mov r12, qword ptr fs:0", "movq %fs:0, %r12"),
+            ("lea r12, [r12 + x@tpoff]", "leaq x@tpoff(%r12), %r12"),
+            ("movzx eax, byte ptr [rdi]", "movzbl (%rdi), %eax"),
+            ("movsx r12, r12w", "movswq %r12w, %r12"),
+            ("imul r12, r13, 24", "imulq $24, %r13, %r12"),
+            ("shl r12, cl", "shlq %cl, %r12"),
+            ("sete al", "sete %al"),
+            ("cqo", "cqto"),
+            ("rep stosq", "rep stosq"),
+            ("ret", "ret"),
+            ("call fib@PLT", "call fib@PLT"),
+            ("call r12", "call *%r12"),
+            ("jmp .L4", "jmp .L4"),
+            ("jne .L4", "jne .L4"),
+        ] {
+            assert_eq!(instruction(intel), att, "{intel}");
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let intel = [
+            "main:",
+            "\t.cfi_def_cfa_register rbp",
+            "\tpush rbp # f(x)",
+            "\t.intel_syntax noprefix",
+            "\tmov rax, 60",
+            "\t.att_syntax prefix",
+            "\t.ascii \"a, b\"",
+            "",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            translate(&intel),
+            [
+                "main:",
+                "\t.cfi_def_cfa_register %rbp",
+                "\tpushq %rbp # f(x)",
+                "\t.intel_syntax noprefix",
+                "\tmov rax, 60",
+                "\t.att_syntax prefix",
+                "\t.ascii \"a, b\"",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    ast::{BinOp, UnOp},
+    ir::{Block, Expr, ExprKind, Id, Stmt},
+};
+use std::collections::{HashMap, HashSet};
+
+/// Locals known to hold a constant at the point of the function the code is
+/// generated for. Statements are generated in order, so a constant assigned
+/// to a local is known until the local is assigned again or control flow
+/// joins with a path that could have changed it.
+///
+/// A local whose address is taken can be changed by a store through a
+/// pointer, and one assigned within an expression could be assigned
+/// conditionally, by the right operand of `&&` for example. Those are never
+/// known.
+#[derive(Debug, Default)]
+pub struct Constants {
+    values: HashMap<Id, i128>,
+    untracked: HashSet<Id>,
+}
+
+impl Constants {
+    pub fn new(block: &Block) -> Self {
+        let mut consts = Self::default();
+
+        consts.block(block, false);
+
+        consts
+    }
+
+    pub fn get(&self, id: Id) -> Option<i128> {
+        self.values.get(&id).copied()
+    }
+
+    /// Records the value assigned to the local, `None` if it isn't constant.
+    pub fn set(&mut self, id: Id, value: Option<i128>) {
+        match value {
+            Some(value) if !self.untracked.contains(&id) => {
+                self.values.insert(id, value);
+            }
+            _ => {
+                self.values.remove(&id);
+            }
+        }
+    }
+
+    /// Forgets every value, where control flow joins with a path whose
+    /// assignments aren't known, like the start of a loop or a label.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn values(&self) -> HashMap<Id, i128> {
+        self.values.clone()
+    }
+
+    /// Goes back to the values of another path, returning the current ones.
+    pub fn replace(&mut self, values: HashMap<Id, i128>) -> HashMap<Id, i128> {
+        std::mem::replace(&mut self.values, values)
+    }
+
+    /// Keeps only the values the other path agrees with, where two paths of
+    /// an `if` join.
+    pub fn join(&mut self, other: &HashMap<Id, i128>) {
+        self.values.retain(|id, value| other.get(id) == Some(value));
+    }
+
+    fn block(&mut self, block: &Block, in_expr: bool) {
+        for stmt in block.0 {
+            self.stmt(stmt, in_expr);
+        }
+
+        if let Some(expr) = &block.1 {
+            self.expr(expr, in_expr);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, in_expr: bool) {
+        match stmt {
+            Stmt::Local(var) => {
+                if let Some(expr) = &var.initializer {
+                    self.expr(expr, true);
+                }
+            }
+            // Only the assignment of an expression statement itself is tracked
+            Stmt::Expr(expr) => self.expr(expr, in_expr),
+            Stmt::Return(Some(expr)) => self.expr(expr, true),
+            Stmt::For(initializer, condition, increment, block) => {
+                for stmt in *initializer {
+                    self.stmt(stmt, in_expr);
+                }
+                for expr in condition.iter().chain(increment) {
+                    self.expr(expr, true);
+                }
+
+                self.block(block, in_expr);
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                self.expr(condition, true);
+                self.block(consequence, in_expr);
+
+                if let Some(alternative) = alternative {
+                    self.block(alternative, in_expr);
+                }
+            }
+            Stmt::Item(_)
+            | Stmt::Return(None)
+            | Stmt::Label(_)
+            | Stmt::Goto(_)
+            | Stmt::Continue
+            | Stmt::Break => (),
+        }
+    }
+
+    /// Walks an expression, `in_expr` if it's evaluated as part of another
+    /// one, where an assignment to a local makes it untracked.
+    fn expr(&mut self, expr: &Expr, in_expr: bool) {
+        match expr.kind {
+            ExprKind::Block(block) => self.block(&block, true),
+            ExprKind::Binary(BinOp::Assign, lhs, rhs) => {
+                if let ExprKind::Ident(id) = lhs.kind {
+                    if in_expr {
+                        self.untracked.insert(id);
+                    }
+                } else {
+                    self.expr(lhs, true);
+                }
+
+                self.expr(rhs, true);
+            }
+            ExprKind::Unary(UnOp::Address, operand) => match operand.kind {
+                ExprKind::Ident(id) => {
+                    self.untracked.insert(id);
+                }
+                _ => self.expr(operand, true),
+            },
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Itoa(lhs, rhs) => {
+                self.expr(lhs, true);
+                self.expr(rhs, true);
+            }
+            ExprKind::Unary(_, expr)
+            | ExprKind::Field(expr, _)
+            | ExprKind::Cast(expr, _)
+            | ExprKind::Assert(expr, _) => self.expr(expr, true),
+            ExprKind::Struct(fields) => fields.iter().for_each(|(_, expr)| self.expr(expr, true)),
+            ExprKind::Array(exprs) | ExprKind::Tuple(exprs) | ExprKind::Print(exprs) => {
+                exprs.iter().for_each(|expr| self.expr(expr, true))
+            }
+            ExprKind::Call(callee, args) => {
+                self.expr(callee, true);
+                args.iter().for_each(|arg| self.expr(arg, true));
+            }
+            ExprKind::Ident(_)
+            | ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => (),
+        }
+    }
+}
@@ -1,16 +1,20 @@
 mod allocator;
+mod att;
+mod consts;
 mod operand;
 mod register;
 
-use super::Codegen;
+use super::{Codegen, Overflow, Syntax};
 use crate::{
     ast::{BinOp, BitwiseOp, CmpOp, IntTy, OpParseError, UintTy, UnOp},
     ir::{Block, Expr, ExprKind, ExprLit, Id, Item, ItemFn, Node, Stmt, Ty, Variable},
+    lexer::span::Span,
     Context,
 };
-use allocator::RegisterAllocator;
+use allocator::{Liveness, RegisterAllocator};
+use consts::Constants;
 use derive_more::derive::Display;
-use indoc::formatdoc;
+use indoc::{formatdoc, indoc};
 use operand::{
     Base, Destination, EffectiveAddress, Immediate, ImmediateStrLitError, Memory, Offset, Source,
 };
@@ -18,17 +22,22 @@ use register::Register;
 use std::collections::HashMap;
 use thiserror::Error;
 
-struct LabelGenerator(usize);
+/// Labels are numbered per item and prefixed with its index, so an item gets
+/// the same ones whichever items are generated along with it
+struct LabelGenerator {
+    item: usize,
+    next: usize,
+}
 
 impl LabelGenerator {
-    pub fn new() -> Self {
-        Self(0)
+    pub fn new(item: usize) -> Self {
+        Self { item, next: 0 }
     }
 
     pub fn generate(&mut self) -> String {
-        let str = format!(".L{}", self.0);
+        let str = format!(".L{}_{}", self.item, self.next);
 
-        self.0 += 1;
+        self.next += 1;
 
         str
     }
@@ -74,22 +83,26 @@ pub enum Amd64AsmError {
     OpParse(#[from] OpParseError),
     #[error(transparent)]
     ImmediateStrLit(#[from] ImmediateStrLitError),
+    #[error("Initializer of static variable `{0}` is not a constant expression")]
+    NonConstInitializer(String),
 }
 
-impl std::fmt::Display for CmpOp {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::LessThan => "setl",
-                Self::LessEqual => "setle",
-                Self::GreaterThan => "setg",
-                Self::GreaterEqual => "setge",
-                Self::Equal => "sete",
-                Self::NotEqual => "setne",
-            }
-        )
+impl CmpOp {
+    /// `set*` instruction of the comparison. Operands whose common type isn't
+    /// a signed integer, pointers among them, are compared as unsigned.
+    fn set_instr(&self, signed: bool) -> &'static str {
+        match (self, signed) {
+            (Self::LessThan, true) => "setl",
+            (Self::LessEqual, true) => "setle",
+            (Self::GreaterThan, true) => "setg",
+            (Self::GreaterEqual, true) => "setge",
+            (Self::LessThan, false) => "setb",
+            (Self::LessEqual, false) => "setbe",
+            (Self::GreaterThan, false) => "seta",
+            (Self::GreaterEqual, false) => "setae",
+            (Self::Equal, _) => "sete",
+            (Self::NotEqual, _) => "setne",
+        }
     }
 }
 
@@ -122,6 +135,22 @@ pub enum Jump {
     LessThan,
     #[display("jle")]
     LessEqual,
+    #[display("jo")]
+    Overflow,
+    #[display("jc")]
+    Carry,
+    #[display("jae")]
+    AboveEqual,
+}
+
+/// Class of a function argument according to the System V AMD64 ABI. There
+/// are no floating point types, so every eightbyte is of the `INTEGER` class.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgClass {
+    /// Passed in as many general purpose registers as the argument has eightbytes
+    Integer(usize),
+    /// Passed on the stack
+    Memory,
 }
 
 #[derive(Debug, Clone)]
@@ -172,23 +201,113 @@ impl From<Register> for Location {
     }
 }
 
+/// Generated code split by the statements of function bodies, along with the
+/// location of every variable. Meant for reading, the assembly is unaffected.
+#[derive(Debug, Default)]
+struct Listing {
+    code: String,
+    /// Storage class, name and address of each variable
+    symbols: Vec<(&'static str, String, EffectiveAddress)>,
+}
+
+impl std::fmt::Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)?;
+        writeln!(f, "symbols")?;
+
+        for (class, name, addr) in &self.symbols {
+            writeln!(f, "  {class:<6} {name} {addr}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Code generated for a single item, put together with the others' by
+/// [`Amd64Asm::link`]
+#[derive(Debug, Default)]
+pub struct Fragment {
+    bss: String,
+    data: String,
+    tdata: String,
+    tbss: String,
+    text: String,
+    listing: Option<Listing>,
+    print_used: bool,
+    itoa_used: bool,
+    trap_used: bool,
+    bounds_trap_used: bool,
+    assert_used: bool,
+}
+
+/// Index of each item generated along with its code, or the error which
+/// stopped the generation at it
+pub type Fragments = Vec<(usize, Result<Fragment, Amd64AsmError>)>;
+
 pub struct Amd64Asm<'a, 'ir> {
     ctx: &'a Context<'ir>,
     allocator: RegisterAllocator,
     label_gen: LabelGenerator,
     bss: String,
     data: String,
+    /// Initialized and zeroed `thread_local` variables
+    tdata: String,
+    tbss: String,
     text: String,
     stack_offset: isize,
+    stack_pushed: usize,
+    fn_name: Option<&'ir str>,
+    ret_addr: Option<EffectiveAddress>,
+    ret_label: Option<String>,
+    /// Label right after the prologue of the current function, set only when
+    /// its tail calls to itself can jump there
+    body_label: Option<String>,
     variables: HashMap<Id, EffectiveAddress>,
+    /// How many static locals of each name the current function has declared
+    /// so far, shadowing ones get a suffix to keep their labels apart
+    static_names: HashMap<&'ir str, usize>,
     fields_offsets: HashMap<Id, HashMap<&'ir str, Offset>>,
+    pic: bool,
+    opt_level: u8,
+    /// Whether the print routines have to be emitted
+    print_used: bool,
+    /// Whether the itoa routines have to be emitted, print routines use them too
+    itoa_used: bool,
+    overflow: Overflow,
+    /// Whether the overflow trap routine has to be emitted
+    trap_used: bool,
+    stack_protector: bool,
+    bounds_check: bool,
+    assertions: bool,
+    debug: bool,
+    align_loops: bool,
+    syntax: Syntax,
+    /// Whether the routine reporting failed assertions has to be emitted
+    assert_used: bool,
+    /// Whether the bounds trap routine has to be emitted
+    bounds_trap_used: bool,
+    listing: Option<Listing>,
+    /// How deep in statements the generated code is, only the outermost ones
+    /// are listed
+    stmt_depth: usize,
+    /// Labels `continue` and `break` jump to in each of the enclosing loops
+    loops: Vec<(String, String)>,
+    /// Locals of the current function holding a known constant, used with `-O1`
+    consts: Constants,
+    /// Source code the instructions are annotated with
+    annotate: Option<&'a str>,
+    /// Whether the code generated now is annotated already, by the outermost
+    /// expression it's part of
+    annotating: bool,
 }
 
 impl<'a, 'ir> Codegen<'a, 'ir> for Amd64Asm<'a, 'ir> {
+    type Error = Amd64AsmError;
+
     fn new(ctx: &'a Context<'ir>) -> Self {
         Self {
             ctx,
-            label_gen: LabelGenerator::new(),
+            label_gen: LabelGenerator::new(0),
             allocator: RegisterAllocator::new(vec![
                 Register::R15,
                 Register::R14,
@@ -205,21 +324,64 @@ impl<'a, 'ir> Codegen<'a, 'ir> for Amd64Asm<'a, 'ir> {
             ]),
             bss: String::new(),
             data: String::new(),
+            tdata: String::new(),
+            tbss: String::new(),
             text: String::new(),
             stack_offset: 0,
+            stack_pushed: 0,
+            fn_name: None,
+            ret_addr: None,
+            ret_label: None,
+            body_label: None,
             variables: HashMap::new(),
+            static_names: HashMap::new(),
             fields_offsets: HashMap::new(),
+            pic: false,
+            opt_level: 0,
+            print_used: false,
+            itoa_used: false,
+            overflow: Overflow::Wrap,
+            trap_used: false,
+            stack_protector: false,
+            bounds_check: false,
+            bounds_trap_used: false,
+            assertions: true,
+            debug: false,
+            align_loops: false,
+            syntax: Syntax::Intel,
+            assert_used: false,
+            listing: None,
+            stmt_depth: 0,
+            loops: Vec::new(),
+            consts: Constants::default(),
+            annotate: None,
+            annotating: false,
         }
     }
 
-    fn compile(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    fn compile(&mut self) -> Result<Vec<u8>, Self::Error> {
+        let fragments = self.fragments(|_| true)?;
+
+        self.link(fragments)
+    }
+}
+
+impl<'a, 'ir> Amd64Asm<'a, 'ir> {
+    /// Generates the globals, then each other item `generate` picks by its
+    /// index into a fragment of its own, until one fails. Items can be split
+    /// between codegens, on threads of their own, [`Amd64Asm::link`] puts
+    /// them together into the code a single one generates.
+    pub fn fragments(
+        &mut self,
+        generate: impl Fn(usize) -> bool,
+    ) -> Result<Fragments, Amd64AsmError> {
         // TODO: that's hacky, get those ids instead of creating them
         self.ctx
             .ir
             .iter_items()
             .enumerate()
             .filter_map(|(i, item)| match item {
-                Item::Struct(_) => Some(Id {
+                Item::Struct(..) | Item::Union(_) => Some(Id {
                     global_id: i,
                     node_id: 0,
                 }),
@@ -227,10 +389,70 @@ impl<'a, 'ir> Codegen<'a, 'ir> for Amd64Asm<'a, 'ir> {
             })
             .for_each(|id| self.set_ty_fields_offsets(id));
 
-        for item in self.ctx.ir.iter_items() {
+        // Globals get their addresses first, a function may use one declared
+        // after it, so every codegen generates them
+        let (globals, items): (Vec<_>, Vec<_>) = self
+            .ctx
+            .ir
+            .iter_items()
+            .enumerate()
+            .partition(|(_, item)| matches!(item, Item::Global(_)));
+        for (i, item) in globals {
+            self.label_gen = LabelGenerator::new(i);
             self.item(&item)?;
         }
 
+        let globals = self.take_fragment();
+        let mut fragments = Vec::new();
+
+        for (i, item) in items.into_iter().filter(|(i, _)| generate(*i)) {
+            self.label_gen = LabelGenerator::new(i);
+
+            let result = self.item(&item).map(|_| self.take_fragment());
+            let failed = result.is_err();
+
+            fragments.push((i, result));
+            if failed {
+                break;
+            }
+        }
+
+        // The code of the globals goes first, it's linked with the fragments
+        self.take_fragment();
+        self.append(globals);
+
+        Ok(fragments)
+    }
+
+    /// Puts the fragments together in the order of their items, along with
+    /// the globals and the routines they use, into the code of the whole
+    /// translation unit. The first item whose generation failed fails it.
+    pub fn link(&mut self, mut fragments: Fragments) -> Result<Vec<u8>, Amd64AsmError> {
+        fragments.sort_by_key(|(i, _)| *i);
+        for (_, fragment) in fragments {
+            self.append(fragment?);
+        }
+
+        if self.print_used {
+            self.text.push_str(Self::PRINT_ROUTINES);
+        }
+        if self.print_used || self.itoa_used {
+            self.text.push_str(Self::ITOA_ROUTINES);
+        }
+        if self.trap_used {
+            self.text.push_str(Self::OVERFLOW_TRAP);
+        }
+        if self.bounds_trap_used {
+            self.text.push_str(Self::BOUNDS_TRAP);
+        }
+        if self.assert_used {
+            self.text.push_str(Self::ASSERT_FAILED);
+        }
+        if self.stack_protector {
+            self.data.push_str(Self::STACK_CANARY);
+            self.text.push_str(Self::STACK_SMASHED);
+        }
+
         let mut result = String::new();
 
         if !self.bss.is_empty() {
@@ -241,21 +463,458 @@ impl<'a, 'ir> Codegen<'a, 'ir> for Amd64Asm<'a, 'ir> {
             result.push_str(".section .data\n");
             result.push_str(&self.data);
         }
+        // The `T` flag makes them templates every thread's copy starts from
+        if !self.tbss.is_empty() {
+            result.push_str(".section .tbss,\"awT\",@nobits\n");
+            result.push_str(&self.tbss);
+        }
+        if !self.tdata.is_empty() {
+            result.push_str(".section .tdata,\"awT\",@progbits\n");
+            result.push_str(&self.tdata);
+        }
         if !self.text.is_empty() {
             result.push_str(".section .text\n");
             result.push_str(&self.text);
         }
+        // Without it the linker assumes the object needs an executable stack
+        result.push_str(".section .note.GNU-stack,\"\",@progbits\n");
+
+        Ok(match self.syntax {
+            // `as` reads AT&T syntax unless told otherwise
+            Syntax::Intel => format!(".intel_syntax noprefix\n{result}"),
+            Syntax::Att => att::translate(&result),
+        }
+        .into_bytes())
+    }
+
+    /// Moves the code generated so far out of the codegen
+    fn take_fragment(&mut self) -> Fragment {
+        Fragment {
+            bss: std::mem::take(&mut self.bss),
+            data: std::mem::take(&mut self.data),
+            tdata: std::mem::take(&mut self.tdata),
+            tbss: std::mem::take(&mut self.tbss),
+            text: std::mem::take(&mut self.text),
+            listing: self.listing.as_mut().map(std::mem::take),
+            print_used: std::mem::take(&mut self.print_used),
+            itoa_used: std::mem::take(&mut self.itoa_used),
+            trap_used: std::mem::take(&mut self.trap_used),
+            bounds_trap_used: std::mem::take(&mut self.bounds_trap_used),
+            assert_used: std::mem::take(&mut self.assert_used),
+        }
+    }
 
-        Ok(result.into_bytes())
+    /// Adds the code of `fragment` after the code generated so far
+    fn append(&mut self, fragment: Fragment) {
+        self.bss.push_str(&fragment.bss);
+        self.data.push_str(&fragment.data);
+        self.tdata.push_str(&fragment.tdata);
+        self.tbss.push_str(&fragment.tbss);
+        self.text.push_str(&fragment.text);
+        if let (Some(listing), Some(fragment)) = (&mut self.listing, fragment.listing) {
+            listing.code.push_str(&fragment.code);
+            listing.symbols.extend(fragment.symbols);
+        }
+        self.print_used |= fragment.print_used;
+        self.itoa_used |= fragment.itoa_used;
+        self.trap_used |= fragment.trap_used;
+        self.bounds_trap_used |= fragment.bounds_trap_used;
+        self.assert_used |= fragment.assert_used;
     }
 }
 
 impl<'a, 'ir> Amd64Asm<'a, 'ir> {
     const BITNESS: usize = 64;
+    const ARG_REGISTERS: [Register; 6] = [
+        Register::Rdi,
+        Register::Rsi,
+        Register::Rdx,
+        Register::Rcx,
+        Register::R8,
+        Register::R9,
+    ];
+    /// Functions, and loops with `align_loops`, start at a multiple of 2 to the
+    /// power of this many bytes, the size of the blocks instructions are
+    /// fetched in
+    const CODE_ALIGNMENT: usize = 4;
+    /// Regions larger than this many bytes are zeroed with `rep stosq` instead
+    /// of a store per word
+    const REP_STOS_THRESHOLD: usize = 64;
+    /// Routines `print!` calls to write a value to stdout. The value is passed
+    /// on the stack and every register they touch is preserved, so they can be
+    /// called in the middle of any expression. Integers are converted with the
+    /// `itoa!` routines into a buffer on the stack.
+    const PRINT_ROUTINES: &'static str = indoc! {"
+        __meraki_print_str:
+        \tpush rax
+        \tpush rcx
+        \tpush rdx
+        \tpush rsi
+        \tpush rdi
+        \tpush r11
+        \tmov rsi, [rsp + 56]
+        \tmov rdx, rsi
+        .Lprint_str_len:
+        \tcmp byte ptr [rdx], 0
+        \tje .Lprint_str_write
+        \tinc rdx
+        \tjmp .Lprint_str_len
+        .Lprint_str_write:
+        \tsub rdx, rsi
+        \tmov rax, 1
+        \tmov rdi, 1
+        \tsyscall
+        \tpop r11
+        \tpop rdi
+        \tpop rsi
+        \tpop rdx
+        \tpop rcx
+        \tpop rax
+        \tret
+        __meraki_print_i64:
+        \tpush rax
+        \tpush rcx
+        \tpush rdx
+        \tpush rsi
+        \tpush rdi
+        \tpush r11
+        \tlea rax, [rip + __meraki_itoa_i64]
+        \tjmp .Lprint_int
+        __meraki_print_u64:
+        \tpush rax
+        \tpush rcx
+        \tpush rdx
+        \tpush rsi
+        \tpush rdi
+        \tpush r11
+        \tlea rax, [rip + __meraki_itoa_u64]
+        .Lprint_int:
+        \tsub rsp, 32
+        \tpush qword ptr [rsp + 88]
+        \tlea rcx, [rsp + 8]
+        \tpush rcx
+        \tcall rax
+        \tadd rsp, 16
+        \tmov rdx, rax
+        \tmov rsi, rsp
+        \tmov rax, 1
+        \tmov rdi, 1
+        \tsyscall
+        \tadd rsp, 32
+        \tpop r11
+        \tpop rdi
+        \tpop rsi
+        \tpop rdx
+        \tpop rcx
+        \tpop rax
+        \tret
+    "};
+    /// Routines `itoa!` calls to write the decimal digits of an integer into a
+    /// buffer. The integer and then the buffer are passed on the stack, the
+    /// number of characters written, not counting the terminating zero, is
+    /// returned in `rax` and every other register is preserved.
+    const ITOA_ROUTINES: &'static str = indoc! {"
+        __meraki_itoa_i64:
+        \tpush rcx
+        \tpush rdx
+        \tpush rsi
+        \tpush rdi
+        \tmov rax, [rsp + 48]
+        \tmov rdi, [rsp + 40]
+        \ttest rax, rax
+        \tjns .Litoa
+        \tneg rax
+        \tmov byte ptr [rdi], 45
+        \tinc rdi
+        \tjmp .Litoa
+        __meraki_itoa_u64:
+        \tpush rcx
+        \tpush rdx
+        \tpush rsi
+        \tpush rdi
+        \tmov rax, [rsp + 48]
+        \tmov rdi, [rsp + 40]
+        .Litoa:
+        \tmov rsi, rdi
+        \tmov rcx, 10
+        .Litoa_digit:
+        \txor rdx, rdx
+        \tdiv rcx
+        \tadd dl, 48
+        \tmov [rdi], dl
+        \tinc rdi
+        \ttest rax, rax
+        \tjnz .Litoa_digit
+        \tmov byte ptr [rdi], 0
+        \tmov rax, rdi
+        \tsub rax, [rsp + 40]
+        \tdec rdi
+        .Litoa_reverse:
+        \tcmp rsi, rdi
+        \tjae .Litoa_done
+        \tmov cl, [rsi]
+        \tmov dl, [rdi]
+        \tmov [rsi], dl
+        \tmov [rdi], cl
+        \tinc rsi
+        \tdec rdi
+        \tjmp .Litoa_reverse
+        .Litoa_done:
+        \tpop rdi
+        \tpop rsi
+        \tpop rdx
+        \tpop rcx
+        \tret
+    "};
+    /// Routine arithmetic jumps to when its result overflows under
+    /// [`Overflow::Trap`]. It reports the overflow on stderr and exits with the
+    /// status of a program killed by `SIGABRT`.
+    const OVERFLOW_TRAP: &'static str = indoc! {"
+        __meraki_overflow_trap:
+        \tmov rax, 1
+        \tmov rdi, 2
+        \tlea rsi, [rip + .Loverflow_msg]
+        \tmov rdx, 17
+        \tsyscall
+        \tmov rax, 231
+        \tmov rdi, 134
+        \tsyscall
+        .Loverflow_msg:
+        \t.ascii \"integer overflow\\n\"
+    "};
+    /// Value every function protected by [`Amd64Asm::stack_protector`] puts
+    /// between its locals and the saved registers. It's fixed for now.
+    const STACK_CANARY: &'static str = indoc! {"
+        __meraki_stack_canary:
+        \t.quad 0x2f8d6b1e9c4a7350
+    "};
+    /// Routine a function jumps to on return when its canary got overwritten,
+    /// exits the same way as [`Self::OVERFLOW_TRAP`].
+    const STACK_SMASHED: &'static str = indoc! {"
+        __meraki_stack_smashed:
+        \tmov rax, 1
+        \tmov rdi, 2
+        \tlea rsi, [rip + .Lstack_smashed_msg]
+        \tmov rdx, 24
+        \tsyscall
+        \tmov rax, 231
+        \tmov rdi, 134
+        \tsyscall
+        .Lstack_smashed_msg:
+        \t.ascii \"stack smashing detected\\n\"
+    "};
+    /// Routine indexing jumps to when the index is past the end of the array
+    /// under [`Amd64Asm::bounds_check`], exits the same way as
+    /// [`Self::OVERFLOW_TRAP`].
+    const BOUNDS_TRAP: &'static str = indoc! {"
+        __meraki_bounds_trap:
+        \tmov rax, 1
+        \tmov rdi, 2
+        \tlea rsi, [rip + .Lbounds_msg]
+        \tmov rdx, 20
+        \tsyscall
+        \tmov rax, 231
+        \tmov rdi, 134
+        \tsyscall
+        .Lbounds_msg:
+        \t.ascii \"index out of bounds\\n\"
+    "};
+    /// Routine a failed `assert!` jumps to with its message in `rsi` and the
+    /// length of it in `rdx`, exits the same way as [`Self::OVERFLOW_TRAP`].
+    const ASSERT_FAILED: &'static str = indoc! {"
+        __meraki_assert_failed:
+        \tmov rax, 1
+        \tmov rdi, 2
+        \tsyscall
+        \tmov rax, 231
+        \tmov rdi, 134
+        \tsyscall
+    "};
+    const CALLER_SAVED_REGISTERS: [Register; 8] = [
+        Register::Rdi,
+        Register::Rsi,
+        Register::Rdx,
+        Register::Rcx,
+        Register::R8,
+        Register::R9,
+        Register::R10,
+        Register::R11,
+    ];
+    const CALLEE_SAVED_REGISTERS: [Register; 4] =
+        [Register::R12, Register::R13, Register::R14, Register::R15];
+
+    /// Generates position-independent code, which can be linked into a shared
+    /// library or a PIE. Statically allocated variables are accessed through
+    /// the GOT and functions are called through the PLT.
+    pub fn pic(mut self, pic: bool) -> Self {
+        self.pic = pic;
+
+        self
+    }
+
+    /// Level 1 turns calls of a function to itself in tail position into jumps,
+    /// so such recursion runs in constant stack space, and computes integer
+    /// arithmetic on constants and locals known to hold one at compile time.
+    pub fn opt_level(mut self, opt_level: u8) -> Self {
+        self.opt_level = opt_level;
+
+        self
+    }
+
+    /// With [`Overflow::Trap`] every addition, subtraction and multiplication
+    /// of integers checks whether its result fits in its type.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+
+        self
+    }
+
+    /// Every function stores a canary right below the saved registers and
+    /// checks it before returning, aborting the program if a write past the
+    /// end of a local changed it.
+    pub fn stack_protector(mut self, stack_protector: bool) -> Self {
+        self.stack_protector = stack_protector;
+
+        self
+    }
+
+    /// Indexing an array of a known length compares the index against it,
+    /// aborting the program when it's out of range. Indexing through a pointer
+    /// isn't checked, its length is unknown.
+    pub fn bounds_check(mut self, bounds_check: bool) -> Self {
+        self.bounds_check = bounds_check;
+
+        self
+    }
+
+    /// `assert!` checks its condition, without assertions it compiles to
+    /// nothing and the condition isn't evaluated.
+    pub fn assertions(mut self, assertions: bool) -> Self {
+        self.assertions = assertions;
+
+        self
+    }
+
+    /// Every function is described by call frame information directives, so
+    /// debuggers can show a backtrace through it.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+
+        self
+    }
+
+    /// The condition of every loop, which the end of its body jumps back to,
+    /// starts at the same alignment as functions do.
+    pub fn align_loops(mut self, align_loops: bool) -> Self {
+        self.align_loops = align_loops;
+
+        self
+    }
+
+    /// Syntax of the generated assembly. Inline assembly is written in Intel
+    /// syntax, with [`Syntax::Att`] it's surrounded by directives switching to
+    /// it and back.
+    pub fn syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = syntax;
+
+        self
+    }
+
+    /// Collects a listing of the generated code while compiling, available
+    /// from [`Amd64Asm::listing_text`] afterward.
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing.then(Listing::default);
+
+        self
+    }
+
+    /// Every instruction generated for an expression gets a comment with the
+    /// source code of the expression, `source` being the code it was parsed
+    /// from.
+    pub fn annotate(mut self, source: Option<&'a str>) -> Self {
+        self.annotate = source;
+
+        self
+    }
+
+    /// Each statement of every function body followed by the code generated
+    /// for it, then the address of every variable.
+    pub fn listing_text(&self) -> Option<String> {
+        self.listing.as_ref().map(|listing| listing.to_string())
+    }
+
+    fn list_symbol(&mut self, class: &'static str, name: String, addr: &EffectiveAddress) {
+        if let Some(listing) = &mut self.listing {
+            listing.symbols.push((class, name, addr.clone()));
+        }
+    }
+
+    /// Address of a variable, or of the code of a function.
+    fn ident_addr(&self, id: Id) -> EffectiveAddress {
+        match self.ctx.ir.get_node(id) {
+            Node::Item(Item::Fn(item)) => EffectiveAddress {
+                base: Base::Label(item.name.to_string()),
+                index: None,
+                scale: None,
+                displacement: None,
+            },
+            _ => self.variables[&id].clone(),
+        }
+    }
 
-    fn expr_addr(&mut self, expr: &Expr) -> Result<EffectiveAddress, Amd64AsmError> {
+    fn expr_addr(&mut self, expr: &Expr<'ir>) -> Result<EffectiveAddress, Amd64AsmError> {
         Ok(match expr.kind {
-            ExprKind::Ident(id) => self.variables[&id].clone(),
+            ExprKind::Ident(id) => match &self.ident_addr(id) {
+                EffectiveAddress {
+                    base: Base::Label(label),
+                    displacement,
+                    ..
+                } if self.pic => {
+                    let r = self.allocator.alloc(OperandSize::Qword)?;
+                    let displacement = *displacement;
+                    let got_entry = EffectiveAddress {
+                        base: Base::Rip(format!("{label}@GOTPCREL")),
+                        index: None,
+                        scale: None,
+                        displacement: None,
+                    };
+
+                    self.mov(&got_entry.src(OperandSize::Qword), &r.into(), false)?;
+
+                    EffectiveAddress {
+                        displacement,
+                        ..r.into()
+                    }
+                }
+                // The thread pointer at `fs:0` is the address of itself, the
+                // variable is at a fixed offset from it. A shared library
+                // can't know the offset until it's loaded, so it's read from
+                // the GOT then.
+                EffectiveAddress {
+                    base: Base::Tls(label),
+                    displacement,
+                    ..
+                } => {
+                    let r = self.allocator.alloc(OperandSize::Qword)?;
+                    let displacement = *displacement;
+
+                    if self.pic {
+                        self.text.push_str(&format!(
+                            "\tmov {r}, qword ptr [rip + {label}@gottpoff]\n\tadd {r}, qword ptr fs:0\n"
+                        ));
+                    } else {
+                        self.text.push_str(&format!(
+                            "\tmov {r}, qword ptr fs:0\n\tlea {r}, [{r} + {label}@tpoff]\n"
+                        ));
+                    }
+
+                    EffectiveAddress {
+                        displacement,
+                        ..r.into()
+                    }
+                }
+                addr => self.frame_addr(addr.clone())?,
+            },
             ExprKind::Unary(op, expr) if op == UnOp::Deref => {
                 let r = self.allocator.alloc(OperandSize::Qword)?;
 
@@ -264,18 +923,89 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                 r.into()
             }
             ExprKind::Field(expr, field) => {
-                let id = match self.ctx.resolve_ty(expr.ty) {
-                    Ty::Struct(id) => id,
+                let offset = match self.ctx.resolve_ty(expr.ty) {
+                    Ty::Struct(id, _) => self.fields_offsets[id][field],
+                    Ty::Tuple(tys) => {
+                        Offset(self.tuple_offsets(tys)[field.parse::<usize>().unwrap()])
+                    }
                     _ => unreachable!(),
                 };
 
-                self.expr_addr(expr)? + self.fields_offsets[id][field]
+                self.expr_addr(expr)? + offset
             }
             expr => unreachable!("{expr:?} is not a valid lvalue expression"),
         })
     }
 
-    fn item(&mut self, item: &Item) -> Result<(), Amd64AsmError> {
+    /// A local further from rbp than a signed 32 bit displacement reaches, in
+    /// a frame larger than 2 GiB, is addressed with its offset loaded into a
+    /// newly allocated index register. Any other address is usable as is.
+    fn frame_addr(&mut self, addr: EffectiveAddress) -> Result<EffectiveAddress, Amd64AsmError> {
+        match addr {
+            EffectiveAddress {
+                base: Base::Register(Register::Rbp),
+                index: None,
+                displacement: Some(Offset(offset)),
+                ..
+            } if i32::try_from(offset).is_err() => {
+                let r = self.allocator.alloc(OperandSize::Qword)?;
+
+                self.mov(
+                    &Source::Immediate(Immediate::Int(offset as i64)),
+                    &r.into(),
+                    true,
+                )?;
+
+                Ok(EffectiveAddress {
+                    base: Base::Register(Register::Rbp),
+                    index: Some(r),
+                    scale: None,
+                    displacement: None,
+                })
+            }
+            addr => Ok(addr),
+        }
+    }
+
+    /// Frees the registers holding the base or index of an address computed
+    /// by [`Self::expr_addr`], if they were allocated to dereference a pointer
+    /// or to reach a local far from rbp.
+    fn free_addr(&mut self, addr: &EffectiveAddress) -> Result<(), Amd64AsmError> {
+        let base = match addr.base {
+            Base::Register(r) => Some(r),
+            _ => None,
+        };
+
+        for r in base.into_iter().chain(addr.index) {
+            if self.allocator.is_used(&r) {
+                self.allocator.free(r)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates `expr` into a newly allocated register of `size`, extending
+    /// the value if the type of the expression is narrower.
+    fn expr_to_reg(
+        &mut self,
+        expr: &Expr<'ir>,
+        size: OperandSize,
+    ) -> Result<Register, Amd64AsmError> {
+        let ty = self.ctx.resolve_ty(expr.ty);
+        let expr_size = self.ty_size(ty).try_into()?;
+        let r = self.allocator.alloc(size)?;
+
+        self.expr(expr, Some(&r.resize(expr_size).into()))?;
+
+        if size > expr_size {
+            self.mov(&r.resize(expr_size).into(), &r.into(), ty.signed())?;
+        }
+
+        Ok(r)
+    }
+
+    fn item(&mut self, item: &Item<'ir>) -> Result<(), Amd64AsmError> {
         match item {
             Item::Fn(item) => self.function(item),
             Item::Global(item) => {
@@ -283,26 +1013,85 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
 
                 Ok(())
             }
-            Item::Struct(_) => Ok(()),
+            Item::Struct(..) | Item::Union(_) => Ok(()),
         }
     }
 
-    fn function(&mut self, item: &ItemFn) -> Result<(), Amd64AsmError> {
+    fn function(&mut self, item: &ItemFn<'ir>) -> Result<(), Amd64AsmError> {
         let name = item.name;
+        let Some(block) = &item.block else {
+            self.text.push_str(&format!(".extern {name}\n"));
+
+            return Ok(());
+        };
+        self.fn_name = Some(name);
+        self.static_names.clear();
+        let consts = std::mem::replace(&mut self.consts, Constants::new(block));
+
+        self.text
+            .push_str(&format!(".p2align {}\n", Self::CODE_ALIGNMENT));
+
+        // `main` is the entry point the C runtime calls, so it's always exported
+        if item.pub_ || name == "main" {
+            self.text.push_str(&format!(".global {name}\n"));
+        }
 
         self.text.push_str(&formatdoc!(
             "
-            .global {name}
+            .type {name}, @function
             {name}:
             "
         ));
 
-        let stack_frame = self.stack_frame_size(&item.block);
+        // The body of a naked function is all of it, there's no frame to set up
+        if item.naked {
+            if let Some(listing) = &mut self.listing {
+                listing.code.push_str(&format!("fn {name}\n"));
+            }
+
+            self.block(block)?;
+            self.text.push_str(&format!(".size {name}, .-{name}\n"));
+            self.fn_name = None;
+            self.consts = consts;
+
+            return Ok(());
+        }
+
+        self.cfi(".cfi_startproc");
+
+        let stack_frame = self.stack_frame_size(item, block);
+
+        if let Some(listing) = &mut self.listing {
+            listing.code.push_str(&format!("fn {name}\n"));
+        }
+
+        // The caller pushed the return address on an aligned stack
+        self.stack_pushed = 8;
+        self.push(&Register::Rbp.into());
+        self.cfi(".cfi_def_cfa_offset 16");
+        self.cfi(".cfi_offset rbp, -16");
+        self.mov(&Register::Rsp.into(), &Register::Rbp.into(), false)?;
+        // The frame is addressed from rbp from now on, pushes don't move it
+        self.cfi(".cfi_def_cfa_register rbp");
+
+        for (i, r) in Self::CALLEE_SAVED_REGISTERS.into_iter().enumerate() {
+            self.push(&r.into());
+            self.cfi(&format!(".cfi_offset {r}, -{}", 24 + i * 8));
+        }
+
+        let saved_registers_offset = -((Self::CALLEE_SAVED_REGISTERS.len() * 8) as isize);
+        self.stack_offset = saved_registers_offset;
 
-        if stack_frame > 0 {
-            let rbp = &Register::Rbp.into();
-            self.push(rbp);
-            self.mov(&Register::Rsp.into(), &Register::Rbp.into(), false)?;
+        // An immediate operand of `sub` is at most 32 bits, sign extended
+        if stack_frame > i32::MAX as usize {
+            self.mov(&(stack_frame as u64).into(), &Register::R11.into(), false)?;
+            self.sub(
+                &Register::Rsp.into(),
+                &Register::R11.into(),
+                &Register::Rsp.into(),
+                false,
+            )?;
+        } else if stack_frame > 0 {
             self.sub(
                 &Register::Rsp.into(),
                 &(stack_frame as u64).into(),
@@ -311,116 +1100,488 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
             )?;
         }
 
-        item.block
-            .0
-            .iter()
-            .map(|stmt| self.stmt(stmt))
-            .collect::<Result<(), _>>()?;
+        let canary = self.stack_protector.then(|| {
+            self.stack_offset -= 8;
+
+            Register::Rbp.into_effective_addr(self.stack_offset)
+        });
+
+        if let Some(canary) = &canary {
+            self.text.push_str(&format!(
+                "\tmov r11, [rip + __meraki_stack_canary]\n\tmov {}, r11\n",
+                canary.dest(OperandSize::Qword)
+            ));
+        }
+
+        if self.opt_level >= 1 && self.tail_callable(item) {
+            let body_label = self.label_gen.generate();
+
+            self.write_label(&body_label);
+            self.body_label = Some(body_label);
+        }
+
+        self.fn_preamble(item)?;
+
+        let ret_label = self.label_gen.generate();
+        self.ret_label = Some(ret_label.clone());
+
+        self.block(block)?;
+
+        if item.noreturn {
+            self.text.push_str("\tud2\n");
+        }
 
-        let ret_label = &self.label_gen.generate();
         self.write_label(&ret_label);
 
-        if stack_frame > 0 {
-            self.text.push_str(&format!("\tleave\n"));
+        // The C runtime exits with the value `main` returns, which is 0 when it
+        // returns nothing
+        if name == "main" && self.ctx.resolve_ty(item.signature.ret_ty) == &Ty::Void {
+            self.text.push_str("\txor eax, eax\n");
+        }
+
+        // r11 isn't used to return values, rax and rdx have to be left alone
+        if let Some(canary) = &canary {
+            self.text.push_str(&format!(
+                "\tmov r11, {}\n\txor r11, [rip + __meraki_stack_canary]\n",
+                canary.dest(OperandSize::Qword)
+            ));
+            self.jcc("__meraki_stack_smashed", Jump::NotEqual);
+        }
+
+        self.lea(
+            &Register::Rsp.into(),
+            &Register::Rbp.into_effective_addr(saved_registers_offset),
+        );
+
+        for r in Self::CALLEE_SAVED_REGISTERS.iter().rev() {
+            self.pop(&(*r).into());
         }
 
+        self.text.push_str(&format!("\tleave\n"));
+        self.cfi(".cfi_def_cfa rsp, 8");
         self.text.push_str(&format!("\tret\n"));
+        self.cfi(".cfi_endproc");
+        self.text.push_str(&format!(".size {name}, .-{name}\n"));
+        // Every value lives in a register only while an expression is evaluated
+        debug_assert!(
+            self.allocator.used().is_empty(),
+            "Registers are still allocated at the end of `{name}`"
+        );
         self.stack_offset = 0;
-        self.variables.clear();
+        self.fn_name = None;
+        self.ret_addr = None;
+        self.ret_label = None;
+        self.body_label = None;
+        self.consts = consts;
+        // Statically allocated variables outlive the function, their labels stay valid
+        self.variables
+            .retain(|_, addr| matches!(addr.base, Base::Label(_) | Base::Tls(_)));
 
         Ok(())
     }
 
-    fn stmt(&mut self, stmt: &Stmt) -> Result<(), Amd64AsmError> {
+    /// Emits a call frame information directive describing the frame of the
+    /// function, used by debuggers and unwinders to walk the stack.
+    fn cfi(&mut self, directive: &str) {
+        if self.debug {
+            self.text.push_str(&format!("\t{directive}\n"));
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'ir>) -> Result<(), Amd64AsmError> {
+        let listed = self.listing.is_some() && self.stmt_depth == 0;
+        let start = self.text.len();
+
+        self.stmt_depth += 1;
+        let result = self.stmt_code(stmt);
+        self.stmt_depth -= 1;
+
+        if let Some(listing) = self.listing.as_mut().filter(|_| listed) {
+            let stmt = match stmt {
+                Stmt::Local(var) if var.static_ => format!("static let {}", var.name),
+                Stmt::Local(var) | Stmt::Item(Item::Global(var)) => format!("let {}", var.name),
+                Stmt::Item(Item::Fn(item)) => format!("fn {}", item.name),
+                Stmt::Item(Item::Struct(_, false)) => String::from("struct"),
+                Stmt::Item(Item::Struct(_, true)) => String::from("packed struct"),
+                Stmt::Item(Item::Union(_)) => String::from("union"),
+                Stmt::Expr(_) => String::from("expr"),
+                Stmt::Return(_) => String::from("return"),
+                Stmt::If(..) => String::from("if"),
+                Stmt::Label(label) => format!("{label}:"),
+                Stmt::Goto(label) => format!("goto {label}"),
+                Stmt::For(..) => String::from("for"),
+                Stmt::Continue => String::from("continue"),
+                Stmt::Break => String::from("break"),
+            };
+
+            listing
+                .code
+                .push_str(&format!("  {stmt}\n{}", &self.text[start..]));
+        }
+
+        result
+    }
+
+    fn stmt_code(&mut self, stmt: &Stmt<'ir>) -> Result<(), Amd64AsmError> {
         match stmt {
             Stmt::Local(stmt) => self.local(stmt),
-            Stmt::Return(expr) => self.ret(expr.as_ref()),
+            Stmt::Return(Some(Expr {
+                kind: ExprKind::Call(callee, args),
+                ..
+            })) if self.self_call(callee) => self.tail_call(args),
+            Stmt::Return(expr) => {
+                self.ret(expr.as_ref())?;
+                self.jcc(&self.ret_label.clone().unwrap(), Jump::Unconditional);
+
+                Ok(())
+            }
             Stmt::Item(item) => self.item(item),
             Stmt::Expr(expr) => self.expr(expr, None),
-        }
-    }
+            Stmt::If(condition, consequence, alternative) => {
+                let alternative_label = self.label_gen.generate();
+                let end_label = self.label_gen.generate();
+                let r = self.expr_to_reg(condition, OperandSize::Byte)?;
 
-    fn expr(&mut self, expr: &Expr, loc: Option<&Location>) -> Result<(), Amd64AsmError> {
-        Ok(match expr.kind {
-            ExprKind::Binary(op, lhs, rhs) => {
-                macro_rules! eval_expr {
-                    // This shit looks scary
-                    ($(($out: ident, $expr: ident)),+) => {
-                        $(
-                            let $out = self.allocator.alloc(self.ty_size(self.ctx.resolve_ty($expr.ty)).try_into()?)?;
-                            self.expr($expr, Some(&$out.into()))?;
-                        )+
-                    };
+                self.cmp(&r.into(), &Source::Immediate(Immediate::UInt(0)));
+                self.allocator.free(r)?;
+                self.jcc(&alternative_label, Jump::Equal);
+                let consts = self.consts.values();
+                self.block(consequence)?;
+
+                match alternative {
+                    Some(alternative) => {
+                        self.jcc(&end_label, Jump::Unconditional);
+                        self.write_label(&alternative_label);
+                        let consequence_consts = self.consts.replace(consts);
+                        self.block(alternative)?;
+                        self.consts.join(&consequence_consts);
+                    }
+                    None => {
+                        self.write_label(&alternative_label);
+                        self.consts.join(&consts);
+                    }
                 }
 
-                let signed = self.ctx.resolve_ty(expr.ty).signed();
+                self.write_label(&end_label);
 
-                match op {
-                    BinOp::Assign => {
-                        let expr_addr = self.expr_addr(lhs)?;
+                Ok(())
+            }
+            Stmt::Label(label) => {
+                self.write_label(&self.goto_label(label));
+                self.consts.clear();
 
-                        self.expr(rhs, Some(&expr_addr.clone().into()))?;
+                Ok(())
+            }
+            Stmt::Goto(label) => {
+                self.jcc(&self.goto_label(label), Jump::Unconditional);
 
-                        if let Some(loc) = loc {
-                            let size = self.ty_size(self.ctx.resolve_ty(expr.ty));
+                Ok(())
+            }
+            Stmt::For(initializer, condition, increment, block) => {
+                let stack_offset = self.stack_offset;
+                let condition_label = self.label_gen.generate();
+                let increment_label = self.label_gen.generate();
+                let end_label = self.label_gen.generate();
+
+                for stmt in *initializer {
+                    self.stmt(stmt)?;
+                }
 
-                            self.mov_loc(&expr_addr.into(), loc, size, signed)?;
-                        }
-                    }
-                    BinOp::Add => {
-                        if let Some(loc) = loc {
-                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+                // The padding is executed once on the way into the loop
+                if self.align_loops {
+                    self.text
+                        .push_str(&format!(".p2align {}\n", Self::CODE_ALIGNMENT));
+                }
 
-                            self.add(
-                                &r_lhs.into(),
-                                &r_rhs.into(),
-                                &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
-                                signed,
-                            )?;
+                self.write_label(&condition_label);
+                self.consts.clear();
 
-                            self.allocator.free(r_lhs)?;
-                            self.allocator.free(r_rhs)?;
-                        }
-                    }
-                    BinOp::Sub => {
-                        if let Some(loc) = loc {
-                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+                if let Some(condition) = condition {
+                    let r = self.expr_to_reg(condition, OperandSize::Byte)?;
 
-                            self.sub(
-                                &r_lhs.into(),
-                                &r_rhs.into(),
-                                &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
-                                signed,
-                            )?;
+                    self.cmp(&r.into(), &Source::Immediate(Immediate::UInt(0)));
+                    self.allocator.free(r)?;
+                    self.jcc(&end_label, Jump::Equal);
+                }
 
-                            self.allocator.free(r_lhs)?;
-                            self.allocator.free(r_rhs)?;
-                        }
-                    }
-                    BinOp::Mul => {
-                        if let Some(loc) = loc {
-                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+                self.loops
+                    .push((increment_label.clone(), end_label.clone()));
+                self.block(block)?;
+                self.loops.pop();
 
-                            self.mul(
-                                &r_lhs.into(),
-                                &r_rhs.into(),
-                                &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
-                                signed,
-                            )?;
+                // Like in C, `continue` jumps here, so the increment runs after it too
+                self.write_label(&increment_label);
+                self.consts.clear();
 
-                            self.allocator.free(r_lhs)?;
-                            self.allocator.free(r_rhs)?;
-                        }
-                    }
-                    BinOp::Div => {
-                        if let Some(loc) = loc {
-                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+                if let Some(increment) = increment {
+                    self.expr(increment, None)?;
+                }
 
-                            self.div(
+                self.jcc(&condition_label, Jump::Unconditional);
+                self.write_label(&end_label);
+                self.consts.clear();
+                self.stack_offset = stack_offset;
+
+                Ok(())
+            }
+            Stmt::Continue => {
+                self.jcc(&self.loops.last().unwrap().0.clone(), Jump::Unconditional);
+
+                Ok(())
+            }
+            Stmt::Break => {
+                self.jcc(&self.loops.last().unwrap().1.clone(), Jump::Unconditional);
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Labels are per function, so they are mangled with the function name
+    fn goto_label(&self, label: &str) -> String {
+        format!(".L{}.{label}", self.fn_name.unwrap())
+    }
+
+    /// Locals of a block die at its end, so their stack slots are reused by the
+    /// following blocks.
+    fn block(&mut self, block: &Block<'ir>) -> Result<(), Amd64AsmError> {
+        self.block_expr(block, None)
+    }
+
+    /// Evaluates a block, its trailing expression ends up in `loc`.
+    fn block_expr(
+        &mut self,
+        block: &Block<'ir>,
+        loc: Option<&Location>,
+    ) -> Result<(), Amd64AsmError> {
+        let stack_offset = self.stack_offset;
+
+        for stmt in block.0 {
+            self.stmt(stmt)?;
+        }
+
+        if let Some(expr) = block.1 {
+            self.expr(expr, loc)?;
+        }
+
+        self.stack_offset = stack_offset;
+
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr<'ir>, loc: Option<&Location>) -> Result<(), Amd64AsmError> {
+        let snippet = self.snippet(expr.span).filter(|_| !self.annotating);
+        let start = self.text.len();
+
+        self.annotating |= snippet.is_some();
+        let result = self.expr_code(expr, loc);
+
+        if let Some(snippet) = snippet {
+            self.annotating = false;
+
+            for line in self.text.split_off(start).split_inclusive('\n') {
+                match line.strip_suffix('\n') {
+                    // Labels and directives are left alone, only instructions are indented
+                    Some(instruction) if instruction.starts_with('\t') => {
+                        self.text.push_str(&format!("{instruction} # {snippet}\n"))
+                    }
+                    _ => self.text.push_str(line),
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Source code of an expression made fit for a comment: on one line and
+    /// cut short if it's long. `None` unless annotating, or if the compiler
+    /// made the expression up.
+    fn snippet(&self, span: &Span) -> Option<String> {
+        const MAX_LEN: usize = 40;
+
+        let code = self.annotate?.get(span.start..span.end)?;
+        let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        match code.char_indices().nth(MAX_LEN) {
+            Some((i, _)) => Some(format!("{}...", &code[..i])),
+            None => Some(code),
+        }
+    }
+
+    fn expr_code(&mut self, expr: &Expr<'ir>, loc: Option<&Location>) -> Result<(), Amd64AsmError> {
+        if let Some(lit) = self.const_lit(expr) {
+            return self.expr(
+                &Expr {
+                    kind: ExprKind::Lit(lit),
+                    ..*expr
+                },
+                loc,
+            );
+        }
+
+        Ok(match expr.kind {
+            ExprKind::Binary(op, lhs, rhs) => {
+                macro_rules! eval_expr {
+                    // This shit looks scary
+                    (($lhs_out: ident, $lhs: ident), ($rhs_out: ident, $rhs: ident)) => {
+                        // The left operand waits in its register while the right one is evaluated
+                        let liveness = if Self::has_call($rhs) {
+                            Liveness::AcrossCalls
+                        } else {
+                            Liveness::Temporary
+                        };
+                        let $lhs_out = self.allocator.alloc_for(
+                            self.ty_size(self.ctx.resolve_ty($lhs.ty)).try_into()?,
+                            liveness,
+                        )?;
+                        self.expr($lhs, Some(&$lhs_out.into()))?;
+                        let $rhs_out = self.allocator.alloc_for(
+                            self.ty_size(self.ctx.resolve_ty($rhs.ty)).try_into()?,
+                            Liveness::Temporary,
+                        )?;
+                        self.expr($rhs, Some(&$rhs_out.into()))?;
+                    };
+                }
+
+                let signed = self.ctx.resolve_ty(expr.ty).signed();
+
+                match op {
+                    BinOp::Assign => {
+                        let expr_addr = self.expr_addr(lhs)?;
+
+                        self.expr(rhs, Some(&expr_addr.clone().into()))?;
+
+                        // Statically allocated variables can be changed by any call
+                        if let (ExprKind::Ident(id), Base::Register(Register::Rbp)) =
+                            (lhs.kind, &expr_addr.base)
+                        {
+                            self.consts.set(id, self.const_value(rhs));
+                        }
+
+                        if let Some(loc) = loc {
+                            let size = self.ty_size(self.ctx.resolve_ty(expr.ty));
+
+                            self.mov_loc(&expr_addr.clone().into(), loc, size, signed)?;
+                        }
+
+                        self.free_addr(&expr_addr)?;
+                    }
+                    BinOp::Add => {
+                        if let Some(loc) = loc {
+                            if let Ty::Ptr(pointee) = self.ctx.resolve_ty(expr.ty) {
+                                let (base, offset) = if self.ctx.resolve_ty(rhs.ty).int() {
+                                    (lhs, rhs)
+                                } else {
+                                    (rhs, lhs)
+                                };
+
+                                return self.ptr_add(
+                                    base,
+                                    offset,
+                                    pointee,
+                                    &loc.dest(OperandSize::Qword),
+                                    false,
+                                );
+                            }
+
+                            let size = self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?;
+                            let r_lhs = self.expr_to_reg(lhs, size)?;
+                            let r_rhs = self.expr_to_reg(rhs, size)?;
+
+                            self.add(&r_lhs.into(), &r_rhs.into(), &loc.dest(size), signed)?;
+                            self.check_overflow(if signed { Jump::Overflow } else { Jump::Carry });
+
+                            self.allocator.free(r_lhs)?;
+                            self.allocator.free(r_rhs)?;
+                        }
+                    }
+                    BinOp::Sub => {
+                        if let Some(loc) = loc {
+                            if let Ty::Ptr(pointee) = self.ctx.resolve_ty(expr.ty) {
+                                return self.ptr_add(
+                                    lhs,
+                                    rhs,
+                                    pointee,
+                                    &loc.dest(OperandSize::Qword),
+                                    true,
+                                );
+                            }
+
+                            let size = self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?;
+                            let r_lhs = self.expr_to_reg(lhs, size)?;
+                            let r_rhs = self.expr_to_reg(rhs, size)?;
+
+                            if let Ty::Ptr(pointee) = self.ctx.resolve_ty(lhs.ty) {
+                                self.ptr_diff(r_lhs, r_rhs, pointee, &loc.dest(size))?;
+                            } else {
+                                self.sub(&r_lhs.into(), &r_rhs.into(), &loc.dest(size), signed)?;
+                                self.check_overflow(if signed {
+                                    Jump::Overflow
+                                } else {
+                                    Jump::Carry
+                                });
+                            }
+
+                            self.allocator.free(r_lhs)?;
+                            self.allocator.free(r_rhs)?;
+                        }
+                    }
+                    BinOp::Mul => {
+                        // A shift can't tell whether the product overflows, trapping needs `imul`
+                        let shift = match self.overflow {
+                            Overflow::Wrap => self
+                                .pow2_lit(rhs)
+                                .map(|shift| (lhs, shift))
+                                .or_else(|| self.pow2_lit(lhs).map(|shift| (rhs, shift))),
+                            Overflow::Trap => None,
+                        };
+
+                        if let (Some(loc), Some((value, shift))) = (loc, shift) {
+                            self.expr(value, Some(loc))?;
+                            self.text.push_str(&format!(
+                                "\tshl {}, {shift}\n",
+                                loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?)
+                            ));
+                        } else if let Some(loc) = loc {
+                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+
+                            self.mul(
+                                &r_lhs.into(),
+                                &r_rhs.into(),
+                                &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
+                                signed,
+                            )?;
+                            // Both `mul` and `imul` set the overflow flag when the
+                            // upper half of the product is significant
+                            self.check_overflow(Jump::Overflow);
+
+                            self.allocator.free(r_lhs)?;
+                            self.allocator.free(r_rhs)?;
+                        }
+                    }
+                    BinOp::Div | BinOp::Rem => {
+                        let shift = match op {
+                            BinOp::Div => self.pow2_lit(rhs),
+                            _ => None,
+                        };
+
+                        if let (Some(loc), Some(shift)) = (loc, shift) {
+                            self.div_pow2(lhs, shift, loc, signed)?;
+                        } else if let Some(loc) = loc {
+                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+
+                            // `div` leaves the quotient in `rax` and the remainder in `rdx`
+                            let result = match op {
+                                BinOp::Div => Register::Rax,
+                                _ => Register::Rdx,
+                            };
+
+                            self.div(
                                 &r_lhs.into(),
                                 &r_rhs.into(),
                                 &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
                                 signed,
+                                result,
                             )?;
 
                             self.allocator.free(r_lhs)?;
@@ -434,18 +1595,45 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     | BinOp::GreaterThan
                     | BinOp::GreaterEqual => {
                         if let Some(loc) = loc {
-                            eval_expr!((r_lhs, lhs), (r_rhs, rhs));
+                            // NULL can only be compared for equality, so operands can be swapped
+                            let (lhs, rhs) = match lhs.kind {
+                                ExprKind::Lit(ExprLit::Null) => (rhs, lhs),
+                                _ => (lhs, rhs),
+                            };
+                            let lhs_ty = self.ctx.resolve_ty(lhs.ty);
+                            let rhs_ty = self.ctx.resolve_ty(rhs.ty);
+                            // Integers are compared as the type they're promoted to
+                            let signed = lhs_ty.common_ty(rhs_ty).is_some_and(|ty| ty.signed());
+                            let size = std::cmp::max(self.ty_size(lhs_ty), self.ty_size(rhs_ty))
+                                .try_into()?;
+                            let r_lhs = self.expr_to_reg(lhs, size)?;
+
+                            match rhs.kind {
+                                ExprKind::Lit(ExprLit::Null) => {
+                                    self.cmp(&r_lhs.into(), &Source::Immediate(Immediate::UInt(0)));
+                                }
+                                _ => {
+                                    let r_rhs = self.expr_to_reg(rhs, size)?;
+
+                                    self.cmp(&r_lhs.into(), &r_rhs.into());
+                                    self.allocator.free(r_rhs)?;
+                                }
+                            }
 
-                            self.cmp(&r_lhs.into(), &r_rhs.into());
                             self.setcc(
                                 &loc.dest(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
                                 CmpOp::try_from(&op)?,
+                                signed,
                             );
-
                             self.allocator.free(r_lhs)?;
-                            self.allocator.free(r_rhs)?;
                         }
                     }
+                    // The operands may have side effects, the value is computed even if unused
+                    BinOp::LogicalOr | BinOp::LogicalAnd if loc.is_none() => {
+                        let r = self.expr_to_reg(expr, OperandSize::Byte)?;
+
+                        self.allocator.free(r)?;
+                    }
                     BinOp::LogicalOr => {
                         if let Some(loc) = loc {
                             self.logical_or(expr, loc, None)?
@@ -510,7 +1698,7 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                         UnOp::LogicalNot => {
                             self.expr(inner_expr, Some(loc))?;
                             self.cmp(&dest, &Source::Immediate(Immediate::UInt(0)));
-                            self.setcc(&dest, CmpOp::Equal);
+                            self.setcc(&dest, CmpOp::Equal, false);
                         }
                         UnOp::Negative => {
                             self.expr(inner_expr, Some(loc))?;
@@ -524,15 +1712,17 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                             self.mov(&r.into(), &loc.dest(OperandSize::Qword), signed)?;
 
                             self.allocator.free(r)?;
+                            self.free_addr(&expr_loc)?;
                         }
                         UnOp::Deref => {
                             let expr_addr = self.expr_addr(expr)?;
                             let src = Source::Memory(Memory {
-                                effective_address: expr_addr,
+                                effective_address: expr_addr.clone(),
                                 size: self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?,
                             });
 
                             self.mov(&src, &dest, signed)?;
+                            self.free_addr(&expr_addr)?;
                         }
                         UnOp::BitwiseNot => {
                             self.expr(inner_expr, Some(loc))?;
@@ -545,12 +1735,23 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                 if let Some(loc) = loc {
                     if let ExprLit::String(str) = lit {
                         let label = self.define_str_literal(str);
+                        let dest = loc.dest(self.ty_size(expr.ty).try_into()?);
 
-                        self.mov(
-                            &Source::Immediate(label.into()),
-                            &loc.dest(self.ty_size(expr.ty).try_into()?),
-                            false,
-                        )?;
+                        if self.pic {
+                            let r = self.allocator.alloc(OperandSize::Qword)?;
+                            let addr = EffectiveAddress {
+                                base: Base::Rip(label),
+                                index: None,
+                                scale: None,
+                                displacement: None,
+                            };
+
+                            self.lea(&r.into(), &addr);
+                            self.mov(&r.into(), &dest, false)?;
+                            self.allocator.free(r)?;
+                        } else {
+                            self.mov(&Source::Immediate(label.into()), &dest, false)?;
+                        }
                     } else {
                         let signed = if let ExprLit::Int(_) = lit {
                             true
@@ -566,21 +1767,32 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     }
                 }
             }
-            ExprKind::Ident(id) => {
+            ExprKind::Ident(_) => {
                 if let Some(loc) = loc {
-                    let expr_addr = &self.variables[&id];
+                    let expr_addr = self.expr_addr(expr)?;
                     let size = self.ty_size(self.ctx.resolve_ty(expr.ty));
 
                     self.mov_loc(&expr_addr.clone().into(), loc, size, false)?;
+                    self.free_addr(&expr_addr)?;
                 }
             }
             ExprKind::Struct(fields) => {
                 if let Some(loc) = loc {
                     let id = match self.ctx.resolve_ty(expr.ty) {
-                        Ty::Struct(id) => id,
+                        Ty::Struct(id, _) => id,
                         _ => unreachable!(),
                     };
 
+                    // Fields which weren't initialized explicitly are zeroed
+                    if fields.len() < self.fields_offsets[id].len() {
+                        match loc {
+                            Location::EffectiveAddress(addr) => {
+                                self.zero(addr, self.struct_size(*id))?;
+                            }
+                            Location::Register(_) => unreachable!(),
+                        }
+                    }
+
                     for (field, expr) in fields {
                         match loc {
                             Location::EffectiveAddress(addr) => {
@@ -594,13 +1806,28 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     }
                 }
             }
+            ExprKind::Tuple(items) => {
+                if let Some(loc) = loc {
+                    let Ty::Tuple(tys) = self.ctx.resolve_ty(expr.ty) else {
+                        unreachable!();
+                    };
+                    let Location::EffectiveAddress(addr) = loc else {
+                        unreachable!();
+                    };
+
+                    for (item, offset) in items.iter().zip(self.tuple_offsets(tys)) {
+                        self.expr(item, Some(&(addr.clone() + Offset(offset)).into()))?;
+                    }
+                }
+            }
             ExprKind::Field(_, _) => {
                 if let Some(loc) = loc {
                     let ty = self.ctx.resolve_ty(expr.ty);
                     let size = self.ty_size(ty);
                     let expr_addr = self.expr_addr(expr)?;
 
-                    self.mov_loc(&expr_addr.into(), loc, size, ty.signed())?;
+                    self.mov_loc(&expr_addr.clone().into(), loc, size, ty.signed())?;
+                    self.free_addr(&expr_addr)?;
                 }
             }
             ExprKind::Cast(expr, ty) => {
@@ -608,6 +1835,20 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     let casted_ty = self.ctx.resolve_ty(ty);
                     let casted_ty_size = self.ty_size(casted_ty).try_into()?;
                     let expr_ty = self.ctx.resolve_ty(expr.ty);
+
+                    // Arrays decay into a pointer to their first element
+                    if let Ty::Array(_) = expr_ty {
+                        let expr_addr = self.expr_addr(expr)?;
+                        let r = self.allocator.alloc(OperandSize::Qword)?;
+
+                        self.lea(&r.into(), &expr_addr);
+                        self.mov(&r.into(), &loc.dest(casted_ty_size), false)?;
+                        self.allocator.free(r)?;
+                        self.free_addr(&expr_addr)?;
+
+                        return Ok(());
+                    }
+
                     let expr_ty_size = self.ty_size(expr_ty).try_into()?;
 
                     if self.ty_size(casted_ty) != self.ty_size(expr_ty) {
@@ -641,18 +1882,203 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     }
                 }
             }
+            ExprKind::Call(callee, args) => self.call(expr, callee, args, loc)?,
+            // The code is emitted verbatim, it's up to the user to preserve
+            // registers the surrounding code relies on
+            ExprKind::InlineAsm(code) => {
+                if self.syntax == Syntax::Att {
+                    self.text.push_str("\t.intel_syntax noprefix\n");
+                }
+                for line in code.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                    self.text.push_str(&format!("\t{line}\n"));
+                }
+                if self.syntax == Syntax::Att {
+                    self.text.push_str("\t.att_syntax prefix\n");
+                }
+            }
+            ExprKind::Print(args) => self.print(args)?,
+            ExprKind::Itoa(value, buf) => self.itoa(value, buf, loc)?,
+            ExprKind::Assert(condition, message) => self.assert(condition, message)?,
+            ExprKind::Block(block) => self.block_expr(&block, loc)?,
+            ExprKind::AlignOf(ty) => {
+                if let Some(loc) = loc {
+                    let align = self.ty_align(self.ctx.resolve_ty(ty));
+
+                    self.mov(
+                        &Source::Immediate(Immediate::UInt(align as u64)),
+                        &loc.dest(OperandSize::Qword),
+                        false,
+                    )?;
+                }
+            }
+            ExprKind::SizeOf(ty) => {
+                if let Some(loc) = loc {
+                    let size = self.ty_size(self.ctx.resolve_ty(ty));
+
+                    self.mov(
+                        &Source::Immediate(Immediate::UInt(size as u64)),
+                        &loc.dest(OperandSize::Qword),
+                        false,
+                    )?;
+                }
+            }
+            ExprKind::Array(items) => {
+                if let Some(loc) = loc {
+                    let ty_arr = match self.ctx.resolve_ty(expr.ty) {
+                        Ty::Array(ty_arr) => ty_arr,
+                        _ => unreachable!(),
+                    };
+                    let elem_size = self.ty_size(ty_arr.ty);
+
+                    for (i, item) in items.iter().enumerate() {
+                        let addr = match loc {
+                            Location::EffectiveAddress(addr) => {
+                                addr.clone() + Offset((i * elem_size) as isize)
+                            }
+                            Location::Register(_) => unreachable!(),
+                        };
+
+                        if self.ty_size(self.ctx.resolve_ty(item.ty)) == elem_size {
+                            self.expr(item, Some(&addr.into()))?;
+                        } else {
+                            let r = self.expr_to_reg(item, elem_size.try_into()?)?;
+
+                            self.mov(&r.into(), &addr.dest(r.size()), ty_arr.ty.signed())?;
+                            self.allocator.free(r)?;
+                        }
+                    }
+                }
+            }
         })
     }
 
     fn global(&mut self, item: &Variable) -> Result<(), Amd64AsmError> {
+        self.static_var(item.name.to_owned(), item)?;
+        self.list_symbol(
+            "global",
+            item.name.to_owned(),
+            &self.variables[&item.id].clone(),
+        );
+
+        Ok(())
+    }
+
+    /// Allocates `var` in `.data` if it has an initializer or in `.bss`
+    /// otherwise, so it's initialized once when the program is loaded. A
+    /// `thread_local` one goes in `.tdata` or `.tbss`, copied for every thread.
+    fn static_var(&mut self, label: String, var: &Variable) -> Result<(), Amd64AsmError> {
+        let ty = self.ctx.resolve_ty(var.ty);
+        let size = self.ty_size(ty);
+        let align = self.ty_align(ty);
+        let addr = EffectiveAddress {
+            base: if var.thread_local {
+                Base::Tls(label.clone())
+            } else {
+                Base::Label(label.clone())
+            },
+            index: None,
+            scale: None,
+            displacement: None,
+        };
+        let (section, object) = match (var.initializer.is_some(), var.thread_local) {
+            (true, false) => (&mut self.data, "@object"),
+            (false, false) => (&mut self.bss, "@object"),
+            (true, true) => (&mut self.tdata, "@tls_object"),
+            (false, true) => (&mut self.tbss, "@tls_object"),
+        };
+
+        if var.pub_ {
+            section.push_str(&format!(".global {label}\n"));
+        }
+
+        // A zero-sized variable takes no storage, the label only gives it an
+        // address. Its initializer can't do anything either.
+        if size == 0 {
+            section.push_str(&format!("{label}:\n"));
+            self.variables.insert(var.id, addr);
+
+            return Ok(());
+        }
+
+        match var.initializer {
+            Some(expr) => {
+                let value = match expr.kind {
+                    ExprKind::Lit(lit) => lit.try_into().ok(),
+                    ExprKind::Unary(
+                        UnOp::Negative,
+                        Expr {
+                            kind: ExprKind::Lit(ExprLit::Int(lit)),
+                            ..
+                        },
+                    ) => Some(Immediate::Int(-lit)),
+                    ExprKind::Unary(
+                        UnOp::Negative,
+                        Expr {
+                            kind: ExprKind::Lit(ExprLit::UInt(lit)),
+                            ..
+                        },
+                    ) => Some(Immediate::Int((*lit as i64).wrapping_neg())),
+                    _ => None,
+                };
+                let directive = match size {
+                    1 => "byte",
+                    2 => "word",
+                    4 => "long",
+                    8 => "quad",
+                    _ => return Err(Amd64AsmError::NonConstInitializer(var.name.to_owned())),
+                };
+                let Some(value) = value else {
+                    return Err(Amd64AsmError::NonConstInitializer(var.name.to_owned()));
+                };
+
+                section.push_str(&formatdoc!(
+                    "
+                    .balign {align}
+                    .type {label}, {object}
+                    .size {label}, {size}
+                    {label}:
+                        .{directive} {value}
+                    "
+                ));
+            }
+            None => {
+                section.push_str(&formatdoc!(
+                    "
+                    .balign {align}
+                    .type {label}, {object}
+                    .size {label}, {size}
+                    {label}:
+                        .zero {size}
+                    "
+                ));
+            }
+        }
+
+        self.variables.insert(var.id, addr);
+
         Ok(())
     }
 
-    fn local(&mut self, stmt: &Variable) -> Result<(), Amd64AsmError> {
+    fn local(&mut self, stmt: &Variable<'ir>) -> Result<(), Amd64AsmError> {
+        if stmt.static_ {
+            let count = self.static_names.entry(stmt.name).or_default();
+            let label = match *count {
+                0 => format!("{}.{}", self.fn_name.unwrap(), stmt.name),
+                n => format!("{}.{}.{n}", self.fn_name.unwrap(), stmt.name),
+            };
+            *count += 1;
+
+            self.static_var(label.clone(), stmt)?;
+            self.list_symbol("static", label, &self.variables[&stmt.id].clone());
+
+            return Ok(());
+        }
+
         let ty = self.ctx.resolve_ty(stmt.ty);
         let size = self.ty_size(ty);
 
-        self.stack_offset -= size as isize;
+        self.stack_offset = -((self.stack_offset.unsigned_abs() + size)
+            .next_multiple_of(self.ty_align(ty)) as isize);
         let addr = EffectiveAddress {
             base: Base::Register(Register::Rbp),
             index: None,
@@ -661,60 +2087,665 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         };
 
         self.variables.insert(stmt.id, addr.clone());
+        self.list_symbol(
+            "local",
+            format!("{}.{}", self.fn_name.unwrap(), stmt.name),
+            &addr,
+        );
 
         if let Some(expr) = stmt.initializer {
-            self.expr(&expr, Some(&addr.into()))?;
+            let addr = self.frame_addr(addr)?;
+
+            self.expr(&expr, Some(&addr.clone().into()))?;
+            self.free_addr(&addr)?;
         }
 
+        let value = stmt.initializer.and_then(|expr| self.const_value(&expr));
+        self.consts.set(stmt.id, value);
+
         Ok(())
     }
 
-    fn define_str_literal(&mut self, literal: &str) -> String {
-        let label = self.label_gen.generate();
-
-        self.data.push_str(&formatdoc!(
-            "
-            {}:
-                .string \"{}\"
-            ",
-            label,
-            literal
-        ));
-
-        label
-    }
+    /// Moves parameters passed in registers into the stack frame and maps the
+    /// ones passed on the stack to their place in the caller's frame.
+    fn fn_preamble(&mut self, item: &ItemFn) -> Result<(), Amd64AsmError> {
+        let tys = item
+            .params
+            .iter()
+            .map(|param| self.ctx.resolve_ty(param.ty))
+            .collect::<Vec<_>>();
+        let (ret_class, classes) =
+            self.classify_params(self.ctx.resolve_ty(item.signature.ret_ty), &tys);
+        let mut registers = Self::ARG_REGISTERS.iter();
 
-    fn ret(&mut self, expr: Option<&Expr>) -> Result<(), Amd64AsmError> {
-        if let Some(expr) = expr {
-            let ty = self.ctx.resolve_ty(expr.ty);
-            let r = self.allocator.alloc(self.ty_size(ty).try_into()?)?;
-            let loc = r.into();
+        if ret_class == ArgClass::Memory {
+            self.stack_offset -= 8;
+            let addr = Register::Rbp.into_effective_addr(self.stack_offset);
 
-            self.expr(expr, Some(&loc))?;
             self.mov(
-                &loc.src(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
-                &Register::Rax.into(),
+                &(*registers.next().unwrap()).into(),
+                &addr.dest(OperandSize::Qword),
                 false,
             )?;
-            self.allocator.free(r)?;
+            self.ret_addr = Some(addr);
+        }
+        // Skip saved rbp and the return address
+        let mut stack_arg_offset = 16;
+
+        for ((param, ty), class) in item.params.iter().zip(tys).zip(classes) {
+            let addr = match class {
+                ArgClass::Integer(eightbytes) => {
+                    self.stack_offset -= (eightbytes * 8) as isize;
+                    let addr = Register::Rbp.into_effective_addr(self.stack_offset);
+
+                    for i in 0..eightbytes {
+                        self.mov(
+                            &(*registers.next().unwrap()).into(),
+                            &(addr.clone() + Offset((i * 8) as isize)).dest(OperandSize::Qword),
+                            false,
+                        )?;
+                    }
+
+                    addr
+                }
+                ArgClass::Memory => {
+                    let addr = Register::Rbp.into_effective_addr(stack_arg_offset);
+                    stack_arg_offset += self.ty_size(ty).next_multiple_of(8) as isize;
+
+                    addr
+                }
+            };
+
+            self.list_symbol("param", format!("{}.{}", item.name, param.name), &addr);
+            self.variables.insert(param.id, addr);
         }
 
         Ok(())
     }
 
-    fn mov(&mut self, src: &Source, dest: &Destination, signed: bool) -> Result<(), Amd64AsmError> {
-        match (dest, src) {
-            (dest @ Destination::Memory(_), src @ Source::Memory(_)) => {
-                let r = self.allocator.alloc(dest.size()).unwrap();
+    /// Classifies the return value and parameters of a function. An aggregate
+    /// returned in memory is written through a pointer passed in as a hidden
+    /// first argument, so it takes up one of the registers.
+    fn classify_params(&self, ret_ty: &Ty, params: &[&Ty]) -> (ArgClass, Vec<ArgClass>) {
+        let ret_class = self.classify_args(&[ret_ty])[0];
 
-                self.mov(src, &r.clone().into(), signed)?;
-                self.mov(&r.clone().into(), dest, signed)?;
+        if ret_class == ArgClass::Memory {
+            let mut tys = vec![&Ty::Ptr(&Ty::Void)];
+            tys.extend_from_slice(params);
 
-                self.allocator.free(r)?;
-            }
-            (dest, src) => {
-                let dest_size = dest.size();
-                let src_size = src.size().unwrap_or_else(|| OperandSize::Qword);
+            (ret_class, self.classify_args(&tys).split_off(1))
+        } else {
+            (ret_class, self.classify_args(params))
+        }
+    }
+
+    fn classify_args(&self, tys: &[&Ty]) -> Vec<ArgClass> {
+        let mut registers = Self::ARG_REGISTERS.len();
+
+        tys.iter()
+            .map(|ty| {
+                let eightbytes = self.ty_size(ty).div_ceil(8);
+
+                match ty {
+                    // Aggregates larger than two eightbytes are always passed in memory
+                    _ if ty.aggregate() && eightbytes > 2 => ArgClass::Memory,
+                    // An argument is passed on the stack as a whole if it doesn't fit in the registers left
+                    _ if eightbytes > registers => ArgClass::Memory,
+                    _ => {
+                        registers -= eightbytes;
+
+                        ArgClass::Integer(eightbytes)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn call(
+        &mut self,
+        expr: &Expr,
+        callee: &Expr<'ir>,
+        args: &[Expr<'ir>],
+        loc: Option<&Location>,
+    ) -> Result<(), Amd64AsmError> {
+        let ret_ty = self.ctx.resolve_ty(expr.ty);
+        let ret_size = self.ty_size(ret_ty);
+        let tys = args
+            .iter()
+            .map(|arg| self.ctx.resolve_ty(arg.ty))
+            .collect::<Vec<_>>();
+        let (ret_class, classes) = self.classify_params(ret_ty, &tys);
+        let mut ret_temp_size = 0;
+
+        // The address has to be taken before the stack pointer moves, `loc` may be relative to it
+        let ret_addr_pushed_at = if ret_class == ArgClass::Memory {
+            let r = self.allocator.alloc(OperandSize::Qword)?;
+
+            match loc {
+                Some(Location::EffectiveAddress(addr)) => self.lea(&r.into(), addr),
+                Some(Location::Register(_)) => unreachable!(),
+                None => {
+                    ret_temp_size = ret_size.next_multiple_of(16);
+
+                    self.sub(
+                        &Register::Rsp.into(),
+                        &(ret_temp_size as u64).into(),
+                        &Register::Rsp.into(),
+                        false,
+                    )?;
+                    self.stack_pushed += ret_temp_size;
+                    self.lea(&r.into(), &Register::Rsp.into());
+                }
+            }
+
+            self.push(&r.into());
+            self.allocator.free(r)?;
+
+            Some(self.stack_pushed)
+        } else {
+            None
+        };
+
+        // Registers holding values of the enclosing expression would be clobbered by the callee
+        let saved = self
+            .allocator
+            .used()
+            .iter()
+            .filter_map(|&i| self.allocator.get(i))
+            .filter(|r| Self::CALLER_SAVED_REGISTERS.contains(r))
+            .filter(|r| match loc {
+                Some(Location::Register(loc)) => &loc.resize(OperandSize::Qword) != r,
+                _ => true,
+            })
+            .collect::<Vec<_>>();
+
+        for r in &saved {
+            self.push(&(*r).into());
+        }
+
+        let stack_args_size: usize = tys
+            .iter()
+            .zip(&classes)
+            .filter(|(_, class)| class == &&ArgClass::Memory)
+            .map(|(ty, _)| self.ty_size(ty).next_multiple_of(8))
+            .sum();
+        // The stack has to be aligned to 16 bytes at the call instruction
+        let padding = (self.stack_pushed + stack_args_size) % 16;
+
+        if padding > 0 {
+            self.sub(
+                &Register::Rsp.into(),
+                &(padding as u64).into(),
+                &Register::Rsp.into(),
+                false,
+            )?;
+            self.stack_pushed += padding;
+        }
+
+        // Arguments are pushed right to left, so the first one ends up on top of the stack
+        for (arg, class) in args.iter().zip(&classes).rev() {
+            if class == &ArgClass::Memory {
+                self.push_arg(arg)?;
+            }
+        }
+
+        // Functions called by name don't need their address in a register
+        let (name, noreturn) = match callee.kind {
+            ExprKind::Unary(
+                UnOp::Address,
+                Expr {
+                    kind: ExprKind::Ident(id),
+                    ..
+                },
+            ) => match self.ctx.ir.get_node(*id) {
+                Node::Item(Item::Fn(item)) => (Some(item.name), item.noreturn),
+                _ => (None, false),
+            },
+            _ => (None, false),
+        };
+
+        if name.is_none() {
+            let r = self.expr_to_reg(callee, OperandSize::Qword)?;
+
+            self.push(&r.into());
+            self.allocator.free(r)?;
+        }
+
+        let mut registers = 0;
+
+        for (arg, class) in args.iter().zip(&classes).rev() {
+            if let ArgClass::Integer(eightbytes) = class {
+                self.push_arg(arg)?;
+                registers += eightbytes;
+            }
+        }
+
+        let first_register = ret_addr_pushed_at.is_some() as usize;
+
+        for r in &Self::ARG_REGISTERS[first_register..first_register + registers] {
+            self.pop(&(*r).into());
+        }
+
+        if let Some(pushed_at) = ret_addr_pushed_at {
+            let addr = Register::Rsp.into_effective_addr((self.stack_pushed - pushed_at) as isize);
+
+            self.mov(
+                &addr.src(OperandSize::Qword),
+                &Self::ARG_REGISTERS[0].into(),
+                false,
+            )?;
+        }
+
+        let variadic = matches!(self.ctx.resolve_ty(callee.ty), Ty::Ptr(Ty::Fn(_, _, true)));
+        // `al` is clobbered below for variadic calls, so the callee address can't live in `rax`
+        let callee_register = if variadic {
+            Register::R11
+        } else {
+            Register::Rax
+        };
+
+        if name.is_none() {
+            self.pop(&callee_register.into());
+        }
+        // Variadic functions expect the number of vector registers used in `al`
+        if variadic {
+            self.text.push_str("\tmov eax, 0\n");
+        }
+
+        match name {
+            Some(name) if self.pic => self.text.push_str(&format!("\tcall {name}@PLT\n")),
+            Some(name) => self.text.push_str(&format!("\tcall {name}\n")),
+            None => self.text.push_str(&format!("\tcall {callee_register}\n")),
+        }
+
+        // Traps instead of running into whatever follows if the callee returns anyway
+        if noreturn {
+            self.text.push_str("\tud2\n");
+        }
+
+        if stack_args_size + padding > 0 {
+            self.add(
+                &Register::Rsp.into(),
+                &((stack_args_size + padding) as u64).into(),
+                &Register::Rsp.into(),
+                false,
+            )?;
+            self.stack_pushed -= stack_args_size + padding;
+        }
+
+        for r in saved.iter().rev() {
+            self.pop(&(*r).into());
+        }
+
+        match (ret_class, loc) {
+            // The callee already wrote the value through the hidden pointer
+            (ArgClass::Memory, _) => {
+                self.add(
+                    &Register::Rsp.into(),
+                    &((8 + ret_temp_size) as u64).into(),
+                    &Register::Rsp.into(),
+                    false,
+                )?;
+                self.stack_pushed -= 8 + ret_temp_size;
+            }
+            (_, None) => (),
+            (_, Some(Location::EffectiveAddress(addr))) if ret_ty.aggregate() => {
+                self.store_eightbytes(&[Register::Rax, Register::Rdx], addr, ret_size)?;
+            }
+            (_, Some(loc)) => {
+                let size = ret_size.try_into()?;
+
+                self.mov(&Register::Rax.resize(size).into(), &loc.dest(size), false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stores `size` bytes packed into eightbytes in `registers` at `addr`,
+    /// without writing past the end of the value.
+    fn store_eightbytes(
+        &mut self,
+        registers: &[Register],
+        addr: &EffectiveAddress,
+        size: usize,
+    ) -> Result<(), Amd64AsmError> {
+        for (i, r) in registers.iter().enumerate().take(size.div_ceil(8)) {
+            let mut offset = i * 8;
+            let mut remaining = std::cmp::min(8, size - offset);
+
+            for chunk_size in [8, 4, 2, 1] {
+                if remaining >= chunk_size {
+                    let chunk_size_op = chunk_size.try_into()?;
+
+                    self.mov(
+                        &r.resize(chunk_size_op).into(),
+                        &(addr.clone() + Offset(offset as isize)).dest(chunk_size_op),
+                        false,
+                    )?;
+                    offset += chunk_size;
+                    remaining -= chunk_size;
+
+                    if remaining > 0 {
+                        self.text
+                            .push_str(&format!("\tshr {r}, {}\n", chunk_size * 8));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the arguments of `print!` one after another, integers are
+    /// printed in decimal and strings up to their terminating zero.
+    fn print(&mut self, args: &[Expr<'ir>]) -> Result<(), Amd64AsmError> {
+        self.print_used = true;
+
+        for arg in args {
+            let routine = match self.ctx.resolve_ty(arg.ty) {
+                Ty::Ptr(_) => "__meraki_print_str",
+                ty if ty.signed() => "__meraki_print_i64",
+                _ => "__meraki_print_u64",
+            };
+            let r = self.expr_to_reg(arg, OperandSize::Qword)?;
+
+            self.push(&r.into());
+            self.text.push_str(&format!("\tcall {routine}\n"));
+            self.pop(&r.into());
+            self.allocator.free(r)?;
+        }
+
+        Ok(())
+    }
+
+    /// Jumps over the abort when `condition` holds, otherwise passes the
+    /// message to the routine reporting the failed assertion.
+    fn assert(&mut self, condition: &Expr<'ir>, message: &str) -> Result<(), Amd64AsmError> {
+        if !self.assertions {
+            return Ok(());
+        }
+
+        self.assert_used = true;
+
+        let message = format!("{message}\n");
+        let message_label = self.define_str_literal(&message);
+        let end_label = self.label_gen.generate();
+        let r = self.expr_to_reg(condition, OperandSize::Byte)?;
+
+        self.cmp(&r.into(), &Source::Immediate(Immediate::UInt(0)));
+        self.allocator.free(r)?;
+        self.jcc(&end_label, Jump::NotEqual);
+        self.text.push_str(&formatdoc!(
+            "
+            \tlea rsi, [rip + {message_label}]
+            \tmov rdx, {}
+            \tjmp __meraki_assert_failed
+            ",
+            message.len()
+        ));
+        self.write_label(&end_label);
+
+        Ok(())
+    }
+
+    /// Converts `value` into decimal digits written into `buf`, the count of
+    /// written characters ends up in `loc`.
+    fn itoa(
+        &mut self,
+        value: &Expr<'ir>,
+        buf: &Expr<'ir>,
+        loc: Option<&Location>,
+    ) -> Result<(), Amd64AsmError> {
+        self.itoa_used = true;
+
+        let routine = if self.ctx.resolve_ty(value.ty).signed() {
+            "__meraki_itoa_i64"
+        } else {
+            "__meraki_itoa_u64"
+        };
+        let r_value = self.expr_to_reg(value, OperandSize::Qword)?;
+        let r_buf = self.expr_to_reg(buf, OperandSize::Qword)?;
+
+        self.push(&r_value.into());
+        self.push(&r_buf.into());
+        self.text.push_str(&format!("\tcall {routine}\n"));
+        self.pop(&r_buf.into());
+        self.pop(&r_value.into());
+        self.allocator.free(r_buf)?;
+        self.allocator.free(r_value)?;
+
+        if let Some(loc) = loc {
+            self.mov(&Register::Rax.into(), &loc.dest(OperandSize::Qword), false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the function can reuse its frame for a call to itself. All the
+    /// arguments have to be passed in registers, the stack ones would
+    /// overwrite the caller's frame, and so does the pointer to the returned
+    /// value.
+    fn tail_callable(&self, item: &ItemFn) -> bool {
+        let tys = item
+            .params
+            .iter()
+            .map(|param| self.ctx.resolve_ty(param.ty))
+            .collect::<Vec<_>>();
+        let (ret_class, classes) =
+            self.classify_params(self.ctx.resolve_ty(item.signature.ret_ty), &tys);
+
+        ret_class != ArgClass::Memory
+            && classes
+                .iter()
+                .all(|class| matches!(class, ArgClass::Integer(_)))
+    }
+
+    /// Whether evaluating the expression calls a function, which clobbers the
+    /// caller saved registers. The routines behind intrinsics preserve them.
+    fn has_call(expr: &Expr) -> bool {
+        match expr.kind {
+            // Statements of the block aren't walked
+            ExprKind::Call(..) | ExprKind::Block(_) => true,
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Itoa(lhs, rhs) => {
+                Self::has_call(lhs) || Self::has_call(rhs)
+            }
+            ExprKind::Unary(_, expr)
+            | ExprKind::Field(expr, _)
+            | ExprKind::Cast(expr, _)
+            | ExprKind::Assert(expr, _) => Self::has_call(expr),
+            ExprKind::Struct(fields) => fields.iter().any(|(_, expr)| Self::has_call(expr)),
+            ExprKind::Array(exprs) | ExprKind::Tuple(exprs) | ExprKind::Print(exprs) => {
+                exprs.iter().any(Self::has_call)
+            }
+            ExprKind::Ident(_)
+            | ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => false,
+        }
+    }
+
+    fn self_call(&self, callee: &Expr) -> bool {
+        match callee.kind {
+            ExprKind::Unary(
+                UnOp::Address,
+                Expr {
+                    kind: ExprKind::Ident(id),
+                    ..
+                },
+            ) if self.body_label.is_some() => match self.ctx.ir.get_node(*id) {
+                Node::Item(Item::Fn(item)) => Some(item.name) == self.fn_name,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Passes the arguments the same way `call` does, but jumps back to the
+    /// start of the function body instead, which stores them over the
+    /// current parameters.
+    fn tail_call(&mut self, args: &[Expr<'ir>]) -> Result<(), Amd64AsmError> {
+        let tys = args
+            .iter()
+            .map(|arg| self.ctx.resolve_ty(arg.ty))
+            .collect::<Vec<_>>();
+        let classes = self.classify_args(&tys);
+        let mut registers = 0;
+
+        for (arg, class) in args.iter().zip(&classes).rev() {
+            if let ArgClass::Integer(eightbytes) = class {
+                self.push_arg(arg)?;
+                registers += eightbytes;
+            }
+        }
+
+        for r in &Self::ARG_REGISTERS[..registers] {
+            self.pop(&(*r).into());
+        }
+
+        self.jcc(&self.body_label.clone().unwrap(), Jump::Unconditional);
+
+        Ok(())
+    }
+
+    /// Pushes `arg` onto the stack, aggregates are copied in place.
+    fn push_arg(&mut self, arg: &Expr<'ir>) -> Result<(), Amd64AsmError> {
+        let ty = self.ctx.resolve_ty(arg.ty);
+
+        match ty {
+            Ty::Struct(..) | Ty::Array(_) | Ty::Tuple(_) => {
+                let size = self.ty_size(ty).next_multiple_of(8);
+
+                // A zero-sized argument is still evaluated but takes no stack space
+                if size > 0 {
+                    self.sub(
+                        &Register::Rsp.into(),
+                        &(size as u64).into(),
+                        &Register::Rsp.into(),
+                        false,
+                    )?;
+                    self.stack_pushed += size;
+                }
+                self.expr(arg, Some(&EffectiveAddress::from(Register::Rsp).into()))?;
+            }
+            _ => {
+                let r = self.expr_to_reg(arg, OperandSize::Qword)?;
+
+                self.push(&r.into());
+                self.allocator.free(r)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Escapes in the literal were decoded by the lexer, so every byte which
+    /// isn't printable ASCII is written as an octal escape for the assembler.
+    fn define_str_literal(&mut self, literal: &str) -> String {
+        let label = self.label_gen.generate();
+        let literal = literal
+            .bytes()
+            .map(|byte| match byte {
+                b'"' | b'\\' => format!("\\{}", byte as char),
+                b' '..=b'~' => char::from(byte).to_string(),
+                _ => format!("\\{byte:03o}"),
+            })
+            .collect::<String>();
+
+        self.data.push_str(&formatdoc!(
+            "
+            {}:
+                .string \"{}\"
+            ",
+            label,
+            literal
+        ));
+
+        label
+    }
+
+    fn ret(&mut self, expr: Option<&Expr<'ir>>) -> Result<(), Amd64AsmError> {
+        if let Some(expr) = expr {
+            let ty = self.ctx.resolve_ty(expr.ty);
+
+            // Aggregates too large for registers are written to the memory provided by the caller
+            if let Some(ret_addr) = self.ret_addr.clone() {
+                let r = self.allocator.alloc(OperandSize::Qword)?;
+
+                self.mov(&ret_addr.src(OperandSize::Qword), &r.into(), false)?;
+                self.expr(expr, Some(&EffectiveAddress::from(r).into()))?;
+                self.mov(&r.into(), &Register::Rax.into(), false)?;
+                self.allocator.free(r)?;
+
+                return Ok(());
+            }
+
+            // Small aggregates are returned in rax and rdx
+            if ty.aggregate() {
+                let size = self.ty_size(ty).next_multiple_of(8);
+                let addr = EffectiveAddress::from(Register::Rsp);
+
+                self.sub(
+                    &Register::Rsp.into(),
+                    &(size as u64).into(),
+                    &Register::Rsp.into(),
+                    false,
+                )?;
+                self.stack_pushed += size;
+                self.expr(expr, Some(&addr.clone().into()))?;
+
+                for (i, r) in [Register::Rax, Register::Rdx]
+                    .into_iter()
+                    .take(size / 8)
+                    .enumerate()
+                {
+                    self.mov(
+                        &(addr.clone() + Offset((i * 8) as isize)).src(OperandSize::Qword),
+                        &r.into(),
+                        false,
+                    )?;
+                }
+
+                self.add(
+                    &Register::Rsp.into(),
+                    &(size as u64).into(),
+                    &Register::Rsp.into(),
+                    false,
+                )?;
+                self.stack_pushed -= size;
+
+                return Ok(());
+            }
+
+            let r = self.allocator.alloc(self.ty_size(ty).try_into()?)?;
+            let loc = r.into();
+
+            self.expr(expr, Some(&loc))?;
+            self.mov(
+                &loc.src(self.ty_size(self.ctx.resolve_ty(expr.ty)).try_into()?),
+                &Register::Rax.into(),
+                false,
+            )?;
+            self.allocator.free(r)?;
+        }
+
+        Ok(())
+    }
+
+    fn mov(&mut self, src: &Source, dest: &Destination, signed: bool) -> Result<(), Amd64AsmError> {
+        match (dest, src) {
+            (dest @ Destination::Memory(_), src @ Source::Memory(_)) => {
+                let r = self.allocator.alloc(dest.size()).unwrap();
+
+                self.mov(src, &r.clone().into(), signed)?;
+                self.mov(&r.clone().into(), dest, signed)?;
+
+                self.allocator.free(r)?;
+            }
+            (dest, src) => {
+                let dest_size = dest.size();
+                let src_size = src.size().unwrap_or_else(|| OperandSize::Qword);
 
                 if dest_size == OperandSize::Qword && src_size == OperandSize::Dword {
                     // On x86_64 you can move 32bit value in 32bit register, and upper 32bits of the register will be zeroed
@@ -747,6 +2778,11 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         size: usize,
         signed: bool,
     ) -> Result<(), Amd64AsmError> {
+        // Moving a zero-sized value doesn't move anything
+        if size == 0 {
+            return Ok(());
+        }
+
         match (dest, src) {
             (Location::EffectiveAddress(lhs), Location::EffectiveAddress(rhs)) => {
                 match OperandSize::try_from(size) {
@@ -770,53 +2806,112 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
 
     fn push(&mut self, src: &Source) {
         self.text.push_str(&format!("\tpush {src}\n"));
+        self.stack_pushed += 8;
     }
 
     fn pop(&mut self, dest: &Destination) {
         self.text.push_str(&format!("\tpop {dest}\n"));
+        self.stack_pushed -= 8;
     }
 
     fn ty_size(&self, ty: &Ty) -> usize {
         match ty {
             Ty::Int(int) if int == &IntTy::Isize => Self::BITNESS / 8,
             Ty::UInt(uint) if uint == &UintTy::Usize => Self::BITNESS / 8,
-            Ty::Ptr(_) | Ty::Array(_) => Self::BITNESS / 8,
-            Ty::Struct(id) => self.struct_size(*id),
+            Ty::Ptr(_) | Ty::Null => Self::BITNESS / 8,
+            Ty::Struct(id, _) => self.struct_size(*id),
+            Ty::Tuple(tys) => match self.tuple_offsets(tys).last() {
+                Some(offset) => (*offset as usize + self.ty_size(tys.last().unwrap()))
+                    .next_multiple_of(self.ty_align(ty)),
+                None => 0,
+            },
             _ => ty.size(|ty| self.ty_size(ty)),
         }
     }
 
+    /// Offsets of the elements of a tuple, which is laid out like a struct
+    /// with a field for each of them.
+    fn tuple_offsets(&self, tys: &[&Ty]) -> Vec<isize> {
+        let mut offset: usize = 0;
+
+        tys.iter()
+            .map(|ty| {
+                offset = offset.next_multiple_of(self.ty_align(ty));
+                let elem_offset = offset;
+                offset += self.ty_size(ty);
+
+                elem_offset as isize
+            })
+            .collect()
+    }
+
     fn struct_size(&self, id: Id) -> usize {
-        let mut size = 0;
-        let mut max = 0;
+        let mut size: usize = 0;
 
         match self.ctx.ir.get_node(id) {
-            Node::Item(Item::Struct(fields)) => {
+            Node::Item(Item::Struct(fields, packed)) => {
                 for (_, ty) in *fields {
-                    let ty_size = self.ty_size(ty);
-
-                    if max < ty_size {
-                        max = ty_size;
+                    if !packed {
+                        size = size.next_multiple_of(self.ty_align(ty));
                     }
-
-                    size = (size + ty_size).next_multiple_of(ty_size);
+                    size += self.ty_size(ty);
                 }
             }
+            // Fields of a union overlap, it's as large as its largest field
+            Node::Item(Item::Union(fields)) => {
+                size = fields
+                    .iter()
+                    .map(|(_, ty)| self.ty_size(ty))
+                    .max()
+                    .unwrap_or(0);
+            }
+            _ => unreachable!(),
+        }
+
+        size.next_multiple_of(self.struct_align(id))
+    }
+
+    /// Structs and unions are aligned like their most aligned field, packed
+    /// structs aren't aligned at all
+    fn struct_align(&self, id: Id) -> usize {
+        match self.ctx.ir.get_node(id) {
+            Node::Item(Item::Struct(_, true)) => 1,
+            Node::Item(Item::Struct(fields, false) | Item::Union(fields)) => fields
+                .iter()
+                .map(|(_, ty)| self.ty_align(ty))
+                .max()
+                .unwrap_or(1),
             _ => unreachable!(),
         }
+    }
 
-        size.next_multiple_of(max)
+    /// Natural alignment of a type, which is never larger than a word. Arrays
+    /// are aligned like their elements, structs like their most aligned field
+    /// and tuples like their most aligned element.
+    fn ty_align(&self, ty: &Ty) -> usize {
+        match ty {
+            Ty::Array(array) => self.ty_align(array.ty),
+            Ty::Tuple(tys) => tys.iter().map(|ty| self.ty_align(ty)).max().unwrap_or(1),
+            Ty::Struct(id, _) => self.struct_align(*id),
+            ty => self.ty_size(ty).clamp(1, Self::BITNESS / 8),
+        }
     }
 
     fn set_ty_fields_offsets(&mut self, id: Id) {
         match self.ctx.ir.get_node(id) {
-            Node::Item(Item::Struct(fields)) => {
+            Node::Item(Item::Struct(fields, packed)) => {
                 let mut offset: usize = 0;
 
+                // A struct without fields still has (no) offsets
+                self.fields_offsets.entry(id).or_default();
                 for (field, ty) in fields.iter() {
                     let size = self.ty_size(ty);
 
-                    offset = offset.next_multiple_of(size);
+                    // Fields of a packed struct may be misaligned, which
+                    // `mov` doesn't mind
+                    if !packed {
+                        offset = offset.next_multiple_of(self.ty_align(ty));
+                    }
                     self.fields_offsets
                         .entry(id)
                         .or_default()
@@ -824,30 +2919,151 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
                     offset += size;
                 }
             }
+            // Every field of a union starts at its beginning
+            Node::Item(Item::Union(fields)) => {
+                let offsets = self.fields_offsets.entry(id).or_default();
+
+                for (field, _) in fields.iter() {
+                    offsets.insert(field, Offset(0));
+                }
+            }
             _ => unreachable!(),
         }
     }
 
-    fn stack_frame_size(&self, block: &Block) -> usize {
-        let mut size = 0;
+    fn stack_frame_size(&self, item: &ItemFn, block: &Block) -> usize {
+        let tys = item
+            .params
+            .iter()
+            .map(|param| self.ctx.resolve_ty(param.ty))
+            .collect::<Vec<_>>();
+        let (ret_class, classes) =
+            self.classify_params(self.ctx.resolve_ty(item.signature.ret_ty), &tys);
+        let mut size: usize = classes
+            .into_iter()
+            .map(|class| match class {
+                ArgClass::Integer(eightbytes) => eightbytes * 8,
+                ArgClass::Memory => 0,
+            })
+            .sum();
+
+        if ret_class == ArgClass::Memory {
+            size += 8;
+        }
+        if self.stack_protector {
+            size += 8;
+        }
+
+        size += self.block_frame_size(block, 0);
+
+        // Keep the stack aligned to 16 bytes for calls
+        size.next_multiple_of(16)
+    }
+
+    /// Size of the stack needed by locals of `block` placed from `offset` on,
+    /// padded the same way as `local` does. Sibling blocks don't live at the
+    /// same time, so they start at the same offset and only the largest of
+    /// them counts.
+    fn block_frame_size(&self, block: &Block, mut offset: usize) -> usize {
+        let mut size = offset;
 
         for stmt in block.0 {
-            match stmt {
-                Stmt::Local(stmt) => {
-                    size += self.ty_size(self.ctx.resolve_ty(stmt.ty));
-                }
-                Stmt::Item(_) | Stmt::Expr(_) | Stmt::Return(_) => (),
-            }
+            size = std::cmp::max(size, self.stmt_frame_size(stmt, &mut offset));
+        }
+
+        if let Some(expr) = block.1 {
+            size = std::cmp::max(size, self.expr_frame_size(expr, offset));
         }
 
         size
     }
 
-    fn add(
-        &mut self,
-        lhs: &Source,
-        rhs: &Source,
-        dest: &Destination,
+    /// Size of the stack needed by `stmt` placed from `offset` on, which is
+    /// moved past the slot of a local it declares.
+    fn stmt_frame_size(&self, stmt: &Stmt, offset: &mut usize) -> usize {
+        match stmt {
+            Stmt::Local(stmt) if !stmt.static_ => {
+                let ty = self.ctx.resolve_ty(stmt.ty);
+
+                *offset = (*offset + self.ty_size(ty)).next_multiple_of(self.ty_align(ty));
+
+                // The slot is taken before the initializer is evaluated
+                match &stmt.initializer {
+                    Some(expr) => std::cmp::max(*offset, self.expr_frame_size(expr, *offset)),
+                    None => *offset,
+                }
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                let mut size = std::cmp::max(
+                    self.expr_frame_size(condition, *offset),
+                    self.block_frame_size(consequence, *offset),
+                );
+
+                if let Some(alternative) = alternative {
+                    size = std::cmp::max(size, self.block_frame_size(alternative, *offset));
+                }
+
+                size
+            }
+            // Locals declared by the initializer live as long as the loop
+            Stmt::For(initializer, condition, increment, block) => {
+                let mut offset = *offset;
+                let mut size = offset;
+
+                for stmt in *initializer {
+                    size = std::cmp::max(size, self.stmt_frame_size(stmt, &mut offset));
+                }
+
+                for expr in condition.iter().chain(increment) {
+                    size = std::cmp::max(size, self.expr_frame_size(expr, offset));
+                }
+
+                std::cmp::max(size, self.block_frame_size(block, offset))
+            }
+            Stmt::Expr(expr) | Stmt::Return(Some(expr)) => self.expr_frame_size(expr, *offset),
+            Stmt::Local(_)
+            | Stmt::Item(_)
+            | Stmt::Return(None)
+            | Stmt::Label(_)
+            | Stmt::Goto(_)
+            | Stmt::Continue
+            | Stmt::Break => *offset,
+        }
+    }
+
+    /// Size of the stack needed by locals of block expressions nested in
+    /// `expr`, placed from `offset` on.
+    fn expr_frame_size(&self, expr: &Expr, offset: usize) -> usize {
+        let exprs = match expr.kind {
+            ExprKind::Block(block) => return self.block_frame_size(&block, offset),
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Itoa(lhs, rhs) => vec![lhs, rhs],
+            ExprKind::Unary(_, expr)
+            | ExprKind::Field(expr, _)
+            | ExprKind::Cast(expr, _)
+            | ExprKind::Assert(expr, _) => vec![expr],
+            ExprKind::Struct(fields) => fields.iter().map(|(_, expr)| expr).collect(),
+            ExprKind::Array(exprs) | ExprKind::Tuple(exprs) | ExprKind::Print(exprs) => {
+                exprs.iter().collect()
+            }
+            ExprKind::Call(callee, args) => std::iter::once(callee).chain(args).collect(),
+            ExprKind::Ident(_)
+            | ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => Vec::new(),
+        };
+
+        exprs
+            .into_iter()
+            .map(|expr| self.expr_frame_size(expr, offset))
+            .fold(offset, std::cmp::max)
+    }
+
+    fn add(
+        &mut self,
+        lhs: &Source,
+        rhs: &Source,
+        dest: &Destination,
         signed: bool,
     ) -> Result<(), Amd64AsmError> {
         lhs.size().map(|size| assert!(size == dest.size()));
@@ -897,6 +3113,12 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         Ok(())
     }
 
+    /// Uses the one operand form, which leaves the double width product in
+    /// `rdx:rax`. The low half is the same for `mul` and `imul`, so a
+    /// truncating multiplication could use either, they only differ in the
+    /// high half. The overflow flag tells whether it's significant, for `mul`
+    /// when the product doesn't fit unsigned and for `imul` when it doesn't fit
+    /// signed, that's why `signed` picks the instruction.
     fn mul(
         &mut self,
         lhs: &Source,
@@ -923,7 +3145,11 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
             self.push(&Register::Rdx.into());
         }
 
-        self.text.push_str(&format!("\timul {dest}\n"));
+        if signed {
+            self.text.push_str(&format!("\timul {dest}\n"));
+        } else {
+            self.text.push_str(&format!("\tmul {dest}\n"));
+        }
 
         if self.allocator.is_used(&Register::Rdx) {
             self.pop(&Register::Rdx.into());
@@ -941,24 +3167,44 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         rhs: &Source,
         dest: &Destination,
         signed: bool,
+        result: Register,
     ) -> Result<(), Amd64AsmError> {
         lhs.size().map(|size| assert_eq!(size, dest.size()));
         rhs.size().map(|size| assert_eq!(size, dest.size()));
         assert!(!(lhs == dest && rhs == dest));
 
-        self.mov(lhs, &Register::Rax.into(), signed)?;
+        self.mov(lhs, &Register::Rax.resize(dest.size()).into(), signed)?;
         self.mov(rhs, dest, signed)?;
         if self.allocator.is_used(&Register::Rdx) {
             self.push(&Register::Rdx.into());
         }
 
+        // The dividend is twice as wide as the divisor: `dx:ax`, `edx:eax` or
+        // `rdx:rax`, and just `ax` for a byte
+        let (extend, instr) = match (signed, dest.size()) {
+            (true, OperandSize::Byte) => ("cbw", "idiv"),
+            (true, OperandSize::Word) => ("cwd", "idiv"),
+            (true, OperandSize::Dword) => ("cdq", "idiv"),
+            (true, OperandSize::Qword) => ("cqo", "idiv"),
+            (false, OperandSize::Byte) => ("movzx ax, al", "div"),
+            (false, _) => ("xor edx, edx", "div"),
+        };
+
         self.text.push_str(&formatdoc!(
             "
-            \tcqo
-            \tidiv {dest}
+            \t{extend}
+            \t{instr} {dest}
             ",
         ));
 
+        // `rdx` is restored below, the remainder has to be moved out of it first
+        match (result, dest.size()) {
+            (Register::Rax, _) => (),
+            // Byte sized division leaves the remainder in `ah` instead
+            (_, OperandSize::Byte) => self.text.push_str("\tshr ax, 8\n"),
+            _ => self.mov(&result.into(), &Register::Rax.into(), false)?,
+        }
+
         if self.allocator.is_used(&Register::Rdx) {
             self.pop(&Register::Rdx.into());
         }
@@ -968,8 +3214,155 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         Ok(())
     }
 
-    fn setcc(&mut self, dest: &Destination, condition: CmpOp) {
-        self.text.push_str(&format!("\t{condition} {dest}\n"));
+    /// Power of two the expression is a constant of, as a shift amount. Only
+    /// used with `-O1`, multiplying or dividing by it becomes a shift.
+    fn pow2_lit(&self, expr: &Expr) -> Option<u32> {
+        let lit = u64::try_from(self.const_value(expr)?).ok()?;
+
+        lit.is_power_of_two().then(|| lit.trailing_zeros())
+    }
+
+    /// Value of an integer expression made of literals and locals holding a
+    /// known constant, computed the way the generated code would. Only with
+    /// `-O1`, and never for an operation that would trap at runtime.
+    fn const_value(&self, expr: &Expr) -> Option<i128> {
+        let ty = self.ctx.resolve_ty(expr.ty);
+
+        if self.opt_level < 1 || !ty.int() {
+            return None;
+        }
+
+        let bits = self.ty_size(ty) as u32 * 8;
+        let mask = (1i128 << bits) - 1;
+        let signed = ty.signed();
+        let value = match expr.kind {
+            ExprKind::Lit(ExprLit::UInt(lit)) => lit.into(),
+            ExprKind::Lit(ExprLit::Int(lit)) => lit.into(),
+            ExprKind::Ident(id) => return self.consts.get(id),
+            ExprKind::Cast(expr, _) => self.const_value(expr)?,
+            ExprKind::Unary(UnOp::Negative, expr) => -self.const_value(expr)?,
+            ExprKind::Unary(UnOp::BitwiseNot, expr) => !self.const_value(expr)?,
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.const_value(lhs)?, self.const_value(rhs)?);
+
+                match op {
+                    BinOp::Add => lhs + rhs,
+                    BinOp::Sub => lhs - rhs,
+                    BinOp::Mul => lhs.checked_mul(rhs)?,
+                    // Dividing by zero or the most negative value by -1 faults
+                    BinOp::Div | BinOp::Rem if rhs == 0 || (signed && rhs == -1) => return None,
+                    BinOp::Div => lhs / rhs,
+                    BinOp::Rem => lhs % rhs,
+                    BinOp::BitwiseAnd => lhs & rhs,
+                    BinOp::BitwiseOr => lhs | rhs,
+                    // The shift count is masked at runtime, a larger one is left to it
+                    BinOp::Shl if (0..bits.into()).contains(&rhs) => (lhs & mask) << rhs,
+                    // `shr` is a logical shift even for signed integers
+                    BinOp::Shr if (0..bits.into()).contains(&rhs) => (lhs & mask) >> rhs,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        let wrapped = match value & mask {
+            value if signed && value >> (bits - 1) == 1 => value - (1 << bits),
+            value => value,
+        };
+
+        match expr.kind {
+            ExprKind::Binary(BinOp::Add | BinOp::Sub | BinOp::Mul, ..)
+                if self.overflow == Overflow::Trap && wrapped != value =>
+            {
+                None
+            }
+            _ => Some(wrapped),
+        }
+    }
+
+    /// Literal a constant expression is replaced with. A 64 bit value has to
+    /// fit in the sign extended 32 bit immediate of `mov`.
+    fn const_lit(&self, expr: &Expr) -> Option<ExprLit<'ir>> {
+        if let ExprKind::Lit(_) = expr.kind {
+            return None;
+        }
+
+        let value = self.const_value(expr)?;
+
+        if self.ty_size(self.ctx.resolve_ty(expr.ty)) == 8 && i32::try_from(value).is_err() {
+            return None;
+        }
+
+        Some(match self.ctx.resolve_ty(expr.ty).signed() {
+            true => ExprLit::Int(value as i64),
+            false => ExprLit::UInt(value as u64),
+        })
+    }
+
+    /// Divides `lhs` by `1 << shift`. An arithmetic shift rounds toward
+    /// negative infinity, so a negative dividend is biased by `(1 << shift) - 1`
+    /// first to round toward zero like `idiv` does.
+    fn div_pow2(
+        &mut self,
+        lhs: &Expr<'ir>,
+        shift: u32,
+        loc: &Location,
+        signed: bool,
+    ) -> Result<(), Amd64AsmError> {
+        let size = self.ty_size(self.ctx.resolve_ty(lhs.ty));
+        let dest = loc.dest(size.try_into()?);
+
+        if !signed || shift == 0 {
+            self.expr(lhs, Some(loc))?;
+            self.text.push_str(&format!("\tshr {dest}, {shift}\n"));
+
+            return Ok(());
+        }
+
+        let r_lhs = self.expr_to_reg(lhs, size.try_into()?)?;
+        let r_bias = self.allocator.alloc(size.try_into()?)?;
+        let bits = size * 8;
+
+        self.mov(&r_lhs.into(), &r_bias.into(), true)?;
+        self.text.push_str(&formatdoc!(
+            "
+            \tsar {r_bias}, {}
+            \tshr {r_bias}, {}
+            \tadd {r_bias}, {r_lhs}
+            \tsar {r_bias}, {shift}
+            ",
+            bits - 1,
+            bits - shift as usize,
+        ));
+        self.mov(&r_bias.into(), &dest, true)?;
+
+        self.allocator.free(r_bias)?;
+        self.allocator.free(r_lhs)?;
+
+        Ok(())
+    }
+
+    /// Jumps to the overflow trap on `condition`, which has to be tested right
+    /// after the arithmetic instruction, before anything clobbers the flags.
+    fn check_overflow(&mut self, condition: Jump) {
+        if self.overflow == Overflow::Trap {
+            self.trap_used = true;
+            self.jcc("__meraki_overflow_trap", condition);
+        }
+    }
+
+    /// `set*` writes only the lowest byte, so a register destination is
+    /// zero-extended afterward to hold exactly 0 or 1 across its full width.
+    fn setcc(&mut self, dest: &Destination, condition: CmpOp, signed: bool) {
+        self.text
+            .push_str(&format!("\t{} {dest}\n", condition.set_instr(signed)));
+
+        if let Destination::Register(r) = dest {
+            self.text.push_str(&format!(
+                "\tmovzx {}, {}\n",
+                r.resize(OperandSize::Dword),
+                r.resize(OperandSize::Byte)
+            ));
+        }
     }
 
     fn cmp(&mut self, dest: &Destination, src: &Source) {
@@ -986,7 +3379,7 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
 
     fn logical_or(
         &mut self,
-        expr: &Expr,
+        expr: &Expr<'ir>,
         loc: &Location,
         labels: Option<(String, String)>,
     ) -> Result<(), Amd64AsmError> {
@@ -1023,7 +3416,7 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
 
     fn logical_and(
         &mut self,
-        expr: &Expr,
+        expr: &Expr<'ir>,
         loc: &Location,
         labels: Option<(String, String)>,
     ) -> Result<(), Amd64AsmError> {
@@ -1102,6 +3495,104 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         Ok(())
     }
 
+    /// Offsets the pointer `base` by `offset` elements of type `pointee`,
+    /// toward lower addresses if `backward`.
+    fn ptr_add(
+        &mut self,
+        base: &Expr<'ir>,
+        offset: &Expr<'ir>,
+        pointee: &Ty,
+        dest: &Destination,
+        backward: bool,
+    ) -> Result<(), Amd64AsmError> {
+        let (r_base, len) = match self.ctx.resolve_ty(base.ty) {
+            // Arrays decay into a pointer to their first element
+            Ty::Array(array) => {
+                let addr = self.expr_addr(base)?;
+                let r = self.allocator.alloc(OperandSize::Qword)?;
+
+                self.lea(&r.into(), &addr);
+                self.free_addr(&addr)?;
+
+                (r, Some(array.len))
+            }
+            _ => (self.expr_to_reg(base, OperandSize::Qword)?, None),
+        };
+        let r_offset = self.expr_to_reg(offset, OperandSize::Qword)?;
+
+        if backward {
+            self.negate(&r_offset.into());
+        }
+
+        // `arr[i]` is `*(arr + i)`, so pointer arithmetic on an array is
+        // checked too. A negative index compares as a huge unsigned one.
+        if let Some(len) = len.filter(|_| self.bounds_check) {
+            self.bounds_trap_used = true;
+            self.cmp(
+                &r_offset.into(),
+                &Source::Immediate(Immediate::UInt(len as u64)),
+            );
+            self.jcc("__meraki_bounds_trap", Jump::AboveEqual);
+        }
+        let elem_size = self.ty_size(pointee);
+        let scale = match elem_size {
+            1 | 2 | 4 | 8 => elem_size,
+            _ => {
+                self.text
+                    .push_str(&format!("\timul {r_offset}, {r_offset}, {elem_size}\n"));
+
+                1
+            }
+        };
+
+        self.lea(
+            &r_base.into(),
+            &EffectiveAddress {
+                base: Base::Register(r_base),
+                index: Some(r_offset),
+                scale: Some(scale),
+                displacement: None,
+            },
+        );
+        self.mov(&r_base.into(), dest, false)?;
+
+        self.allocator.free(r_base)?;
+        self.allocator.free(r_offset)?;
+
+        Ok(())
+    }
+
+    /// Number of elements of type `pointee` between the pointers in `lhs` and
+    /// `rhs`. The byte difference is always a multiple of the element size, so
+    /// the division is exact and an arithmetic shift rounds correctly.
+    fn ptr_diff(
+        &mut self,
+        lhs: Register,
+        rhs: Register,
+        pointee: &Ty,
+        dest: &Destination,
+    ) -> Result<(), Amd64AsmError> {
+        self.text.push_str(&format!("\tsub {lhs}, {rhs}\n"));
+
+        match self.ty_size(pointee) {
+            0 | 1 => self.mov(&lhs.into(), dest, true)?,
+            size if size.is_power_of_two() => {
+                self.text
+                    .push_str(&format!("\tsar {lhs}, {}\n", size.trailing_zeros()));
+                self.mov(&lhs.into(), dest, true)?;
+            }
+            size => self.div(
+                &lhs.into(),
+                &Source::Immediate(Immediate::UInt(size as u64)),
+                dest,
+                true,
+                Register::Rax,
+            )?,
+        }
+
+        Ok(())
+    }
+
     fn lea(&mut self, dest: &Destination, address: &EffectiveAddress) {
         self.text.push_str(&format!("\tlea {dest}, {address}\n"));
     }
@@ -1114,21 +3605,2070 @@ impl<'a, 'ir> Amd64Asm<'a, 'ir> {
         self.text.push_str(&format!("\tnot {dest}\n"));
     }
 
+    fn zero(&mut self, dest: &EffectiveAddress, size: usize) -> Result<(), Amd64AsmError> {
+        let mut offset = 0;
+
+        if size > Self::REP_STOS_THRESHOLD {
+            offset = size / 8 * 8;
+            self.rep_stosq(dest, size / 8);
+        }
+
+        for chunk_size in [8, 4, 2, 1] {
+            while size - offset >= chunk_size {
+                self.mov(
+                    &Source::Immediate(Immediate::UInt(0)),
+                    &(dest.clone() + Offset(offset as isize)).dest(chunk_size.try_into()?),
+                    false,
+                )?;
+                offset += chunk_size;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zeroes `count` words at `dest` in a single `rep stosq`. The address is
+    /// taken into `rax` before `rcx` and `rdi` are saved, so it may be based on
+    /// either of them.
+    fn rep_stosq(&mut self, dest: &EffectiveAddress, count: usize) {
+        let saved: Vec<Register> = [Register::Rcx, Register::Rdi]
+            .into_iter()
+            .filter(|r| self.allocator.is_used(r))
+            .collect();
+
+        self.lea(&Register::Rax.into(), dest);
+        for r in &saved {
+            self.push(&(*r).into());
+        }
+
+        self.text.push_str(&formatdoc!(
+            "
+            \tmov rdi, rax
+            \txor eax, eax
+            \tmov rcx, {count}
+            \trep stosq
+            ",
+        ));
+
+        for r in saved.iter().rev() {
+            self.pop(&(*r).into());
+        }
+    }
+
+    /// Copies `size` bytes with the widest moves that fit in what's left, like
+    /// [`Self::zero`]. Nothing past `size` bytes is read or written, and unlike
+    /// `rep movsb` it doesn't clobber `rcx`, `rsi` and `rdi`, which may hold
+    /// allocated values. Every chunk goes through the same temporary register,
+    /// the registers `src` and `dest` are based on stay allocated meanwhile.
     fn inline_memcpy(
         &mut self,
         src: &EffectiveAddress,
         dest: &EffectiveAddress,
         size: usize,
     ) -> Result<(), Amd64AsmError> {
-        self.mov(
-            &Source::Immediate(Immediate::UInt(size as u64)),
-            &Register::Rcx.into(),
-            false,
-        )?;
-        self.lea(&Register::Rsi.into(), src);
-        self.lea(&Register::Rdi.into(), dest);
-        self.text.push_str("\trep movsb\n");
+        let r_tmp = self.allocator.alloc(OperandSize::Qword)?;
+        let mut offset = 0;
+
+        for chunk_size in [8, 4, 2, 1] {
+            while size - offset >= chunk_size {
+                let chunk = chunk_size.try_into()?;
+                let r_chunk = r_tmp.resize(chunk);
+
+                self.mov(
+                    &(src.clone() + Offset(offset as isize)).src(chunk),
+                    &r_chunk.into(),
+                    false,
+                )?;
+                self.mov(
+                    &r_chunk.into(),
+                    &(dest.clone() + Offset(offset as isize)).dest(chunk),
+                    false,
+                )?;
+                offset += chunk_size;
+            }
+        }
+
+        self.allocator.free(r_tmp)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{register::Register, Amd64Asm, OperandSize};
+    use crate::{
+        codegen::{Codegen, Syntax},
+        diagnostics::Diagnostics,
+        lexer::Lexer,
+        lowering::Lowering,
+        parser,
+        ty_problem::TypeError,
+        Context,
+    };
+    use bumpalo::Bump;
+    use indoc::indoc;
+
+    fn compile(source: &str) -> String {
+        compile_with(source, false, 0)
+    }
+
+    fn compile_with(source: &str, pic: bool, opt_level: u8) -> String {
+        compile_configured(source, |codegen| codegen.pic(pic).opt_level(opt_level))
+    }
+
+    fn compile_configured(
+        source: &str,
+        configure: impl for<'a, 'ir> FnOnce(Amd64Asm<'a, 'ir>) -> Amd64Asm<'a, 'ir>,
+    ) -> String {
+        // Type errors fail the test with their message, like the `should_panic` ones expect
+        type_checked(source, |ctx| {
+            String::from_utf8(configure(Amd64Asm::new(ctx)).compile().unwrap()).unwrap()
+        })
+        .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Error the type checker finds in `source`, which has to have one
+    fn type_error(source: &str) -> TypeError {
+        type_checked(source, |_| ()).unwrap_err()
+    }
+
+    /// Lowers and type checks `source`, then passes the result to `f`
+    fn type_checked<T>(
+        source: &str,
+        f: impl for<'ir> FnOnce(&Context<'ir>) -> T,
+    ) -> Result<T, TypeError> {
+        let mut diagnostics = Diagnostics::new(source);
+        let ast = parser::Parser::new(Lexer::new(source), &mut diagnostics)
+            .parse()
+            .unwrap();
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+
+        let allocator = Bump::new();
+        let mut ctx = Context::new(&allocator);
+
+        Lowering::new(&mut ctx)
+            .lower(ast)
+            .and_then(|_| ctx.ty_problem.solve(&ctx.ir))?;
+
+        Ok(f(&ctx))
+    }
+
+    #[test]
+    fn register_from_size() {
+        let tests = [
+            (1, Register::R15b),
+            (2, Register::R15w),
+            (4, Register::R15d),
+            (8, Register::R15),
+        ];
+
+        for (size, expected) in tests {
+            let size = OperandSize::try_from(size).unwrap();
+
+            assert_eq!(Register::R15.resize(size), expected);
+            assert_eq!(expected.size(), size);
+        }
+
+        // A 3 byte value doesn't fit any register, it's an error instead of `r15`
+        assert_eq!(
+            OperandSize::try_from(3).unwrap_err().to_string(),
+            "3 is not a valid operand size"
+        );
+    }
+
+    #[test]
+    fn large_stack_frame() {
+        let asm = compile(
+            "
+            struct Frame {
+                data: [8192]u8;
+            }
+
+            fn main() -> u8 {
+                let first: u8 = 3;
+                let big: Frame = Frame {};
+
+                big.data[8191] = 2;
+
+                return big.data[8191] + first;
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tsub rsp, 8208\n"), "{asm}");
+        assert!(asm.contains("\tmov byte ptr [rbp - 33], 3\n"), "{asm}");
+        assert!(asm.contains("\tlea rax, [rbp - 8225]\n"), "{asm}");
+    }
+
+    #[test]
+    fn largest_stack_frame() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let big: [2147483616]u8;
+
+                return 0;
+            }
+            ",
+        );
+
+        // The first byte of `big` is 2 GiB below rbp, the lowest displacement
+        assert!(asm.contains("\tsub rsp, 2147483616\n"), "{asm}");
+    }
+
+    #[test]
+    fn stack_frame_beyond_displacement() {
+        let asm = compile_configured(
+            "
+            fn main() -> u8 {
+                let first: u8 = 3;
+                let big: [3000000000]u8;
+                let last: u8 = 4;
+
+                big[0] = 1;
+
+                return big[0] + first + last;
+            }
+            ",
+            |codegen| codegen.stack_protector(true),
+        );
+
+        // The frame is too large for an immediate, and so are the offsets of
+        // the locals below the first 2 GiB, which are loaded into an index
+        assert!(
+            asm.contains("\tmov r11, 3000000016\n\tsub rsp, r11\n"),
+            "{asm}"
+        );
+        assert!(asm.contains("\tmov byte ptr [rbp - 41], 3\n"), "{asm}");
+        assert!(
+            asm.contains("\tmov r15, -3000000042\n\tmov byte ptr [rbp + r15], 4\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains("\tmov r14, -3000000041\n\tlea r13, [rbp + r14]\n"),
+            "{asm}"
+        );
+        // The canary stays right below the saved registers
+        assert!(asm.contains("\tmov qword ptr [rbp - 40], r11\n"), "{asm}");
+    }
+
+    #[test]
+    fn sibling_blocks_share_stack_slots() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let flag: bool = true;
+
+                if flag == true {
+                    let lhs: [64]u8;
+                    let value: u8 = 1;
+                } else {
+                    let rhs: [64]u8;
+                    let value: u8 = 2;
+                }
+
+                return 0;
+            }
+            ",
+        );
+
+        // 1 byte for `flag` and 65 for the locals of either branch, aligned to 16 bytes
+        assert!(asm.contains("\tsub rsp, 80\n"), "{asm}");
+    }
+
+    #[test]
+    fn stack_params() {
+        let asm = compile(
+            "
+            fn sum(a: u64, b: u64, c: u64, d: u64, e: u64, f: u64, g: u64, h: u64) -> u64 {
+                let x: u64 = g;
+                let y: u64 = h;
+
+                h = x;
+
+                return a + b + c + d + e + f + y + h;
+            }
+
+            fn main() -> u8 {
+                return sum(1, 2, 3, 4, 5, 6, 7, 8) as u8;
+            }
+            ",
+        );
+
+        // 48 bytes for the parameters passed in registers and 16 for the
+        // locals, the ones passed on the stack stay in the caller's frame
+        assert!(asm.contains("\tsub rsp, 64\n"), "{asm}");
+        assert!(asm.contains("\tmov qword ptr [rbp - 80], r9\n"), "{asm}");
+        assert!(
+            asm.contains("\tmov r15, qword ptr [rbp + 16]\n\tmov qword ptr [rbp - 88], r15\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains("\tmov r15, qword ptr [rbp + 24]\n\tmov qword ptr [rbp - 96], r15\n"),
+            "{asm}"
+        );
+        assert!(asm.contains("\tmov qword ptr [rbp + 24], r15\n"), "{asm}");
+    }
+
+    #[test]
+    fn position_independent_code() {
+        let source = "
+            let total: u8 = 1;
+
+            fn get() -> u8 {
+                return total;
+            }
+
+            fn main() -> u8 {
+                return get();
+            }
+            ";
+
+        let asm = compile_with(source, true, 0);
+        assert!(asm.contains("qword ptr [rip + total@GOTPCREL]\n"), "{asm}");
+        assert!(asm.contains("\tcall get@PLT\n"), "{asm}");
+
+        // Globals are addressed relative to `rip` without PIC too
+        let asm = compile_with(source, false, 0);
+        assert!(asm.starts_with(".intel_syntax noprefix\n"), "{asm}");
+        assert!(asm.contains(" ptr [rip + total]\n"), "{asm}");
+        assert!(asm.contains("\tcall get\n"), "{asm}");
+        assert!(!asm.contains("GOTPCREL"), "{asm}");
+    }
+
+    #[test]
+    fn inline_asm() {
+        let asm = compile(
+            "
+            fn exit() -> void {
+                asm(\"
+                    mov rax, 60
+                    mov rdi, 0
+                    syscall
+                \");
+            }
+            ",
+        );
+
+        assert!(
+            asm.contains("\tmov rax, 60\n\tmov rdi, 0\n\tsyscall\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn extern_function() {
+        let asm = compile(
+            "
+            extern fn exit(status: i32) -> void;
+
+            fn main() -> u8 {
+                exit(1);
+
+                return 0;
+            }
+            ",
+        );
+
+        assert!(asm.contains(".extern exit\n"), "{asm}");
+        assert!(!asm.contains("exit:"), "{asm}");
+        assert!(asm.contains("\tcall exit\n"), "{asm}");
+    }
+
+    #[test]
+    fn variadic_call() {
+        let asm = compile(
+            "
+            extern fn printf(fmt: *u8, ...) -> i32;
+
+            fn main() -> i32 {
+                return printf(\"%d\", 42);
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tmov eax, 0\n\tcall printf\n"), "{asm}");
+    }
+
+    #[test]
+    fn comparison_zero_extends_register() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let a: i32 = 1;
+                let b: i32 = 2;
+
+                return (a < b) as u8;
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tsetl r15b\n\tmovzx r15d, r15b\n"), "{asm}");
+    }
+
+    #[test]
+    fn comparison_signedness() {
+        let tests = [
+            ("let a: i8 = 1; let b: i8 = 2;", "setl"),
+            ("let a: u64 = 1; let b: u64 = 2;", "setb"),
+            // The unsigned type wins at equal or greater width
+            ("let a: i8 = 1; let b: u16 = 2;", "setb"),
+            ("let a: isize = 1; let b: u64 = 2;", "setb"),
+            // The signed type is wider and can hold every value of the unsigned one
+            ("let a: i32 = 1; let b: u16 = 2;", "setl"),
+            ("let a: *u8 = NULL; let b: *u8 = NULL;", "setb"),
+        ];
+
+        for (locals, instr) in tests {
+            let asm = compile(&format!(
+                "fn main() -> u8 {{ {locals} return (a < b) as u8; }}"
+            ));
+
+            assert!(
+                asm.contains(&format!("\t{instr} r15b\n")),
+                "{locals}\n{asm}"
+            );
+        }
+    }
+
+    #[test]
+    fn literal_comparison() {
+        compile("fn main() -> u8 { let b: bool = 1 == 1; if 1 < 2 { return 1; } return 0; }");
+    }
+
+    #[test]
+    fn comparison_in_wider_arithmetic() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let a: i32 = 1;
+                let b: i32 = 2;
+                let c: i64 = 3;
+                let d: i64 = 4;
+
+                if (a < b) as u64 + (c < d) as u64 == 2 {
+                    return 1;
+                }
+
+                return 0;
+            }
+            ",
+        );
+
+        // Both bytes are extended to a qword before they're added
+        assert!(asm.contains("\tmovzx r13, r13b\n"), "{asm}");
+        assert!(asm.contains("\tmovzx r12, r12b\n\tadd r13, r12\n"), "{asm}");
+    }
+
+    #[test]
+    fn operand_living_across_call() {
+        let asm = compile(
+            "
+            fn one() -> u64 {
+                return 1;
+            }
+
+            fn main() -> u64 {
+                let a: u64 = 2;
+                let b: u64 = 3;
+
+                return a * b + one();
+            }
+            ",
+        );
+        let body = &asm[asm.find("main:").unwrap()..];
+        let body = &body[body.find("\tsub rsp").unwrap()..];
+
+        // The product waits for the call in a callee saved register, so nothing is pushed around it
+        assert!(body.contains("\tmov r14, rax\n\tcall one\n"), "{asm}");
+        assert!(!body.contains("\tpush "), "{asm}");
+        // The operands of the product are short lived and get caller saved registers
+        assert!(body.contains("\tmov r11, qword ptr [rbp - 40]\n"), "{asm}");
+    }
+
+    #[test]
+    fn struct_copy_chunks() {
+        let tests: [(usize, &[(&str, usize)]); 5] = [
+            (3, &[("word", 0), ("byte", 2)]),
+            (5, &[("dword", 0), ("byte", 4)]),
+            (6, &[("dword", 0), ("word", 4)]),
+            (7, &[("dword", 0), ("word", 4), ("byte", 6)]),
+            (9, &[("qword", 0), ("byte", 8)]),
+        ];
+
+        for (size, chunks) in tests {
+            let fields = (0..size).map(|i| format!("f{i}: u8;")).collect::<String>();
+            let asm = compile(&format!(
+                "
+                struct Bytes {{ {fields} }}
+
+                fn main() -> u8 {{
+                    let src: Bytes = Bytes {{ f0: 1 }};
+                    let dest: Bytes = src;
+
+                    return 0;
+                }}
+                "
+            ));
+            // `src` lies right below the saved registers and `dest` right below it
+            let dest = 32 + 2 * size;
+            let stores = asm
+                .lines()
+                .filter_map(|line| {
+                    let (ptr, addr) = line.strip_prefix("\tmov ")?.split_once(" ptr [rbp - ")?;
+                    let (addr, _) = addr.split_once("], ")?;
+
+                    Some((ptr, dest.checked_sub(addr.parse().ok()?)?))
+                })
+                .filter(|(_, offset)| *offset < size)
+                .collect::<Vec<_>>();
+
+            assert_eq!(stores, chunks, "{size} bytes: {asm}");
+
+            for (ptr, offset) in chunks {
+                assert!(
+                    asm.contains(&format!(", {ptr} ptr [rbp - {}]\n", dest - size - offset)),
+                    "{size} bytes: {asm}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn self_referential_struct() {
+        let asm = compile(
+            "
+            struct Node {
+                value: i32;
+                next: *Node;
+            }
+
+            fn main() -> u8 {
+                let head: Node = Node { value: 1 };
+                let tail: *Node = head.next;
+
+                return 0;
+            }
+            ",
+        );
+
+        // `next` is pointer sized, so `Node` takes 16 bytes and `tail` 8 more
+        assert!(asm.contains("\tsub rsp, 32\n"), "{asm}");
+    }
+
+    #[test]
+    fn struct_fields_are_aligned() {
+        let asm = compile(
+            "
+            struct Packed {
+                a: u8;
+                b: [3]u8;
+                c: u32;
+            }
+
+            fn main() -> u8 {
+                let packed: [4]Packed;
+
+                return 0;
+            }
+            ",
+        );
+
+        // `c` only needs to be 4 byte aligned, so `Packed` takes 8 bytes rather than 12
+        assert!(asm.contains("\tsub rsp, 32\n"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Tuples of 2 and 3 elements can't be unified")]
+    fn destructure_arity_mismatch() {
+        compile(
+            "
+            fn main() -> u8 {
+                let (a, b) = (1, 2, 3);
+
+                return a;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify i64 and u8")]
+    fn tuple_element_mismatch() {
+        compile(
+            "
+            fn f() -> (i32, u8) {
+                return (1, 2 as i64);
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn indexing_is_pointer_arithmetic() {
+        let source = |store: &str| {
+            format!(
+                "
+                fn main() -> u8 {{
+                    let arr: [4]u16 = [1, 2, 3, 4];
+                    let p: *u16 = arr as *u16;
+
+                    {store} = 5;
+
+                    return 0;
+                }}
+                "
+            )
+        };
+        let asm = compile(&source("p[2]"));
+
+        assert_eq!(asm, compile(&source("*(p + 2)")));
+        assert!(asm.contains("* 2]\n"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "recursive type `Node` has infinite size")]
+    fn struct_containing_itself() {
+        compile(
+            "
+            struct Node {
+                value: i32;
+                next: Node;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "recursive type `Outer` has infinite size")]
+    fn structs_containing_each_other() {
+        compile(
+            "
+            struct Outer {
+                inner: [2]Inner;
+            }
+
+            struct Inner {
+                outer: Outer;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn items_used_before_declared() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let p: Point = Point { x: 1, y: 2 };
+
+                return (sum(&p) + scale) as u8;
+            }
+
+            fn sum(p: *Point) -> i32 {
+                return p->x + p->y;
+            }
+
+            struct Point {
+                x: i32;
+                y: i32;
+            }
+
+            let scale: i32 = 3;
+            ",
+        );
+
+        assert!(asm.contains("call sum\n"), "{asm}");
+    }
+
+    #[test]
+    fn overload_declared_after_call() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                return size(1 as u16) + size(true);
+            }
+
+            fn size(x: u16) -> u8 {
+                return 2;
+            }
+
+            fn size(x: bool) -> u8 {
+                return 1;
+            }
+            ",
+        );
+
+        assert!(asm.contains("call size.u16\n"), "{asm}");
+        assert!(asm.contains("call size.bool\n"), "{asm}");
+    }
+
+    #[test]
+    fn overload_labels_apart_from_other_functions() {
+        let asm = compile(
+            "
+            fn abs(x: i32) -> i32 {
+                return x;
+            }
+
+            fn abs(x: i64) -> i64 {
+                return x;
+            }
+
+            fn abs_i32(x: i32) -> i32 {
+                return abs(x);
+            }
+            ",
+        );
+
+        assert!(asm.contains("\nabs.i32:\n"), "{asm}");
+        assert!(asm.contains("\nabs_i32:\n"), "{asm}");
+    }
+
+    #[test]
+    fn not_operators() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let a: u8 = ~0x0f;
+                let b: bool = !(a == 0xf0);
+
+                return a;
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tnot "), "{asm}");
+        assert!(asm.contains("\tsete "), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify u8 and bool")]
+    fn logical_not_of_integer() {
+        compile(
+            "
+            fn main() -> bool {
+                let a: u8 = 1;
+
+                return !a;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify i32 and bool")]
+    fn arithmetic_with_non_integer() {
+        compile(
+            "
+            fn main() -> i32 {
+                let a: i32 = 1;
+                let b: bool = true;
+
+                return a - b;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn division_by_constant_zero() {
+        let source = "
+            fn main() -> u8 {
+                let x: u8 = 1;
+
+                return x / (2 - 2);
+            }
+            ";
+        let err = type_error(source);
+        let span = err.span.unwrap();
+
+        assert_eq!(err.message, "Division by zero");
+        assert_eq!(&source[span.start..span.end], "x / (2 - 2)");
+    }
+
+    #[test]
+    fn division_extends_dividend() {
+        let tests = [
+            ("i8", "\tcbw\n\tidiv "),
+            ("i16", "\tcwd\n\tidiv "),
+            ("i32", "\tcdq\n\tidiv "),
+            ("i64", "\tcqo\n\tidiv "),
+            ("u8", "\tmovzx ax, al\n\tdiv "),
+            ("u32", "\txor edx, edx\n\tdiv "),
+        ];
+
+        for (ty, expected) in tests {
+            let asm = compile(&format!(
+                "
+                fn main() -> u8 {{
+                    let x: {ty} = 100;
+                    let y: {ty} = 7;
+
+                    return (x / y) as u8;
+                }}
+                "
+            ));
+
+            assert!(asm.contains(expected), "{asm}");
+        }
+    }
+
+    #[test]
+    fn remainder_by_constant_zero() {
+        let source = "
+            fn main() -> u8 {
+                let x: u8 = 1;
+
+                return x % 0;
+            }
+            ";
+        let err = type_error(source);
+        let span = err.span.unwrap();
+
+        assert_eq!(err.message, "Remainder of a division by zero");
+        assert_eq!(&source[span.start..span.end], "x % 0");
+    }
+
+    #[test]
+    fn function_pointer() {
+        let asm = compile(
+            "
+            fn one() -> u8 {
+                return 1;
+            }
+
+            fn main() -> u8 {
+                let f: *fn() -> u8 = &one;
+
+                return f();
+            }
+            ",
+        );
+
+        assert!(asm.contains(", [rip + one]\n"), "{asm}");
+        assert!(asm.contains("\tcall rax\n"), "{asm}");
+    }
+
+    #[test]
+    fn address_of_lvalues() {
+        compile(
+            "
+            struct Point {
+                x: u8;
+                y: u8;
+            }
+
+            fn main() -> u8 {
+                let x: u8 = 1;
+                let arr: [4]u8 = [1, 2, 3, 4];
+                let point: Point = Point { x: 5, y: 6 };
+                let i: usize = 2;
+                let p: *u8 = &x;
+                let q: *u8 = &arr[i];
+                let r: *u8 = &*p;
+                let s: *u8 = &point.y;
+
+                return *p + *q + *r + *s;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't take the address of a temporary value")]
+    fn address_of_literal() {
+        compile(
+            "
+            fn main() -> u8 {
+                let p: *u8 = &5;
+
+                return *p;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't take the address of a temporary value")]
+    fn address_of_cast() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u16 = 1;
+                let p: *u8 = &(x as u8);
+
+                return *p;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't take the address of a temporary value")]
+    fn address_of_temporary() {
+        compile(
+            "
+            fn main() -> u8 {
+                let p: *u8 = &(1 + 2);
+
+                return *p;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn continue_runs_increment() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                for let i: u8 = 0; i < 10; i = i + 1 {
+                    continue;
+                }
+
+                return 0;
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tjmp .L0_2\n.L0_2:\n"), "{asm}");
+    }
+
+    #[test]
+    fn break_outside_of_loop() {
+        let source = "
+            fn main() -> u8 {
+                if true == true {
+                    break;
+                }
+
+                return 0;
+            }
+            ";
+        let err = type_error(source);
+        let span = err.span.unwrap();
+
+        assert_eq!(err.message, "`break` outside of a loop");
+        assert_eq!(&source[span.start..span.end], "break;");
+    }
+
+    #[test]
+    fn continue_in_block_expr() {
+        let source = "
+            fn main() -> u8 {
+                while true {
+                    let x: u8 = { continue; 1 };
+                }
+
+                return 0;
+            }
+            ";
+        let err = type_error(source);
+        let span = err.span.unwrap();
+
+        assert_eq!(err.message, "`continue` outside of a loop");
+        assert_eq!(&source[span.start..span.end], "continue;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't index a value of type i32")]
+    fn index_scalar() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: i32 = 5;
+
+                return x[0] as u8;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't index with a value of type bool")]
+    fn index_with_bool() {
+        compile(
+            "
+            fn main() -> u8 {
+                let arr: [2]u8 = [1, 2];
+
+                return arr[true];
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify u16 and u8")]
+    fn index_elem_ty() {
+        compile(
+            "
+            fn main() -> u8 {
+                let arr: [2]u16 = [1, 2];
+
+                return arr[1];
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn narrowing_literal() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let x: u32 = 300;
+                let y: u8 = 200;
+                let z: u8 = x as u8;
+
+                return y + z;
+            }
+            ",
+        );
+
+        // The literal takes the type of the variable, only `x` is narrowed
+        assert!(asm.contains("\tmov dword ptr [rbp - 36], 300\n"), "{asm}");
+        assert!(asm.contains("\tmov byte ptr [rbp - 37], 200\n"), "{asm}");
+        assert!(
+            asm.contains("\tmov r15d, dword ptr [rbp - 36]\n\tmov byte ptr [rbp - 38], r15b\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn int_lit_out_of_range() {
+        let tests = [
+            (
+                "let x: i8 = -129;",
+                "Literal `-129` is out of range for i8, it needs i16",
+            ),
+            (
+                "let x: u8 = 300;",
+                "Literal `300` is out of range for u8, it needs u16",
+            ),
+            (
+                "let x: u32 = -1;",
+                "Literal `-1` is out of range for u32, it needs i8",
+            ),
+            (
+                "let x: i64 = -9223372036854775809;",
+                "Literal `-9223372036854775809` doesn't fit in any integer type",
+            ),
+        ];
+
+        for (stmt, message) in tests {
+            let source = format!("fn main() -> u8 {{ {stmt} return 0; }}");
+            let err = type_error(&source);
+
+            assert_eq!(err.message, message, "{stmt}");
+            // The span covers the literal along with its sign
+            let span = err.span.unwrap();
+            let lit = stmt.split_once("= ").unwrap().1.trim_end_matches(';');
+            assert_eq!(&source[span.start..span.end], lit);
+        }
+
+        compile(
+            "
+            fn main() -> u8 {
+                let x: i8 = -128;
+                let y: u8 = 255;
+                let z: i64 = -9223372036854775808;
+
+                return y + x as u8 + z as u8;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify u8 and u32")]
+    fn narrowing_variable() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u32 = 300;
+                let y: u8 = x;
+
+                return y;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify")]
+    fn fn_ty_mismatch() {
+        compile(
+            "
+            fn double(x: u8) -> u8 {
+                return x * 2;
+            }
+
+            fn main() -> u8 {
+                let cb: fn(u16) -> u8 = double;
+
+                return cb(1);
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn assert_disabled() {
+        let source = "
+            fn main() -> u8 {
+                let x: u8 = 1;
+
+                assert!(x == 2);
+
+                return x;
+            }
+            ";
+
+        assert!(compile(source).contains("\tjmp __meraki_assert_failed\n"));
+        assert!(
+            !compile_configured(source, |codegen| codegen.assertions(false))
+                .contains("__meraki_assert_failed")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to unify u8 and bool")]
+    fn assert_non_bool() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u8 = 1;
+
+                assert!(x);
+
+                return x;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an integer, got bool")]
+    fn bitwise_not_of_bool() {
+        compile(
+            "
+            fn main() -> bool {
+                return ~true;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn self_tail_call() {
+        let source = "
+            fn count(n: u64, acc: u64) -> u64 {
+                if n == 0 {
+                    return acc;
+                }
+
+                return count(n - 1, acc + 1);
+            }
+            ";
+        let asm = compile_with(source, false, 1);
+
+        assert!(!asm.contains("\tcall count\n"), "{asm}");
+        assert!(compile(source).contains("\tcall count\n"));
+    }
+
+    #[test]
+    fn non_executable_stack() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                return 0;
+            }
+            ",
+        );
+        let note = ".section .note.GNU-stack,\"\",@progbits\n";
+
+        assert!(asm.ends_with(note), "{asm}");
+        assert_eq!(asm.matches(note).count(), 1, "{asm}");
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let asm = compile(
+            "
+            struct Unit {}
+
+            let nothing: Unit;
+
+            fn take(unit: Unit, value: u8) -> u8 {
+                return value;
+            }
+
+            fn main() -> u8 {
+                let unit: Unit = Unit {};
+                let copy: Unit = unit;
+
+                nothing = copy;
+
+                return take(nothing, 7);
+            }
+            ",
+        );
+        let main = &asm[asm.find("main:\n").unwrap()..asm.find(".size main").unwrap()];
+
+        assert!(
+            asm.contains(".section .bss\nnothing:\n.section .text\n"),
+            "{asm}"
+        );
+        assert!(!main.contains(" ptr ["), "{asm}");
+        assert!(!main.contains("\tsub rsp"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "`x` shadows a parameter, it can only be redeclared in an inner block"
+    )]
+    fn local_shadows_param() {
+        compile(
+            "
+            fn main(x: u8) -> u8 {
+                let x: u8 = 1;
+
+                return x;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn param_shadowed_in_inner_block() {
+        compile(
+            "
+            fn main(x: u8) -> u8 {
+                if x > 0 {
+                    let x: u8 = 1;
+
+                    return x;
+                }
+
+                return x;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn zero_large_local() {
+        let asm = compile(
+            "
+            struct Buf {
+                data: [1024]u8;
+                len: u8;
+            }
+
+            fn main() -> u8 {
+                let buf: Buf = Buf { len: 1 };
+
+                return buf.len;
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tmov rcx, 128\n\trep stosq\n"), "{asm}");
+        // The byte left over after the words is zeroed by itself, then `len` is
+        // stored and loaded
+        assert_eq!(asm.matches("qword ptr [").count(), 0, "{asm}");
+        assert_eq!(asm.matches("byte ptr [").count(), 3, "{asm}");
+    }
+
+    #[test]
+    fn restrict_ptr_param() {
+        compile(
+            "
+            fn copy(dest: *restrict u8, src: *restrict u8) -> void {
+                *dest = *src;
+            }
+
+            fn main() -> u8 {
+                let a: u8 = 1;
+                let b: u8 = 2;
+                let p: *u8 = &b;
+
+                copy(&a, p);
+
+                return a;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`defer` can't be used in a block expression with a value")]
+    fn defer_in_block_expr_with_value() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u8 = {
+                    defer main();
+                    1
+                };
+
+                return x;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn memcpy_reuses_register() {
+        let asm = compile(
+            "
+            struct Odd {
+                bytes: [15]u8;
+            }
+
+            fn main() -> u8 {
+                let x: Odd = Odd {};
+                let y: Odd = x;
+
+                return y.bytes[14];
+            }
+            ",
+        );
+        let copy = indoc! {"
+            \tmov r15, qword ptr [rbp - 47]
+            \tmov qword ptr [rbp - 62], r15
+            \tmov r15d, dword ptr [rbp - 39]
+            \tmov dword ptr [rbp - 54], r15d
+            \tmov r15w, word ptr [rbp - 35]
+            \tmov word ptr [rbp - 50], r15w
+            \tmov r15b, byte ptr [rbp - 33]
+            \tmov byte ptr [rbp - 48], r15b
+        "};
+
+        // A load and a store per chunk, all through the same register
+        assert!(asm.contains(copy), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Type alias `A` refers to itself")]
+    fn type_alias_cycle() {
+        compile(
+            "
+            type A = *B;
+            type B = [2]A;
+
+            fn main() -> u8 {
+                return 0;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`Foo` is already declared in this scope")]
+    fn type_alias_named_like_struct() {
+        compile(
+            "
+            type Foo = u8;
+
+            struct Foo {
+                a: u8;
+            }
+
+            fn main() -> u8 {
+                return 0;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn packed_struct_layout() {
+        let asm = compile(
+            "
+            packed struct Packed {
+                a: i8;
+                b: i32;
+            }
+
+            struct Padded {
+                a: i8;
+                b: i32;
+            }
+
+            fn main() -> u8 {
+                let p: Packed = Packed { a: 1, b: 2 };
+                let q: Padded = Padded { a: 3, b: 4 };
+
+                return (p.a + q.a) as u8;
+            }
+            ",
+        );
+
+        // 5 bytes with `b` right after `a`, against 8 with `b` aligned
+        assert!(
+            asm.contains("\tmov byte ptr [rbp - 37], 1\n\tmov dword ptr [rbp - 36], 2\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains("\tmov byte ptr [rbp - 48], 3\n\tmov dword ptr [rbp - 44], 4\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn void_main_returns_zero() {
+        let asm = compile("fn main() -> void {}\nfn f() -> void {}");
+        let main = &asm[asm.find("main:\n").unwrap()..asm.find(".size main").unwrap()];
+        let f = &asm[asm.find("f:\n").unwrap()..asm.find(".size f").unwrap()];
+
+        assert!(main.contains(".L0_0:\n\txor eax, eax\n"), "{asm}");
+        assert!(!f.contains("xor eax, eax"), "{asm}");
+    }
+
+    #[test]
+    fn union_layout() {
+        let asm = compile(
+            "
+            union Bits {
+                word: u32;
+                byte: u8;
+            }
+
+            fn main() -> u8 {
+                let bits: Bits = Bits { word: 258 };
+
+                return bits.byte + sizeof(Bits) as u8;
+            }
+            ",
+        );
+
+        // Both fields are at the start of the union
+        assert!(asm.contains("dword ptr [rbp - 36], 258"), "{asm}");
+        assert!(asm.contains("byte ptr [rbp - 36]\n"), "{asm}");
+        assert!(asm.contains("mov r13, 4\n"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Union expression of `Bits` sets more than one field")]
+    fn union_expr_multiple_fields() {
+        compile(
+            "
+            union Bits {
+                word: u32;
+                byte: u8;
+            }
+
+            fn main() -> u8 {
+                let bits: Bits = Bits { word: 1, byte: 2 };
+
+                return bits.byte;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn symbol_directives() {
+        let asm = compile(
+            "
+            let counter: u32 = 1;
+
+            fn main() -> u8 {
+                static let calls: u16;
+
+                return 0;
+            }
+            ",
+        );
+
+        assert!(
+            asm.contains(".global main\n.type main, @function\nmain:\n"),
+            "{asm}"
+        );
+        assert!(asm.contains("\tret\n.size main, .-main\n"), "{asm}");
+        assert!(
+            asm.contains(".type counter, @object\n.size counter, 4\ncounter:\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains(".type main.calls, @object\n.size main.calls, 2\nmain.calls:\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn shadowed_static_locals() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                static let x: u8 = 1;
+
+                if x == 1 {
+                    static let x: u8 = 2;
+                } else {
+                    static let x: u8 = 3;
+                }
+
+                return x;
+            }
+            ",
+        );
+
+        assert!(asm.contains("main.x:\n"), "{asm}");
+        assert!(asm.contains("main.x.1:\n"), "{asm}");
+        assert!(asm.contains("main.x.2:\n"), "{asm}");
+    }
+
+    #[test]
+    fn code_alignment() {
+        let source = "
+            fn helper() -> u8 {
+                return 1;
+            }
+
+            pub fn api() -> u8 {
+                return 2;
+            }
+
+            fn main() -> u8 {
+                let i: u8 = 0;
+
+                while i < 10 {
+                    i = i + helper();
+                }
+
+                return i;
+            }
+            ";
+        let asm = compile(source);
+
+        assert!(
+            asm.contains(".p2align 4\n.type helper, @function\nhelper:\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains(".p2align 4\n.global api\n.type api, @function\napi:\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains(".p2align 4\n.global main\n.type main, @function\nmain:\n"),
+            "{asm}"
+        );
+        assert_eq!(asm.matches(".p2align").count(), 3, "{asm}");
+
+        let asm = compile_configured(source, |codegen| codegen.align_loops(true));
+
+        assert!(asm.contains(".p2align 4\n.L2_1:\n"), "{asm}");
+        assert_eq!(asm.matches(".p2align").count(), 4, "{asm}");
+    }
+
+    #[test]
+    fn thread_local_globals() {
+        let source = "
+            thread_local let counter: u64 = 5;
+            pub thread_local let scratch: u32;
+
+            fn main() -> u8 {
+                counter = counter + scratch as u64;
+
+                return 0;
+            }
+            ";
+        let asm = compile(source);
+
+        assert!(
+            asm.contains(
+                ".section .tbss,\"awT\",@nobits\n.global scratch\n.balign 4\n.type scratch, @tls_object\n"
+            ),
+            "{asm}"
+        );
+        assert!(
+            asm.contains(
+                ".section .tdata,\"awT\",@progbits\n.balign 8\n.type counter, @tls_object\n"
+            ),
+            "{asm}"
+        );
+        // Addressed from the thread pointer rather than `rip`
+        assert!(
+            asm.contains("\tmov r15, qword ptr fs:0\n\tlea r15, [r15 + counter@tpoff]\n"),
+            "{asm}"
+        );
+        assert!(!asm.contains("rip + counter"), "{asm}");
+
+        let asm = compile_with(source, true, 0);
+
+        assert!(
+            asm.contains(
+                "\tmov r15, qword ptr [rip + counter@gottpoff]\n\tadd r15, qword ptr fs:0\n"
+            ),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn bounds_check() {
+        let source = "
+            fn main() -> u8 {
+                let arr: [3]u8 = [1, 2, 3];
+                let p: *u8 = &arr[0];
+                let i: usize = 2;
+
+                return arr[i] + p[i];
+            }
+            ";
+        let asm = compile_configured(source, |codegen| codegen.bounds_check(true));
+
+        // Both indexes into the array are checked, the pointer's length is unknown
+        assert_eq!(
+            asm.matches(", 3\n\tjae __meraki_bounds_trap\n").count(),
+            2,
+            "{asm}"
+        );
+        assert!(asm.contains("__meraki_bounds_trap:\n"), "{asm}");
+
+        let asm = compile(source);
+
+        assert!(!asm.contains("__meraki_bounds_trap"), "{asm}");
+    }
+
+    #[test]
+    fn syntax() {
+        let source = "
+            fn main() -> u8 {
+                let x: u32 = 7;
+                asm(\"nop\");
+
+                return x as u8;
+            }
+            ";
+        let asm = compile(source);
+
+        assert!(asm.starts_with(".intel_syntax noprefix\n"), "{asm}");
+        assert!(asm.contains("\tmov rbp, rsp\n"), "{asm}");
+        assert!(asm.contains("\tmov dword ptr [rbp - 36], 7\n"), "{asm}");
+
+        let asm = compile_configured(source, |codegen| codegen.syntax(Syntax::Att));
+
+        assert!(!asm.starts_with(".intel_syntax"), "{asm}");
+        assert!(asm.contains("\tmovq %rsp, %rbp\n"), "{asm}");
+        assert!(asm.contains("\tmovl $7, -36(%rbp)\n"), "{asm}");
+        assert!(
+            asm.contains("\t.intel_syntax noprefix\n\tnop\n\t.att_syntax prefix\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn cfi_directives() {
+        let source = "
+            fn id(x: u8) -> u8 {
+                return x;
+            }
+
+            fn main() -> u8 {
+                return id(0);
+            }
+            ";
+        let asm = compile_configured(source, |codegen| codegen.debug(true));
+
+        assert_eq!(asm.matches(".cfi_startproc\n").count(), 2, "{asm}");
+        assert_eq!(asm.matches(".cfi_endproc\n").count(), 2, "{asm}");
+        assert!(
+            asm.contains("main:\n\t.cfi_startproc\n\tpush rbp\n\t.cfi_def_cfa_offset 16\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains("\tret\n\t.cfi_endproc\n.size main, .-main\n"),
+            "{asm}"
+        );
+        assert!(!compile(source).contains(".cfi"));
+    }
+
+    #[test]
+    fn visibility() {
+        let asm = compile(
+            "
+            pub let exported: u8 = 1;
+            let private: u8 = 2;
+
+            fn helper() -> u8 {
+                return exported + private;
+            }
+
+            pub fn api() -> u8 {
+                return helper();
+            }
+
+            fn main() -> u8 {
+                return api();
+            }
+            ",
+        );
+
+        assert!(asm.contains(".global api\n"), "{asm}");
+        assert!(asm.contains(".global main\n"), "{asm}");
+        assert!(asm.contains(".global exported\n"), "{asm}");
+        assert!(!asm.contains(".global helper\n"), "{asm}");
+        assert!(!asm.contains(".global private\n"), "{asm}");
+        assert!(asm.contains("\tcall helper\n"), "{asm}");
+    }
+
+    #[test]
+    fn listing() {
+        let source = "
+            let counter: u64 = 1;
+
+            fn main(argc: i32) -> u64 {
+                static let calls: u64;
+                let x: u64 = counter;
+
+                return x;
+            }
+            ";
+        let mut diagnostics = Diagnostics::new(source);
+        let ast = parser::Parser::new(Lexer::new(source), &mut diagnostics)
+            .parse()
+            .unwrap();
+        let allocator = Bump::new();
+        let mut ctx = Context::new(&allocator);
+
+        Lowering::new(&mut ctx).lower(ast).unwrap();
+        ctx.ty_problem.solve(&ctx.ir).unwrap();
+
+        let mut codegen = Amd64Asm::new(&ctx).listing(true);
+        let asm = String::from_utf8(codegen.compile().unwrap()).unwrap();
+
+        assert_eq!(
+            codegen.listing_text().unwrap(),
+            indoc! {"
+                fn main
+                  static let calls
+                  let x
+                \tmov r15, qword ptr [rip + counter]
+                \tmov qword ptr [rbp - 48], r15
+                  return
+                \tmov r15, qword ptr [rbp - 48]
+                \tmov rax, r15
+                \tjmp .L1_0
+                symbols
+                  global counter [rip + counter]
+                  param  main.argc [rbp - 40]
+                  static main.calls [rip + main.calls]
+                  local  main.x [rbp - 48]
+            "}
+        );
+        // The listing doesn't change the generated code
+        assert!(!asm.contains("symbols"), "{asm}");
+        assert!(Amd64Asm::new(&ctx).listing_text().is_none());
+    }
+
+    #[test]
+    fn annotate() {
+        let source = "
+            fn main() -> u8 {
+                let x: u8 = 2;
+
+                if x < 3 {
+                    return (x +
+                        1) as u8;
+                }
+
+                return 0;
+            }
+            ";
+        let asm = compile_configured(source, |codegen| codegen.annotate(Some(source)));
+
+        assert!(asm.contains("\tmov byte ptr [rbp - 33], 2 # 2\n"), "{asm}");
+        // Nested expressions are part of the outermost one, spread over lines it's joined
+        assert!(asm.contains("\tadd r14b, r13b # (x + 1) as u8\n"), "{asm}");
+        // Code which isn't part of an expression has no comment
+        assert!(asm.contains("\tje .L0_1\n"), "{asm}");
+        assert!(asm.contains("\n.L0_1:\n"), "{asm}");
+    }
+
+    #[test]
+    fn strength_reduction() {
+        let source = "
+            fn scale(a: u64, b: i32) -> u64 {
+                return a * 8 + 16 * a + a / 4 + (b / 8) as u64;
+            }
+            ";
+        let asm = compile_with(source, false, 1);
+
+        assert!(!asm.contains("mul "), "{asm}");
+        assert!(!asm.contains("div "), "{asm}");
+        assert!(asm.contains(", 3\n"), "{asm}");
+        assert!(asm.contains(", 4\n"), "{asm}");
+        assert!(asm.contains("\tshr r12, 2\n"), "{asm}");
+        // A negative dividend is biased by 7 before the shift to round toward zero
+        assert!(asm.contains("\tsar r11d, 31\n\tshr r11d, 29\n"), "{asm}");
+        assert!(asm.contains("\tsar r11d, 3\n"), "{asm}");
+
+        let asm = compile_with(source, false, 0);
+
+        assert!(asm.contains("\tmul "), "{asm}");
+        assert!(asm.contains("\tidiv "), "{asm}");
+    }
+
+    #[test]
+    fn string_escapes() {
+        let asm = compile(
+            r#"
+            fn main() -> u8 {
+                let s: *u8 = "\x41\u{1F600}\n\"\\";
+
+                return 0;
+            }
+            "#,
+        );
+
+        assert!(
+            asm.contains(r#".string "A\360\237\230\200\012\"\\""#),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn constant_propagation() {
+        let source = "
+            fn main() -> i32 {
+                let k: i32 = 4;
+                let x: i32 = 0;
+                let y: i32 = 0;
+                let p: *i32 = &y;
+
+                x = k * k;
+                y = k + 1;
+
+                if x == 16 {
+                    k = 5;
+                }
+
+                return x + y * k;
+            }
+            ";
+        let asm = compile_with(source, false, 1);
+
+        assert!(asm.contains("\tmov dword ptr [rbp - 40], 16\n"), "{asm}");
+        // `y` can be changed through `p`, and `k` differs between the branches
+        assert!(asm.contains("\tmov dword ptr [rbp - 44], 5\n"), "{asm}");
+        assert!(asm.contains("\timul "), "{asm}");
+
+        let asm = compile_with(source, false, 0);
+
+        assert!(!asm.contains("], 16\n"), "{asm}");
+    }
+
+    #[test]
+    fn logical_ops_as_statements() {
+        let asm = compile(
+            "
+            fn yes() -> bool {
+                return true;
+            }
+
+            fn no() -> bool {
+                return false;
+            }
+
+            fn main() -> u8 {
+                no() || yes();
+
+                return 0;
+            }
+            ",
+        );
+
+        // The value isn't used, but the right operand is still called when the left one is false
+        assert!(asm.contains("\tcall no\n"), "{asm}");
+        assert!(asm.contains("\tcall yes\n"), "{asm}");
+    }
+
+    #[test]
+    fn naked_function() {
+        let asm = compile(
+            "
+            naked fn answer() -> u64 {
+                asm(\"
+                    mov rax, 42
+                    ret
+                \");
+            }
+
+            fn main() -> u8 {
+                return answer() as u8;
+            }
+            ",
+        );
+
+        // The assembly is the whole function, there's no prologue or epilogue
+        assert!(
+            asm.contains("answer:\n\tmov rax, 42\n\tret\n.size answer, .-answer\n"),
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn noreturn_function() {
+        let asm = compile(
+            "
+            extern noreturn fn exit(status: i32) -> void;
+
+            noreturn fn fail() -> void {
+                exit(1);
+            }
+
+            fn main() -> u8 {
+                fail();
+            }
+            ",
+        );
+
+        assert!(asm.contains("\tcall exit\n\tud2\n"), "{asm}");
+        assert!(asm.contains("\tcall fail\n\tud2\n"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(expected = "`noreturn` function `fail` can't return")]
+    fn noreturn_function_returns() {
+        compile(
+            "
+            noreturn fn fail() -> void {
+                return;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Naked function `f` can only contain inline assembly")]
+    fn naked_function_with_stmts() {
+        compile(
+            "
+            naked fn f() -> u64 {
+                let a: u64 = 1;
+                asm(\"ret\");
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn itoa_routines_emitted_once() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                static let buf: [21]u8;
+                let len: usize = itoa!(-5, buf as *u8) + itoa!(5u8, buf as *u8);
+
+                print!(len, 7u16);
+
+                return 0;
+            }
+            ",
+        );
+
+        assert_eq!(asm.matches("__meraki_itoa_i64:\n").count(), 1, "{asm}");
+        assert_eq!(asm.matches("__meraki_itoa_u64:\n").count(), 1, "{asm}");
+        assert_eq!(
+            asm.matches("\tcall __meraki_itoa_i64\n").count(),
+            1,
+            "{asm}"
+        );
+        assert_eq!(
+            asm.matches("\tcall __meraki_itoa_u64\n").count(),
+            1,
+            "{asm}"
+        );
+    }
+
+    #[test]
+    fn void_ptr_cast_arithmetic() {
+        let asm = compile(
+            "
+            fn main() -> u8 {
+                let x: u64 = 5;
+                let p: *void = &x as *void;
+                let q: *u8 = (p as *u8) + 3;
+
+                return *q;
+            }
+            ",
+        );
+
+        // Cast to a byte pointer, the offset is scaled by 1 rather than the size of void
+        assert!(asm.contains("\tlea r15, [r15 + r14 * 1]\n"), "{asm}");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't do arithmetic on a pointer of type *void, cast it to *u8 first"
+    )]
+    fn void_ptr_add() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u64 = 5;
+                let p: *void = &x as *void;
+                let q: *void = p + 1;
+
+                return 0;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't do arithmetic on a pointer of type *void, cast it to *u8 first"
+    )]
+    fn void_ptr_sub() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u64 = 5;
+                let p: *void = &x as *void;
+
+                return (p - p) as u8;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't do arithmetic on a pointer of type *void, cast it to *u8 first"
+    )]
+    fn void_ptr_index() {
+        compile(
+            "
+            fn main() -> u8 {
+                let x: u64 = 5;
+                let p: *void = &x as *void;
+
+                p[1];
+
+                return 0;
+            }
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't print a value of type bool")]
+    fn print_bool() {
+        compile(
+            "
+            fn main() -> u8 {
+                print!(true);
+
+                return 0;
+            }
+            ",
+        );
+    }
+}
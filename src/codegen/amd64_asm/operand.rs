@@ -53,7 +53,18 @@ impl std::fmt::Display for Offset {
 #[derive(Clone, Debug, PartialEq, Display)]
 pub enum Base {
     Register(Register),
+    /// A symbol, loaded through the GOT when it may be defined in another
+    /// module. Addressed relative to `rip` either way, an absolute address
+    /// would need the executable to be linked at a fixed address.
+    #[display("rip + {_0}")]
     Label(String),
+    #[display("rip + {_0}")]
+    Rip(String),
+    /// A `thread_local` variable, at an offset from the thread pointer in
+    /// `fs:0`. It's never used as is, the address is computed into a register
+    /// first.
+    #[display("fs:{_0}@tpoff")]
+    Tls(String),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -89,7 +100,7 @@ impl std::fmt::Display for EffectiveAddress {
         }
 
         if let Some(scale) = self.scale {
-            str.push_str(&format!("* {scale}"));
+            str.push_str(&format!(" * {scale}"));
         }
 
         if let Some(displacement) = &self.displacement {
@@ -125,6 +136,8 @@ impl From<Register> for EffectiveAddress {
 pub enum Immediate {
     Int(i64),
     UInt(u64),
+    // Without `offset` a bare label would be read as a memory operand
+    #[display("offset {_0}")]
     Label(String),
 }
 
@@ -265,3 +278,49 @@ impl PartialEq<&Destination> for &Source {
         other == self
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Base, EffectiveAddress, Offset};
+    use crate::codegen::amd64_asm::register::Register;
+
+    #[test]
+    fn effective_address_displacement() {
+        let tests = [
+            (Register::Rbp.into_effective_addr(-8), "[rbp - 8]"),
+            (Register::R15.into_effective_addr(8), "[r15 + 8]"),
+            (Register::Rax.into_effective_addr(0), "[rax]"),
+            (
+                EffectiveAddress {
+                    base: Base::Label(String::from("foo")),
+                    index: None,
+                    scale: None,
+                    displacement: Some(Offset(-5)),
+                },
+                "[rip + foo - 5]",
+            ),
+            (
+                EffectiveAddress {
+                    base: Base::Register(Register::Rbp),
+                    index: Some(Register::Rcx),
+                    scale: Some(8),
+                    displacement: Some(Offset(-16)),
+                },
+                "[rbp + rcx * 8 - 16]",
+            ),
+            // A field at a positive offset of a local keeps the sign of the sum
+            (
+                Register::Rbp.into_effective_addr(-16) + Offset(4),
+                "[rbp - 12]",
+            ),
+            (
+                Register::Rdi.into_effective_addr(-4) + Offset(12),
+                "[rdi + 8]",
+            ),
+        ];
+
+        for (addr, expected) in tests {
+            assert_eq!(addr.to_string(), expected);
+        }
+    }
+}
@@ -222,6 +222,14 @@ impl Register {
         }
     }
 
+    /// Whether a called function has to preserve the register
+    pub fn callee_saved(&self) -> bool {
+        matches!(
+            self.resize(OperandSize::Qword),
+            Self::Rbp | Self::Rsp | Self::R12 | Self::R13 | Self::R14 | Self::R15
+        )
+    }
+
     pub fn size(&self) -> OperandSize {
         match self {
             Self::Al => OperandSize::Byte,
@@ -1,11 +1,35 @@
 pub mod amd64_asm;
 
 use crate::Context;
+use clap::ValueEnum;
+
+/// What fixed-width integer arithmetic does when the result doesn't fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, ValueEnum)]
+pub enum Overflow {
+    /// Keep the low bits of the result
+    #[default]
+    Wrap,
+    /// Abort the program
+    Trap,
+}
+
+/// Syntax of the generated assembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, ValueEnum)]
+pub enum Syntax {
+    /// Operands written destination first, registers without a prefix
+    #[default]
+    Intel,
+    /// Operands written source first, registers prefixed with `%` and sizes
+    /// told by the mnemonic
+    Att,
+}
 
 pub trait Codegen<'a, 'ir> {
+    type Error: std::error::Error;
+
     fn new(ctx: &'a Context<'ir>) -> Self
     where
         Self: Sized;
 
-    fn compile(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+    fn compile(&mut self) -> Result<Vec<u8>, Self::Error>;
 }
@@ -1,28 +1,38 @@
 use crate::{
-    codegen::{amd64_asm::Amd64Asm, Codegen},
-    diagnostics::Diagnostics,
-    lexer::Lexer,
+    ast::{self, dump::Dump, unused::Unused},
+    cache::{Cache, CacheStatus},
+    codegen::{
+        amd64_asm::{Amd64Asm, Amd64AsmError},
+        Codegen, Overflow, Syntax,
+    },
+    diagnostics::{Diagnostic, Diagnostics},
+    lexer::{span::Span, Lexer, Token, TokenKind},
     lowering::Lowering,
-    parser, Context,
+    parser,
+    ty_problem::TypeError,
+    Context,
 };
 use bumpalo::Bump;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::{
     fs::File,
     io::{Read, Write},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
-    process::Stdio,
+    process::{Child, ExitStatus, Stdio},
 };
+use thiserror::Error;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct CompileArgs {
-    /// Source code file to compile
+    /// Source code files to compile into a single translation unit, `-` reads from stdin
     #[arg(required = true)]
-    pub file: PathBuf,
+    pub files: Vec<PathBuf>,
 
-    /// Output binary file name
-    #[arg(short, default_value = "a.out")]
+    /// Output file name, `a.out` for an executable and the name of the first
+    /// source file with the `.a` extension for a static library
+    #[arg(short)]
     pub output: Option<PathBuf>,
 
     /// Produce assembly output
@@ -33,64 +43,258 @@ pub struct CompileArgs {
     #[arg(short = 'c', default_value_t = false, group = "output_t")]
     pub object_only: bool,
 
+    /// Produce something else than an executable
+    #[arg(long = "emit", value_enum, group = "output_t")]
+    pub emit: Option<Emit>,
+
     #[arg(long = "macro")]
     pub macro_libs: Vec<String>,
 
     #[arg(long = "shared", default_value_t = false)]
     pub shared: bool,
+
+    /// Generate position-independent code
+    #[arg(long = "pic", default_value_t = false)]
+    pub pic: bool,
+
+    /// Optimization level, 1 turns self-recursive tail calls into jumps and
+    /// propagates constants through locals
+    #[arg(short = 'O', default_value_t = 0)]
+    pub opt_level: u8,
+
+    /// Whether integer arithmetic wraps around or aborts the program on overflow
+    #[arg(long = "overflow", value_enum, default_value_t = Overflow::Wrap)]
+    pub overflow: Overflow,
+
+    /// Syntax of the assembly `-S` writes
+    #[arg(long = "syntax", value_enum, default_value_t = Syntax::Intel)]
+    pub syntax: Syntax,
+
+    /// Put a canary between the locals and the return address of every function,
+    /// aborting the program when it's overwritten
+    #[arg(long = "stack-protector", default_value_t = false)]
+    pub stack_protector: bool,
+
+    /// Compare every index into an array of a known length against it,
+    /// aborting the program when it's out of range
+    #[arg(long = "bounds-check", default_value_t = false)]
+    pub bounds_check: bool,
+
+    /// Emit call frame information, so debuggers can unwind the stack
+    #[arg(long = "debug", default_value_t = false)]
+    pub debug: bool,
+
+    /// Align the start of every loop like functions are, to 16 bytes
+    #[arg(long = "align-loops", default_value_t = false)]
+    pub align_loops: bool,
+
+    /// Compile `assert!` to nothing
+    #[arg(long = "no-assert", default_value_t = false)]
+    pub no_assert: bool,
+
+    /// Write the code generated for each statement and the address of every
+    /// variable into a `.lst` file, for debugging
+    #[arg(long = "listing", default_value_t = false)]
+    pub listing: bool,
+
+    /// Comment every instruction with the source code of the expression it
+    /// was generated for. Only a single source file can be annotated.
+    #[arg(long = "annotate", default_value_t = false)]
+    pub annotate: bool,
+
+    /// Reuse the assembly generated for the same source code and flags from an
+    /// earlier compilation, kept in this directory
+    #[arg(long = "cache-dir")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Generate the functions on this many threads, the assembly is the same
+    /// whatever the number
+    #[arg(short = 'j', long = "jobs", default_value_t = NonZeroUsize::MIN)]
+    pub jobs: NonZeroUsize,
+
+    /// Print the token stream and exit
+    #[arg(long = "emit-tokens", default_value_t = false, group = "output_t")]
+    pub emit_tokens: bool,
+
+    /// Print the parsed AST and exit
+    #[arg(long = "emit-ast", default_value_t = false, group = "output_t")]
+    pub emit_ast: bool,
 }
 
-pub fn compile(args: CompileArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let mut file = File::open(&args.file)?;
-    let mut source_code = String::new();
+impl CompileArgs {
+    /// Path the assembly and object files are named after. Source code read
+    /// from stdin has no name, so it falls back to `a`.
+    fn base_path(&self) -> PathBuf {
+        match self.files.first() {
+            Some(path) if path != Path::new("-") => path.clone(),
+            _ => PathBuf::from("a"),
+        }
+    }
 
-    file.read_to_string(&mut source_code)?;
+    /// Flags which change the generated code, part of the cache key
+    pub fn target(&self) -> Target {
+        Target {
+            pic: self.pic,
+            opt_level: self.opt_level,
+            overflow: self.overflow,
+            stack_protector: self.stack_protector,
+            bounds_check: self.bounds_check,
+            assertions: !self.no_assert,
+            debug: self.debug,
+            align_loops: self.align_loops,
+            syntax: self.syntax,
+        }
+    }
+}
 
-    let report_diag_and_exit = |diag: &Diagnostics| -> ! {
-        println!("{diag}");
+/// Kinds of output `--emit` can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Emit {
+    /// Archive of the object file, to be linked into other programs
+    Staticlib,
+}
 
-        std::process::exit(0x45)
-    };
+/// How the code is generated, the defaults are the ones of the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Target {
+    pub pic: bool,
+    pub opt_level: u8,
+    pub overflow: Overflow,
+    pub stack_protector: bool,
+    pub bounds_check: bool,
+    pub assertions: bool,
+    pub debug: bool,
+    pub align_loops: bool,
+    pub syntax: Syntax,
+}
 
-    let mut diagnostics = Diagnostics::new(&source_code);
-    let lexer = Lexer::new(&source_code);
-    let ast = match parser::Parser::new(lexer, &mut diagnostics).parse() {
-        Ok(ast) => ast,
-        Err(_) => report_diag_and_exit(&diagnostics),
-    };
+impl Default for Target {
+    fn default() -> Self {
+        Self {
+            pic: false,
+            opt_level: 0,
+            overflow: Overflow::Wrap,
+            stack_protector: false,
+            bounds_check: false,
+            assertions: true,
+            debug: false,
+            align_loops: false,
+            syntax: Syntax::Intel,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CompileError {
+    /// Errors found in the source code, rendered along with it
+    #[error("{0}")]
+    Diagnostics(String),
+    /// Type errors without a span to show the source code of
+    #[error("error: {0}")]
+    Type(#[from] TypeError),
+    #[error(transparent)]
+    Codegen(#[from] Amd64AsmError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An assembler, archiver or linker run which didn't succeed
+    #[error("`{0}` failed, {1}")]
+    Tool(&'static str, ExitStatus),
+}
 
-    if diagnostics.has_errors() {
-        report_diag_and_exit(&mut diagnostics);
+/// Compiles source code into assembly without touching the filesystem, for
+/// embedding the compiler. Warnings are dropped. Errors in the source code are
+/// returned rendered like on the command line.
+pub fn compile_str(source: &str, target: Target) -> Result<Vec<u8>, CompileError> {
+    let mut diagnostics = Diagnostics::new(source);
+    let ast = parser::Parser::new(Lexer::new(source), &mut diagnostics).parse();
+
+    match ast {
+        Ok(ast) if !diagnostics.has_errors() => {
+            match generate_code(vec![ast], target, false, None, NonZeroUsize::MIN) {
+                Ok((code, _)) => Ok(code),
+                Err(err) => Err(with_source(err, |_| Some(Diagnostics::new(source)))),
+            }
+        }
+        _ => Err(CompileError::Diagnostics(diagnostics.to_string())),
     }
+}
 
-    let allocator = Bump::new();
-    let mut ctx = Context::new(&allocator);
+fn read_source(path: &Path) -> std::io::Result<String> {
+    let mut source_code = String::new();
 
-    Lowering::new(&mut ctx).lower(ast);
-    ctx.ty_problem.solve(&ctx.ir);
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut source_code)?;
+    } else {
+        File::open(path)?.read_to_string(&mut source_code)?;
+    }
 
-    //MacroExpansion::new(args.macro_libs).run_pass(&mut stmts, &mut scope);
-    //SymbolResolver::new(()).run_pass(&mut stmts, &mut scope)?;
-    //TypeChecker::new(()).run_pass(&mut stmts, &mut scope)?;
+    Ok(source_code)
+}
 
-    let codegen: &mut dyn Codegen = &mut Amd64Asm::new(&ctx);
-    let code = codegen.compile()?;
+/// Compiles the files into an executable, a static library, an object file or
+/// assembly. With a cache directory the assembly of unchanged source code is
+/// reused, the returned status tells whether it was. Warnings aren't reported
+/// again then.
+pub fn compile(args: CompileArgs) -> Result<CacheStatus, CompileError> {
+    let sources = args
+        .files
+        .iter()
+        .map(|path| read_source(path))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    // Dumps, listings and annotations are produced only while compiling
+    let cache = args
+        .cache_dir
+        .as_ref()
+        .filter(|_| !(args.emit_tokens || args.emit_ast || args.listing || args.annotate))
+        .map(|dir| Cache::new(dir, &sources, args.target()));
+
+    let (code, status) = match cache.as_ref().and_then(Cache::get) {
+        Some(code) => (code, CacheStatus::Hit),
+        None => {
+            let Some(code) = generate(&args, &sources)? else {
+                return Ok(CacheStatus::Disabled);
+            };
+
+            match &cache {
+                Some(cache) => {
+                    cache.put(&code)?;
+
+                    (code, CacheStatus::Miss)
+                }
+                None => (code, CacheStatus::Disabled),
+            }
+        }
+    };
 
     if args.assembly_only {
-        let asm_filename = args.file.with_extension("s");
+        let asm_filename = args.base_path().with_extension("s");
         let mut file = std::fs::File::create(&asm_filename)?;
 
         file.write_all(&code)?;
 
-        return Ok(());
+        return Ok(status);
     }
 
-    let obj_filename = args.file.with_extension("o");
+    let obj_filename = args.base_path().with_extension("o");
 
     assemble(&code, &obj_filename)?;
 
     if args.object_only {
-        return Ok(());
+        return Ok(status);
+    }
+
+    if args.emit == Some(Emit::Staticlib) {
+        let archive_filename = match args.output {
+            Some(output) => output,
+            None => args.base_path().with_extension("a"),
+        };
+
+        let archived = archive(&obj_filename, &archive_filename);
+
+        std::fs::remove_file(&obj_filename)?;
+        archived?;
+
+        return Ok(status);
     }
 
     let binary_filename = if let Some(output) = args.output {
@@ -99,37 +303,260 @@ pub fn compile(args: CompileArgs) -> Result<(), Box<dyn std::error::Error>> {
         "a.out".into()
     };
 
-    link(&obj_filename, &binary_filename, args.shared)?;
+    let linked = link(&obj_filename, &binary_filename, args.shared);
 
     // Remove intermediate steps file
     std::fs::remove_file(&obj_filename)?;
+    linked?;
+
+    Ok(status)
+}
+
+/// Generates the assembly of the whole translation unit, `None` when only the
+/// tokens or the AST were asked for.
+fn generate(args: &CompileArgs, sources: &[String]) -> Result<Option<Vec<u8>>, CompileError> {
+    // Top-level items of every file are lowered together
+    let mut files = Vec::new();
+
+    for (path, source_code) in args.files.iter().zip(sources) {
+        let mut diagnostics = Diagnostics::new(source_code).file(file_name(path));
+        let lexer = Lexer::new(source_code);
+
+        if args.emit_tokens {
+            for token in lexer {
+                match token {
+                    Ok(token) => println!("{}", dump_token(&token)),
+                    Err((diag, span)) => diagnostics.error(diag, span),
+                }
+            }
+
+            if diagnostics.has_errors() {
+                return Err(CompileError::Diagnostics(diagnostics.to_string()));
+            }
+
+            continue;
+        }
+
+        match parser::Parser::new(lexer, &mut diagnostics).parse() {
+            Ok(items) => {
+                // Items skipped while recovering from an error would make
+                // the lints report bogus warnings
+                if !diagnostics.has_errors() {
+                    Unused::check(&items, &mut diagnostics);
+                }
+                files.push(items);
+            }
+            Err(_) => return Err(CompileError::Diagnostics(diagnostics.to_string())),
+        };
+
+        if diagnostics.has_errors() {
+            return Err(CompileError::Diagnostics(diagnostics.to_string()));
+        }
+
+        // Only warnings are left, they don't stop the compilation
+        if !diagnostics.is_empty() {
+            eprint!("{diagnostics}");
+        }
+    }
+
+    if args.emit_tokens {
+        return Ok(None);
+    }
+
+    if args.emit_ast {
+        print!("{}", Dump(&files.concat()));
+
+        return Ok(None);
+    }
 
-    Ok(())
+    // Spans are offsets into the file they were parsed from, they can't tell files apart
+    let annotate = match sources {
+        [source] if args.annotate => Some(source.as_str()),
+        _ => None,
+    };
+    let (code, listing) = generate_code(files, args.target(), args.listing, annotate, args.jobs)
+        .map_err(|err| {
+            with_source(err, |file| {
+                // Errors found after the files are merged don't tell which one they're in
+                let file = file.or((sources.len() == 1).then_some(0))?;
+
+                Some(Diagnostics::new(&sources[file]).file(file_name(&args.files[file])))
+            })
+        })?;
+
+    if let Some(listing) = listing {
+        std::fs::write(args.base_path().with_extension("lst"), listing)?;
+    }
+
+    Ok(Some(code))
 }
 
-fn assemble(source: &[u8], output: &Path) -> std::io::Result<()> {
+/// Lowers the items of the files of a translation unit and generates its
+/// assembly, along with the listing if asked for. The assembly is annotated
+/// with the source code `annotate` holds. The items are split between `jobs`
+/// threads, each lowering the files again: the context holds the bump
+/// allocator, which can't be shared between threads.
+fn generate_code(
+    files: Vec<Vec<ast::Item>>,
+    target: Target,
+    listing: bool,
+    annotate: Option<&str>,
+    jobs: NonZeroUsize,
+) -> Result<(Vec<u8>, Option<String>), CompileError> {
+    let jobs = jobs.get();
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (1..jobs)
+            .map(|job| {
+                let files = files.clone();
+
+                scope.spawn(move || {
+                    let allocator = Bump::new();
+                    let ctx = lower(files, &allocator)?;
+
+                    Ok::<_, CompileError>(
+                        codegen(&ctx, target, listing, annotate).fragments(|i| i % jobs == job)?,
+                    )
+                })
+            })
+            .collect();
+
+        let allocator = Bump::new();
+        let ctx = lower(files, &allocator)?;
+        let mut codegen = codegen(&ctx, target, listing, annotate);
+        let mut fragments = codegen.fragments(|i| i % jobs == 0)?;
+
+        for thread in threads {
+            fragments.extend(thread.join().unwrap()?);
+        }
+
+        let code = codegen.link(fragments)?;
+
+        Ok((code, codegen.listing_text()))
+    })
+}
+
+fn lower(files: Vec<Vec<ast::Item>>, allocator: &Bump) -> Result<Context<'_>, CompileError> {
+    let mut ctx = Context::new(allocator);
+
+    Lowering::new(&mut ctx).lower_files(files)?;
+    ctx.ty_problem.solve(&ctx.ir)?;
+
+    //MacroExpansion::new(args.macro_libs).run_pass(&mut stmts, &mut scope);
+    //SymbolResolver::new(()).run_pass(&mut stmts, &mut scope)?;
+    //TypeChecker::new(()).run_pass(&mut stmts, &mut scope)?;
+
+    Ok(ctx)
+}
+
+fn codegen<'a, 'ir>(
+    ctx: &'a Context<'ir>,
+    target: Target,
+    listing: bool,
+    annotate: Option<&'a str>,
+) -> Amd64Asm<'a, 'ir> {
+    Amd64Asm::new(ctx)
+        .pic(target.pic)
+        .opt_level(target.opt_level)
+        .overflow(target.overflow)
+        .stack_protector(target.stack_protector)
+        .bounds_check(target.bounds_check)
+        .assertions(target.assertions)
+        .debug(target.debug)
+        .align_loops(target.align_loops)
+        .syntax(target.syntax)
+        .listing(listing)
+        .annotate(annotate)
+}
+
+/// Renders a type error along with the code it's about, like the errors found
+/// while parsing. `diagnostics` gives the ones of the file with the index the
+/// error tells, `None` if the source code isn't known.
+fn with_source<'src>(
+    err: CompileError,
+    diagnostics: impl FnOnce(Option<usize>) -> Option<Diagnostics<'src>>,
+) -> CompileError {
+    let CompileError::Type(TypeError {
+        message,
+        span: Some(span),
+        file,
+    }) = &err
+    else {
+        return err;
+    };
+    let Some(mut diagnostics) = diagnostics(*file) else {
+        return err;
+    };
+
+    diagnostics.error(Diagnostic::Type(message.clone()), span.clone());
+
+    CompileError::Diagnostics(diagnostics.to_string())
+}
+
+/// Name of the file at `path` in diagnostics
+fn file_name(path: &Path) -> String {
+    if path == Path::new("-") {
+        String::from("<stdin>")
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Formats a token along with its byte span, literals are prefixed with their kind
+fn dump_token(token: &Token) -> String {
+    let Span { start, end } = token.span;
+
+    match token.kind {
+        TokenKind::Ident(_) | TokenKind::Integer(_) | TokenKind::String(_) | TokenKind::Char(_) => {
+            format!("{start}..{end} {} {token}", token.kind)
+        }
+        _ => format!("{start}..{end} {token}"),
+    }
+}
+
+/// Waits for `child`, a run of `tool`, to exit successfully
+fn wait(tool: &'static str, mut child: Child) -> Result<(), CompileError> {
+    let status = child.wait()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CompileError::Tool(tool, status))
+    }
+}
+
+fn assemble(source: &[u8], output: &Path) -> Result<(), CompileError> {
     let source = std::process::Command::new("echo")
         .stdout(Stdio::piped())
         .arg(std::str::from_utf8(source).unwrap())
         .spawn()?;
 
-    let as_args = vec![
-        "-msyntax=intel",
-        "-mnaked-reg",
-        "-o",
-        output.to_str().unwrap(),
-    ];
+    let as_args = vec!["-o", output.to_str().unwrap()];
 
-    std::process::Command::new("as")
+    let child = std::process::Command::new("as")
         .args(as_args)
         .stdin(Stdio::from(source.stdout.unwrap()))
-        .spawn()?
-        .wait()?;
+        .spawn()?;
+
+    wait("as", child)
+}
+
+fn archive(input: &Path, output: &Path) -> Result<(), CompileError> {
+    // `ar` adds to an existing archive, members left from another compilation
+    // would still be there
+    match std::fs::remove_file(output) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => (),
+    }
+
+    let child = std::process::Command::new("ar")
+        .args(["rcs", output.to_str().unwrap(), input.to_str().unwrap()])
+        .spawn()?;
 
-    Ok(())
+    wait("ar", child)
 }
 
-fn link(input: &Path, output: &Path, shared: bool) -> std::io::Result<()> {
+fn link(input: &Path, output: &Path, shared: bool) -> Result<(), CompileError> {
     const OBJ_PATH: &'static str = "/usr/lib/x86_64-linux-gnu";
     let linker = format!("{OBJ_PATH}/ld-linux-x86-64.so.2");
     let crt = format!("{OBJ_PATH}/crt1.o");
@@ -150,10 +577,7 @@ fn link(input: &Path, output: &Path, shared: bool) -> std::io::Result<()> {
         args.push("-shared");
     }
 
-    std::process::Command::new("ld")
-        .args(args)
-        .spawn()?
-        .wait()?;
+    let child = std::process::Command::new("ld").args(args).spawn()?;
 
-    Ok(())
+    wait("ld", child)
 }
@@ -1,10 +1,27 @@
-use crate::lexer::{span::Span, TokenKind};
+use crate::{
+    ast::const_eval::ConstEvalError,
+    lexer::{span::Span, TokenKind},
+};
 use derive_more::derive::Display;
 
 #[derive(Debug, Display)]
 pub enum Diagnostic {
     #[display("syntax error: unknown character")]
     UnknownChar,
+    #[display("syntax error: invalid digit for a base {_0} literal")]
+    InvalidDigit(u32),
+    #[display("syntax error: integer literal has no digits")]
+    MissingDigits,
+    #[display("syntax error: unterminated block comment")]
+    UnterminatedComment,
+    #[display("syntax error: unterminated string literal")]
+    UnterminatedString,
+    #[display("syntax error: unterminated character literal")]
+    UnterminatedChar,
+    #[display("syntax error: character literal must be a single ASCII character")]
+    InvalidCharLit,
+    #[display("syntax error: invalid escape sequence")]
+    InvalidEscape,
     #[display("syntax error: expected {_0}")]
     ParseExpected(String),
     #[display("syntax error: {_0} is not a valid prefix operator")]
@@ -13,12 +30,18 @@ pub enum Diagnostic {
     ExpressionInfix(TokenKind),
     #[display("field `{_0}` is already declared")]
     RepeatingField(String),
-    #[display("parameter `{_0}` is already declared")]
+    #[display("duplicate parameter `{_0}`")]
     RepeatingParam(String),
     #[display("integer literal is too large value exceeds limit of `{}`", u64::MAX)]
     IntegerLitralTooLong,
+    #[display("invalid suffix `{_0}` for integer literal")]
+    InvalidIntSuffix(String),
+    #[display("literal out of range for `{_0}`")]
+    IntLitOutOfRange(String),
     #[display("function definition is not allowed here")]
     IllegalFunctionDefinition,
+    #[display("`return` outside of function")]
+    ReturnOutsideFn,
     #[display("type mismatch: `{_0}` expected, `{_1}` found")]
     TypeMismatch(String, String),
     #[display("type hint required")]
@@ -27,6 +50,17 @@ pub enum Diagnostic {
     NotAPointer(String),
     #[display("cannot cast `{_0}` into `{_1}`")]
     InvalidCast(String, String),
+    #[display("array length must be a constant: {_0}")]
+    NonConstArrayLen(ConstEvalError),
+    #[display("array length `{_0}` is negative")]
+    NegativeArrayLen(i128),
+    #[display("unused variable `{_0}`")]
+    UnusedVariable(String),
+    #[display("value assigned to `{_0}` is overwritten before being read")]
+    OverwrittenAssignment(String),
+    /// Found after parsing, while lowering or inferring types
+    #[display("{_0}")]
+    Type(String),
 }
 
 #[derive(Debug, Eq, PartialEq, Display)]
@@ -47,6 +81,8 @@ struct Message {
 #[derive(Debug)]
 pub struct Diagnostics<'src> {
     source: &'src str,
+    /// Name of the file the source code is from
+    file: Option<String>,
     messages: Vec<Message>,
 }
 
@@ -54,10 +90,17 @@ impl<'src> Diagnostics<'src> {
     pub fn new(source: &'src str) -> Self {
         Self {
             source,
+            file: None,
             messages: Vec::new(),
         }
     }
 
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+
+        self
+    }
+
     pub fn error(&mut self, diag: Diagnostic, span: Span) {
         self.messages.push(Message {
             level: Level::Error,
@@ -74,10 +117,21 @@ impl<'src> Diagnostics<'src> {
         })
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
     pub fn has_errors(&self) -> bool {
         self.messages.iter().any(|msg| msg.level == Level::Error)
     }
 
+    pub fn error_count(&self) -> usize {
+        self.messages
+            .iter()
+            .filter(|msg| msg.level == Level::Error)
+            .count()
+    }
+
     fn row(&self, col: usize) -> usize {
         self.source[..col].chars().filter(|ch| ch == &'\n').count()
     }
@@ -91,12 +145,11 @@ impl<'src> Diagnostics<'src> {
             .unwrap_or(row)
     }
 
-    fn lines(&self, span: &Span) -> usize {
+    /// End of the part of the span on its first line
+    fn first_line_end(&self, span: &Span) -> usize {
         self.source[span.start..span.end]
-            .chars()
-            .filter(|ch| ch == &'\n')
-            .count()
-            + 1
+            .find('\n')
+            .map_or(span.end, |len| span.start + len)
     }
 }
 
@@ -118,35 +171,38 @@ impl std::fmt::Display for Diagnostics<'_> {
             let col = self.row(message.span.start);
             let row = self.column(message.span.start);
 
-            writeln!(f, "notarealfilename.rs:{}:{}", col + 1, row + 1)?;
-
-            if self.lines(&message.span) > 1 {
-                todo!("Dunno how to handle multiline diagnostics");
-            } else {
-                writeln!(
-                    f,
-                    "{BLUE_FG}{} |{RESET} {}",
-                    col + 1,
-                    self.source.lines().nth(col).unwrap()
-                )?;
-                writeln!(
-                    f,
-                    "{}{}",
-                    (0..4).into_iter().map(|_| " ").collect::<String>(),
-                    (0..self.column(message.span.end))
-                        .into_iter()
-                        .map(|i| {
-                            if (self.column(message.span.start)..self.column(message.span.end))
-                                .contains(&i)
-                            {
-                                format!("{color}^{RESET}")
-                            } else {
-                                " ".into()
-                            }
-                        })
-                        .collect::<String>()
-                )?;
-            }
+            writeln!(
+                f,
+                "{}:{}:{}",
+                self.file.as_deref().unwrap_or("notarealfilename.rs"),
+                col + 1,
+                row + 1
+            )?;
+
+            // Only the first line of a span covering several is shown
+            let end = self.first_line_end(&message.span);
+
+            writeln!(
+                f,
+                "{BLUE_FG}{} |{RESET} {}",
+                col + 1,
+                self.source.lines().nth(col).unwrap()
+            )?;
+            writeln!(
+                f,
+                "{}{}",
+                (0..4).into_iter().map(|_| " ").collect::<String>(),
+                (0..self.column(end))
+                    .into_iter()
+                    .map(|i| {
+                        if (self.column(message.span.start)..self.column(end)).contains(&i) {
+                            format!("{color}^{RESET}")
+                        } else {
+                            " ".into()
+                        }
+                    })
+                    .collect::<String>()
+            )?;
         }
 
         Ok(())
@@ -1,7 +1,10 @@
 mod ordered_map;
 mod types;
 
-use crate::ast::{BinOp, UnOp};
+use crate::{
+    ast::{BinOp, UnOp},
+    lexer::span::Span,
+};
 use bumpalo::Bump;
 
 pub use ordered_map::OrderedMap;
@@ -17,6 +20,37 @@ pub struct Id {
 pub struct Expr<'ir> {
     pub ty: &'ir Ty<'ir>,
     pub kind: ExprKind<'ir>,
+    /// Source code the expression was lowered from, expressions the compiler
+    /// makes up get the span of the one they stand for
+    pub span: &'ir Span,
+}
+
+impl Expr<'_> {
+    /// Whether the expression refers to a memory location, so its address can
+    /// be taken and it can be assigned to.
+    pub fn lvalue(&self) -> bool {
+        match self.kind {
+            ExprKind::Ident(_) | ExprKind::Unary(UnOp::Deref, _) => true,
+            ExprKind::Field(expr, _) => expr.lvalue(),
+            _ => false,
+        }
+    }
+
+    /// Whether the expression is the address of a function, which is what a
+    /// function name decays into.
+    pub fn fn_ptr(&self) -> bool {
+        matches!(
+            self.kind,
+            ExprKind::Unary(
+                UnOp::Address,
+                Expr {
+                    ty: Ty::Fn(..),
+                    kind: ExprKind::Ident(_),
+                    ..
+                }
+            )
+        )
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -29,6 +63,22 @@ pub enum ExprKind<'ir> {
     Struct(&'ir [(&'ir str, Expr<'ir>)]),
     Field(&'ir Expr<'ir>, &'ir str),
     Cast(&'ir Expr<'ir>, &'ir Ty<'ir>),
+    Array(&'ir [Expr<'ir>]),
+    /// Elements are accessed as fields named by their index
+    Tuple(&'ir [Expr<'ir>]),
+    Call(&'ir Expr<'ir>, &'ir [Expr<'ir>]),
+    InlineAsm(&'ir str),
+    AlignOf(&'ir Ty<'ir>),
+    SizeOf(&'ir Ty<'ir>),
+    /// Expansion of `print!`, writes each argument to stdout
+    Print(&'ir [Expr<'ir>]),
+    /// Expansion of `itoa!`, writes the decimal digits of an integer into a
+    /// buffer and evaluates to their count
+    Itoa(&'ir Expr<'ir>, &'ir Expr<'ir>),
+    /// Expansion of `assert!`, aborts the program with the message if the
+    /// condition is false
+    Assert(&'ir Expr<'ir>, &'ir str),
+    Block(Block<'ir>),
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -46,10 +96,24 @@ pub enum Stmt<'ir> {
     Item(Item<'ir>),
     Expr(Expr<'ir>),
     Return(Option<Expr<'ir>>),
+    If(Expr<'ir>, Block<'ir>, Option<Block<'ir>>),
+    Label(&'ir str),
+    Goto(&'ir str),
+    /// Initializer, condition, increment and body, `while` is a `for` with
+    /// only a condition. A destructuring initializer takes several statements.
+    For(
+        &'ir [Stmt<'ir>],
+        Option<Expr<'ir>>,
+        Option<Expr<'ir>>,
+        Block<'ir>,
+    ),
+    Continue,
+    Break,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Block<'ir>(pub &'ir [Stmt<'ir>]);
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Statements and the trailing expression the block evaluates to, if any
+pub struct Block<'ir>(pub &'ir [Stmt<'ir>], pub Option<&'ir Expr<'ir>>);
 
 #[derive(Debug, PartialEq)]
 pub struct Signature<'ir> {
@@ -61,8 +125,16 @@ pub struct Signature<'ir> {
 pub struct ItemFn<'ir> {
     pub id: Id,
     pub name: &'ir str,
+    pub params: &'ir [&'ir Variable<'ir>],
     pub signature: Signature<'ir>,
-    pub block: Block<'ir>,
+    /// Exported from the object file with `.global`
+    pub pub_: bool,
+    /// Emitted without a prologue or an epilogue
+    pub naked: bool,
+    /// Never returns to its caller
+    pub noreturn: bool,
+    /// `None` for `extern` functions defined elsewhere
+    pub block: Option<Block<'ir>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -71,18 +143,27 @@ pub struct Variable<'ir> {
     pub name: &'ir str,
     pub ty: &'ir Ty<'ir>,
     pub initializer: Option<Expr<'ir>>,
+    pub static_: bool,
+    /// Every thread has its own copy, only globals can be `thread_local`
+    pub thread_local: bool,
+    /// Exported from the object file, only globals can be `pub`
+    pub pub_: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Item<'ir> {
     Fn(&'ir ItemFn<'ir>),
     Global(&'ir Variable<'ir>),
-    Struct(&'ir [(&'ir str, &'ir Ty<'ir>)]),
+    /// Fields of a struct and whether it's packed
+    Struct(&'ir [(&'ir str, &'ir Ty<'ir>)], bool),
+    /// Fields of a union, all at offset 0
+    Union(&'ir [(&'ir str, &'ir Ty<'ir>)]),
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum Node<'ir> {
     Item(Item<'ir>),
+    Param(&'ir Variable<'ir>),
     Stmt(Stmt<'ir>),
     Expr(Expr<'ir>),
 }
@@ -118,6 +199,10 @@ impl<'ir> Ir<'ir> {
             .into_iter()
     }
 
+    pub fn alloc_ty(&self, ty: Ty<'ir>) -> &'ir Ty<'ir> {
+        self.allocator.alloc(ty)
+    }
+
     pub fn get_node(&self, id: Id) -> &'ir Node<'ir> {
         &self.globals[id.global_id].0[id.node_id]
     }
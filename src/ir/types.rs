@@ -21,7 +21,32 @@ impl IntTy {
     }
 }
 
+impl IntTy {
+    /// Width in bytes the type is ranked by when promoted. Pointer-sized
+    /// integers rank like the 64 bit ones, as wide as pointers on amd64.
+    pub fn rank(&self) -> usize {
+        self.size().unwrap_or(8)
+    }
+
+    /// Whether the integer literal `lit`, negated if `negative`, is in range.
+    /// The negative range reaches one further than the positive one.
+    pub fn fits_lit(&self, lit: u64, negative: bool) -> bool {
+        u128::from(lit) < (1 << (self.rank() * 8 - 1)) + u128::from(negative)
+    }
+}
+
 impl UintTy {
+    /// Width in bytes the type is ranked by when promoted, like [`IntTy::rank`]
+    pub fn rank(&self) -> usize {
+        self.size().unwrap_or(8)
+    }
+
+    /// Whether the integer literal `lit`, negated if `negative`, is in range,
+    /// which only `0` is when negated
+    pub fn fits_lit(&self, lit: u64, negative: bool) -> bool {
+        (!negative || lit == 0) && u128::from(lit) < 1 << (self.rank() * 8)
+    }
+
     fn size(&self) -> Option<usize> {
         Some(match self {
             Self::U8 => 1,
@@ -52,8 +77,11 @@ pub enum Ty<'ir> {
     UInt(UintTy),
     Ptr(&'ir Ty<'ir>),
     Array(TyArray<'ir>),
-    Fn(&'ir [&'ir Ty<'ir>], &'ir Ty<'ir>),
-    Struct(super::Id),
+    /// Parameters, return type and whether the function is variadic
+    Fn(&'ir [&'ir Ty<'ir>], &'ir Ty<'ir>, bool),
+    /// Id of the struct item and its name
+    Struct(super::Id, &'ir str),
+    Tuple(&'ir [&'ir Ty<'ir>]),
     Infer(ty_problem::Id),
 }
 
@@ -68,7 +96,7 @@ impl Ty<'_> {
             Self::Int(int) => int.size().unwrap_or_else(|| f(self)),
             Self::UInt(uint) => uint.size().unwrap_or_else(|| f(self)),
             Self::Array(ty_arr) => ty_arr.ty.size(f) * ty_arr.len,
-            Self::Ptr(_) | Self::Fn(_, _) | Self::Struct(_) => f(self),
+            Self::Ptr(_) | Self::Fn(..) | Self::Struct(..) | Self::Tuple(_) => f(self),
             Self::Infer(_) => unreachable!(),
         }
     }
@@ -82,17 +110,27 @@ impl std::fmt::Display for Ty<'_> {
             Self::Bool => write!(f, "bool"),
             Self::Void => write!(f, "void"),
             Self::Ptr(type_) => write!(f, "*{type_}"),
-            Self::Array(array) => write!(f, "{}[{}]", array.ty, array.len),
-            Self::Fn(params, return_type) => write!(
+            Self::Array(array) => write!(f, "[{}]{}", array.len, array.ty),
+            Self::Fn(params, return_type, variadic) => write!(
                 f,
-                "fn ({}) -> {return_type}",
+                "fn({}{}) -> {return_type}",
                 params
                     .iter()
                     .map(|type_| type_.to_string())
-                    .collect::<String>()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if *variadic { ", ..." } else { "" }
             ),
             Self::Null => write!(f, "NULL"),
-            Self::Struct(_) => write!(f, "owo"),
+            Self::Struct(_, name) => write!(f, "{name}"),
+            Self::Tuple(tys) => write!(
+                f,
+                "({})",
+                tys.iter()
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             Self::Infer(id) => write!(f, "infer({id:?})"),
         }
     }
@@ -107,6 +145,10 @@ impl<'ir> Ty<'ir> {
         matches!(self, Self::Array(..))
     }
 
+    pub fn aggregate(&self) -> bool {
+        matches!(self, Self::Struct(..) | Self::Array(..) | Self::Tuple(..))
+    }
+
     pub fn signed(&self) -> bool {
         matches!(self, Self::Int(..))
     }
@@ -114,4 +156,438 @@ impl<'ir> Ty<'ir> {
     pub fn int(&self) -> bool {
         matches!(self, Self::UInt(_) | Self::Int(_))
     }
+
+    /// Narrowest integer type holding the literal `lit`, negated if `negative`.
+    /// A negative value needs a signed type, so `-128` is an `i8` but `-129`
+    /// and `-255` widen to `i16`.
+    pub fn int_lit(lit: u64, negative: bool) -> Option<Self> {
+        let tys = if negative && lit != 0 {
+            [IntTy::I8, IntTy::I16, IntTy::I32, IntTy::I64].map(Self::Int)
+        } else {
+            [UintTy::U8, UintTy::U16, UintTy::U32, UintTy::U64].map(Self::UInt)
+        };
+
+        tys.into_iter().find(|ty| ty.fits_int_lit(lit, negative))
+    }
+
+    /// Whether the integer literal `lit`, negated if `negative`, is in range of
+    /// this type.
+    pub fn fits_int_lit(&self, lit: u64, negative: bool) -> bool {
+        match self {
+            Self::Int(ty) => ty.fits_lit(lit, negative),
+            Self::UInt(ty) => ty.fits_lit(lit, negative),
+            _ => false,
+        }
+    }
+
+    /// Whether a value of this type contains the struct `id` by value. Pointers
+    /// have a fixed size no matter what they point to, so they don't count.
+    pub fn embeds(&self, id: super::Id) -> bool {
+        match self {
+            Self::Struct(struct_id, _) => struct_id == &id,
+            Self::Array(TyArray { ty, .. }) => ty.embeds(id),
+            _ => false,
+        }
+    }
+
+    /// Type of the elements of a pointer or an array, the one indexing it
+    /// gives, or `None` if values of this type can't be indexed.
+    pub fn elem(&self) -> Option<&'ir Ty<'ir>> {
+        match self {
+            Self::Ptr(ty) | Self::Array(TyArray { ty, .. }) => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// Spelling of the type which can be part of a symbol name, used to give
+    /// each overload of a function its own label.
+    pub fn mangle(&self) -> String {
+        match self {
+            Self::Ptr(ty) => format!("p{}", ty.mangle()),
+            Self::Array(TyArray { ty, len }) => format!("a{len}{}", ty.mangle()),
+            Self::Fn(params, ret_ty, variadic) => format!(
+                "f{}{}{}r{}",
+                params.len(),
+                params.iter().map(|ty| ty.mangle()).collect::<String>(),
+                if *variadic { "v" } else { "" },
+                ret_ty.mangle()
+            ),
+            Self::Struct(id, _) => format!("s{}", id.global_id),
+            Self::Tuple(tys) => format!(
+                "t{}{}",
+                tys.len(),
+                tys.iter().map(|ty| ty.mangle()).collect::<String>()
+            ),
+            Self::Infer(_) => unreachable!(),
+            ty => ty.to_string().to_lowercase(),
+        }
+    }
+
+    /// Whether a value of this type can be stored in a place of type `ty`,
+    /// promoting it according to [`Ty::common_ty`] if needed.
+    pub fn coercible_to(&'ir self, ty: &'ir Ty<'ir>) -> bool {
+        self == ty || ty.common_ty(self) == Some(ty)
+    }
+
+    /// Whether an expression of this type can be explicitly casted into `ty`.
+    pub fn castable_to(&self, ty: &Ty<'ir>) -> bool {
+        match (self, ty) {
+            (Self::Int(_) | Self::UInt(_) | Self::Bool, Self::Int(_) | Self::UInt(_)) => true,
+            (Self::Ptr(_) | Self::Null, Self::Ptr(_)) => true,
+            (Self::Ptr(_), Self::UInt(UintTy::Usize))
+            | (Self::UInt(UintTy::Usize), Self::Ptr(_)) => true,
+            (Self::Array(TyArray { ty: elem, .. }), Self::Ptr(ty)) => {
+                elem == ty || ty == &&Self::Void
+            }
+            (lhs, rhs) => lhs == rhs,
+        }
+    }
+
+    /// Returns the type two integer operands are promoted to, or `None` if
+    /// either of them isn't an integer. Types are ranked by their width, see
+    /// [`IntTy::rank`]:
+    /// - operands of the same signedness promote to the wider of the two,
+    ///   `isize` and `usize` win over the 64 bit types of the same width;
+    /// - a signed operand strictly wider than the unsigned one wins, because
+    ///   it can represent every value of the unsigned type;
+    /// - otherwise the unsigned type wins, so mixing signedness at equal width
+    ///   promotes to the unsigned type.
+    pub fn common_ty(&'ir self, other: &'ir Ty<'ir>) -> Option<&'ir Ty<'ir>> {
+        Some(match (self, other) {
+            (Self::Int(lhs), Self::Int(rhs)) => {
+                if (lhs.rank(), lhs) >= (rhs.rank(), rhs) {
+                    self
+                } else {
+                    other
+                }
+            }
+            (Self::UInt(lhs), Self::UInt(rhs)) => {
+                if (lhs.rank(), lhs) >= (rhs.rank(), rhs) {
+                    self
+                } else {
+                    other
+                }
+            }
+            (Self::Int(int), Self::UInt(uint)) => {
+                if int.rank() > uint.rank() {
+                    self
+                } else {
+                    other
+                }
+            }
+            (Self::UInt(uint), Self::Int(int)) => {
+                if int.rank() > uint.rank() {
+                    other
+                } else {
+                    self
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ty, TyArray};
+    use crate::{
+        ast::{IntTy, UintTy},
+        ir::Id,
+    };
+
+    #[test]
+    fn int_ty_ordering() {
+        let int_tys = [IntTy::I8, IntTy::I16, IntTy::I32, IntTy::I64, IntTy::Isize];
+        let uint_tys = [
+            UintTy::U8,
+            UintTy::U16,
+            UintTy::U32,
+            UintTy::U64,
+            UintTy::Usize,
+        ];
+
+        for window in int_tys.windows(2) {
+            assert!(window[0] < window[1], "{} < {}", window[0], window[1]);
+        }
+
+        for window in uint_tys.windows(2) {
+            assert!(window[0] < window[1], "{} < {}", window[0], window[1]);
+        }
+
+        for (uint, int) in uint_tys.into_iter().zip(int_tys) {
+            assert_eq!(uint.to_signed(), int);
+        }
+    }
+
+    #[test]
+    fn negative_int_lit_ty() {
+        let tests = [
+            (0, true, Some(Ty::UInt(UintTy::U8))),
+            (255, false, Some(Ty::UInt(UintTy::U8))),
+            (256, false, Some(Ty::UInt(UintTy::U16))),
+            (128, true, Some(Ty::Int(IntTy::I8))),
+            (129, true, Some(Ty::Int(IntTy::I16))),
+            (u8::MAX.into(), true, Some(Ty::Int(IntTy::I16))),
+            (32768, true, Some(Ty::Int(IntTy::I16))),
+            (32769, true, Some(Ty::Int(IntTy::I32))),
+            (1 << 63, true, Some(Ty::Int(IntTy::I64))),
+            ((1 << 63) + 1, true, None),
+            (u64::MAX, false, Some(Ty::UInt(UintTy::U64))),
+        ];
+
+        for (lit, negative, expected) in tests {
+            assert_eq!(
+                Ty::int_lit(lit, negative),
+                expected,
+                "{lit}, negative: {negative}"
+            );
+        }
+    }
+
+    #[test]
+    fn display() {
+        let tests = [
+            (Ty::Ptr(&Ty::Int(IntTy::I32)), "*i32"),
+            (Ty::Ptr(&Ty::Ptr(&Ty::UInt(UintTy::U8))), "**u8"),
+            (
+                Ty::Array(TyArray {
+                    ty: &Ty::Int(IntTy::I32),
+                    len: 4,
+                }),
+                "[4]i32",
+            ),
+            (
+                Ty::Ptr(&Ty::Array(TyArray {
+                    ty: &Ty::Struct(Id::default(), "Foo"),
+                    len: 2,
+                })),
+                "*[2]Foo",
+            ),
+            (
+                Ty::Ptr(&Ty::Fn(
+                    &[&Ty::Int(IntTy::I32), &Ty::Bool],
+                    &Ty::Bool,
+                    false,
+                )),
+                "*fn(i32, bool) -> bool",
+            ),
+            (
+                Ty::Fn(
+                    &[&Ty::Ptr(&Ty::UInt(UintTy::U8))],
+                    &Ty::Int(IntTy::I32),
+                    true,
+                ),
+                "fn(*u8, ...) -> i32",
+            ),
+            (Ty::Tuple(&[&Ty::UInt(UintTy::U8), &Ty::Bool]), "(u8, bool)"),
+        ];
+
+        for (ty, expected) in tests {
+            assert_eq!(ty.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn elem() {
+        let tests = [
+            (Ty::Ptr(&Ty::Int(IntTy::I32)), Some(&Ty::Int(IntTy::I32))),
+            (Ty::Ptr(&Ty::Void), Some(&Ty::Void)),
+            (
+                Ty::Array(TyArray {
+                    ty: &Ty::Ptr(&Ty::Bool),
+                    len: 3,
+                }),
+                Some(&Ty::Ptr(&Ty::Bool)),
+            ),
+            (Ty::Int(IntTy::I32), None),
+            (Ty::Bool, None),
+            (Ty::Null, None),
+            (Ty::Struct(Id::default(), "Foo"), None),
+            (Ty::Tuple(&[&Ty::UInt(UintTy::U8)]), None),
+        ];
+
+        for (ty, expected) in tests {
+            assert_eq!(ty.elem(), expected, "{ty}");
+        }
+    }
+
+    #[test]
+    fn castable_to() {
+        let tests = [
+            (Ty::UInt(UintTy::U8), Ty::Int(IntTy::I64), true),
+            (Ty::Int(IntTy::I64), Ty::UInt(UintTy::U8), true),
+            (Ty::Bool, Ty::UInt(UintTy::U8), true),
+            (Ty::Ptr(&Ty::UInt(UintTy::U8)), Ty::Ptr(&Ty::Void), true),
+            (Ty::Ptr(&Ty::Void), Ty::Ptr(&Ty::Int(IntTy::I32)), true),
+            (Ty::Null, Ty::Ptr(&Ty::Int(IntTy::I32)), true),
+            (Ty::Ptr(&Ty::Bool), Ty::UInt(UintTy::Usize), true),
+            (Ty::UInt(UintTy::Usize), Ty::Ptr(&Ty::Bool), true),
+            (
+                Ty::Array(TyArray {
+                    ty: &Ty::Int(IntTy::I32),
+                    len: 4,
+                }),
+                Ty::Ptr(&Ty::Int(IntTy::I32)),
+                true,
+            ),
+            (
+                Ty::Array(TyArray {
+                    ty: &Ty::Int(IntTy::I32),
+                    len: 4,
+                }),
+                Ty::Ptr(&Ty::Void),
+                true,
+            ),
+            (
+                Ty::Struct(Id::default(), "Foo"),
+                Ty::Struct(Id::default(), "Foo"),
+                true,
+            ),
+            (Ty::Ptr(&Ty::Bool), Ty::UInt(UintTy::U32), false),
+            (Ty::Int(IntTy::I64), Ty::Ptr(&Ty::Bool), false),
+            (Ty::UInt(UintTy::U8), Ty::Bool, false),
+            (
+                Ty::Struct(Id::default(), "Foo"),
+                Ty::UInt(UintTy::U64),
+                false,
+            ),
+            (
+                Ty::UInt(UintTy::U64),
+                Ty::Struct(Id::default(), "Foo"),
+                false,
+            ),
+            (
+                Ty::Array(TyArray {
+                    ty: &Ty::Int(IntTy::I32),
+                    len: 4,
+                }),
+                Ty::Ptr(&Ty::UInt(UintTy::U8)),
+                false,
+            ),
+            (Ty::Void, Ty::UInt(UintTy::U8), false),
+        ];
+
+        for (ty, casted_ty, expected) in tests {
+            assert_eq!(ty.castable_to(&casted_ty), expected, "{ty} as {casted_ty}");
+        }
+    }
+
+    #[test]
+    fn common_ty() {
+        let tests = [
+            (
+                Ty::UInt(UintTy::U8),
+                Ty::UInt(UintTy::U8),
+                Some(Ty::UInt(UintTy::U8)),
+            ),
+            (
+                Ty::UInt(UintTy::U8),
+                Ty::UInt(UintTy::U16),
+                Some(Ty::UInt(UintTy::U16)),
+            ),
+            (
+                Ty::UInt(UintTy::U64),
+                Ty::UInt(UintTy::U32),
+                Some(Ty::UInt(UintTy::U64)),
+            ),
+            (
+                Ty::Int(IntTy::I8),
+                Ty::Int(IntTy::I32),
+                Some(Ty::Int(IntTy::I32)),
+            ),
+            (
+                Ty::Int(IntTy::Isize),
+                Ty::Int(IntTy::I64),
+                Some(Ty::Int(IntTy::Isize)),
+            ),
+            (
+                Ty::Int(IntTy::I32),
+                Ty::UInt(UintTy::U32),
+                Some(Ty::UInt(UintTy::U32)),
+            ),
+            (
+                Ty::UInt(UintTy::U32),
+                Ty::Int(IntTy::I32),
+                Some(Ty::UInt(UintTy::U32)),
+            ),
+            (
+                Ty::Int(IntTy::I16),
+                Ty::UInt(UintTy::U8),
+                Some(Ty::Int(IntTy::I16)),
+            ),
+            (
+                Ty::UInt(UintTy::U8),
+                Ty::Int(IntTy::I16),
+                Some(Ty::Int(IntTy::I16)),
+            ),
+            (
+                Ty::Int(IntTy::I8),
+                Ty::UInt(UintTy::U64),
+                Some(Ty::UInt(UintTy::U64)),
+            ),
+            (Ty::Bool, Ty::UInt(UintTy::U8), None),
+            (Ty::Ptr(&Ty::Void), Ty::UInt(UintTy::Usize), None),
+        ];
+
+        for (lhs, rhs, expected) in tests {
+            assert_eq!(
+                lhs.common_ty(&rhs),
+                expected.as_ref(),
+                "common type of {lhs} and {rhs}"
+            );
+        }
+    }
+
+    #[test]
+    fn common_ty_of_every_integer_pair() {
+        // Width in bytes of each type, pointer-sized integers are 64 bit wide
+        let tys = [
+            (Ty::UInt(UintTy::U8), 1),
+            (Ty::Int(IntTy::I8), 1),
+            (Ty::UInt(UintTy::U16), 2),
+            (Ty::Int(IntTy::I16), 2),
+            (Ty::UInt(UintTy::U32), 4),
+            (Ty::Int(IntTy::I32), 4),
+            (Ty::UInt(UintTy::U64), 8),
+            (Ty::Int(IntTy::I64), 8),
+            (Ty::UInt(UintTy::Usize), 8),
+            (Ty::Int(IntTy::Isize), 8),
+        ];
+        let pointer_sized = |ty: &Ty| matches!(ty, Ty::Int(IntTy::Isize) | Ty::UInt(UintTy::Usize));
+
+        for (lhs, lhs_width) in &tys {
+            for (rhs, rhs_width) in &tys {
+                let expected = match (lhs, rhs) {
+                    // At equal width the pointer-sized type wins
+                    (Ty::Int(_), Ty::Int(_)) | (Ty::UInt(_), Ty::UInt(_)) => {
+                        if (lhs_width, pointer_sized(lhs)) >= (rhs_width, pointer_sized(rhs)) {
+                            lhs
+                        } else {
+                            rhs
+                        }
+                    }
+                    (Ty::Int(_), _) if lhs_width > rhs_width => lhs,
+                    (Ty::UInt(_), _) if rhs_width > lhs_width => rhs,
+                    (Ty::UInt(_), _) => lhs,
+                    _ => rhs,
+                };
+
+                assert_eq!(
+                    lhs.common_ty(rhs),
+                    Some(expected),
+                    "common type of {lhs} and {rhs}"
+                );
+                assert_eq!(lhs.common_ty(rhs), rhs.common_ty(lhs));
+            }
+        }
+
+        assert_eq!(
+            Ty::Int(IntTy::Isize).common_ty(&Ty::UInt(UintTy::U64)),
+            Some(&Ty::UInt(UintTy::U64))
+        );
+        assert_eq!(
+            Ty::UInt(UintTy::Usize).common_ty(&Ty::Int(IntTy::I64)),
+            Some(&Ty::UInt(UintTy::Usize))
+        );
+    }
 }
@@ -1,8 +1,104 @@
 mod token;
 
+use crate::diagnostics::Diagnostic;
 use span::Span;
 pub use token::TokenKind;
 
+/// Parses the value of an integer literal token, taking its base prefix into account
+/// Integer literal suffixes which fix the type of the literal, like `10u8`
+pub const INT_LIT_SUFFIXES: [&str; 10] = [
+    "u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize",
+];
+
+/// Splits an integer literal into its digits and type suffix.
+pub fn split_int_lit_suffix(lit: &str) -> (&str, Option<&str>) {
+    match lit.find(['u', 'i']) {
+        Some(i) => (&lit[..i], Some(&lit[i..])),
+        None => (lit, None),
+    }
+}
+
+pub fn parse_int_lit(lit: &str) -> Result<u64, std::num::ParseIntError> {
+    let (lit, _) = split_int_lit_suffix(lit);
+    let (radix, digits) = match lit.get(..2) {
+        Some("0x") => (16, &lit[2..]),
+        Some("0o") => (8, &lit[2..]),
+        Some("0b") => (2, &lit[2..]),
+        _ => (10, lit),
+    };
+
+    u64::from_str_radix(digits, radix)
+}
+
+/// Decodes the escapes of a string or character literal starting at `start`
+/// into its bytes.
+fn unescape(lit: &str, start: usize, bytes: bool) -> Result<Vec<u8>, (Diagnostic, Span)> {
+    let mut decoded = Vec::with_capacity(lit.len());
+    let mut pos = 0;
+
+    while let Some(i) = lit[pos..].find('\\').map(|i| pos + i) {
+        decoded.extend_from_slice(&lit.as_bytes()[pos..i]);
+        pos = i + decode_escape(&lit[i..], bytes, &mut decoded).map_err(|len| {
+            let span = Span {
+                start: start + i,
+                end: start + i + len,
+            };
+
+            (Diagnostic::InvalidEscape, span)
+        })?;
+    }
+
+    decoded.extend_from_slice(&lit.as_bytes()[pos..]);
+
+    Ok(decoded)
+}
+
+/// Decodes the escape `lit` starts with, returning its length, or the length
+/// of the invalid part of it. `\xHH` is limited to ASCII unless `bytes`,
+/// anything above isn't a character on its own, and `\u{...}` is encoded as
+/// UTF-8.
+fn decode_escape(lit: &str, bytes: bool, decoded: &mut Vec<u8>) -> Result<usize, usize> {
+    let hex = |digits: &str| digits.chars().all(|ch| ch.is_ascii_hexdigit());
+    let byte = match lit[1..].chars().next() {
+        Some('n') => b'\n',
+        Some('t') => b'\t',
+        Some('r') => b'\r',
+        Some('0') => b'\0',
+        Some(ch @ ('\\' | '"' | '\'')) => ch as u8,
+        Some('x') => {
+            let digits = lit.get(2..4).filter(|digits| hex(digits)).ok_or(2_usize)?;
+
+            match u8::from_str_radix(digits, 16) {
+                Ok(byte) if bytes || byte.is_ascii() => decoded.push(byte),
+                _ => return Err(4),
+            }
+
+            return Ok(4);
+        }
+        Some('u') => {
+            let end = lit
+                .find('}')
+                .filter(|_| lit[2..].starts_with('{'))
+                .ok_or(2_usize)?;
+            let digits = &lit[3..end];
+            let ch = Some(digits)
+                .filter(|digits| (1..=6).contains(&digits.len()) && hex(digits))
+                .and_then(|digits| char::from_u32(u32::from_str_radix(digits, 16).ok()?))
+                .ok_or(end + 1)?;
+
+            decoded.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+
+            return Ok(end + 1);
+        }
+        Some(ch) => return Err(1 + ch.len_utf8()),
+        None => return Err(1),
+    };
+
+    decoded.push(byte);
+
+    Ok(2)
+}
+
 pub mod span {
     #[derive(Debug, Clone, PartialEq)]
     pub struct Span {
@@ -31,6 +127,17 @@ pub struct Token {
     pub span: Span,
 }
 
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            TokenKind::Ident(value) | TokenKind::Integer(value) => write!(f, "{value}"),
+            TokenKind::String(value) => write!(f, "{value:?}"),
+            TokenKind::Char(value) => write!(f, "{:?}", char::from(*value)),
+            kind => write!(f, "{kind}"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Lexer<'src> {
     input: &'src str,
@@ -63,6 +170,7 @@ impl<'src> Lexer<'src> {
             }
             None => {
                 self.ch = '\0';
+                self.position = self.read_position;
             }
         }
     }
@@ -81,28 +189,117 @@ impl<'src> Lexer<'src> {
         self.input[pos..self.position].to_string()
     }
 
-    fn read_int(&mut self) -> String {
-        let pos = self.position;
+    fn read_int(&mut self) -> Result<String, Diagnostic> {
+        let mut lit = String::new();
+        let radix = match (self.ch, self.peek()) {
+            ('0', Some(prefix @ ('x' | 'o' | 'b'))) => {
+                lit.push('0');
+                lit.push(prefix);
+                self.read_char();
+                self.read_char();
+
+                match prefix {
+                    'x' => 16,
+                    'o' => 8,
+                    _ => 2,
+                }
+            }
+            _ => 10,
+        };
+        let mut invalid = false;
+        let mut digits = 0;
+
+        while self.ch.is_ascii_alphanumeric() || self.ch == '_' {
+            if digits > 0 && matches!(self.ch, 'u' | 'i') {
+                let suffix = self.read_ident();
+
+                if !INT_LIT_SUFFIXES.contains(&suffix.as_str()) {
+                    return Err(Diagnostic::InvalidIntSuffix(suffix));
+                }
+
+                lit.push_str(&suffix);
+
+                break;
+            }
+            if self.ch != '_' {
+                invalid |= !self.ch.is_digit(radix);
+                digits += 1;
+                lit.push(self.ch);
+            }
 
-        while self.ch.is_ascii_digit() {
             self.read_char();
         }
 
-        self.input[pos..self.position].to_string()
+        if invalid {
+            Err(Diagnostic::InvalidDigit(radix))
+        } else if digits == 0 {
+            Err(Diagnostic::MissingDigits)
+        } else {
+            Ok(lit)
+        }
     }
 
-    fn read_string(&mut self) -> String {
+    /// Reads the literal up to and including the closing `quote`, or up to
+    /// the end of the line if it can't span lines.
+    fn read_quoted(&mut self, quote: char, multiline: bool) -> Option<&'src str> {
         let pos = self.position + 1;
 
         loop {
             self.read_char();
 
-            if self.ch == '"' || self.ch == '\0' {
-                break self.input[pos..self.position].to_string();
+            // An escaped quote doesn't close the literal
+            if self.ch == '\\' && !matches!(self.peek(), Some('\n') | None) {
+                self.read_char();
+
+                continue;
+            }
+
+            match self.ch {
+                ch if ch == quote => {
+                    let lit = &self.input[pos..self.position];
+
+                    self.read_char();
+
+                    break Some(lit);
+                }
+                '\n' if !multiline => break None,
+                '\0' => break None,
+                _ => (),
             }
         }
     }
 
+    /// Strings can span lines, an unterminated one would take the rest of the
+    /// file with it. It's cut at the end of the line it starts on instead, so
+    /// lexing picks up on the next one.
+    fn read_string(&mut self) -> Result<String, (Diagnostic, Span)> {
+        let start = self.position;
+        let Some(lit) = self.read_quoted('"', true) else {
+            self.read_position = self.input[start..]
+                .find('\n')
+                .map_or(self.input.len(), |end| start + end);
+            self.read_char();
+
+            return Err((Diagnostic::UnterminatedString, self.span()));
+        };
+        let bytes = unescape(lit, start + 1, false)?;
+
+        // Only escapes of ASCII characters are allowed, so the bytes are UTF-8
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+
+    fn read_char_lit(&mut self) -> Result<u8, (Diagnostic, Span)> {
+        let start = self.position;
+        let lit = self
+            .read_quoted('\'', false)
+            .ok_or_else(|| (Diagnostic::UnterminatedChar, self.span()))?;
+
+        match unescape(lit, start + 1, true)?[..] {
+            [byte] => Ok(byte),
+            _ => Err((Diagnostic::InvalidCharLit, self.span())),
+        }
+    }
+
     fn skip_whitespace(&mut self) {
         while self.ch.is_ascii_whitespace() {
             self.read_char();
@@ -110,7 +307,34 @@ impl<'src> Lexer<'src> {
     }
 
     fn skip_comment(&mut self) {
-        while self.ch != '\n' {
+        while self.ch != '\n' && self.ch != '\0' {
+            self.read_char();
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), Diagnostic> {
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek()) {
+                ('/', Some('*')) => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', Some('/')) => {
+                    depth -= 1;
+                    self.read_char();
+
+                    if depth == 0 {
+                        self.read_char();
+
+                        return Ok(());
+                    }
+                }
+                ('\0', _) => return Err(Diagnostic::UnterminatedComment),
+                _ => (),
+            }
+
             self.read_char();
         }
     }
@@ -124,7 +348,7 @@ impl<'src> Lexer<'src> {
 }
 
 impl<'src> Iterator for Lexer<'src> {
-    type Item = Result<Token, Span>;
+    type Item = Result<Token, (Diagnostic, Span)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.skip_whitespace();
@@ -148,16 +372,31 @@ impl<'src> Iterator for Lexer<'src> {
                 }
             }
             '+' => TokenKind::Plus,
-            '/' => {
-                if self.peek() == Some('/') {
+            '/' => match self.peek() {
+                Some('/') => {
                     self.skip_comment();
 
                     return self.next();
+                }
+                Some('*') => {
+                    if let Err(diag) = self.skip_block_comment() {
+                        return Some(Err((diag, self.span())));
+                    }
+
+                    return self.next();
+                }
+                _ => TokenKind::Slash,
+            },
+            '%' => TokenKind::Percent,
+            '.' => {
+                if self.input[self.read_position..].starts_with("..") {
+                    self.read_char();
+                    self.read_char();
+                    TokenKind::Ellipsis
                 } else {
-                    TokenKind::Slash
+                    TokenKind::Period
                 }
             }
-            '.' => TokenKind::Period,
             '~' => TokenKind::Tilde,
             '&' => {
                 if self.peek() == Some('&') {
@@ -215,15 +454,27 @@ impl<'src> Iterator for Lexer<'src> {
             ']' => TokenKind::RBracket,
             ',' => TokenKind::Comma,
             ':' => TokenKind::Colon,
-            '"' => TokenKind::String(self.read_string()),
-            '0'..='9' => {
-                let num = self.read_int();
-
-                return Some(Ok(Token {
-                    kind: TokenKind::Integer(num),
+            '"' | '\'' => {
+                let kind = match self.ch {
+                    '"' => self.read_string().map(TokenKind::String),
+                    _ => self.read_char_lit().map(TokenKind::Char),
+                };
+
+                return Some(kind.map(|kind| Token {
+                    kind,
                     span: self.span(),
                 }));
             }
+            '0'..='9' => {
+                return Some(
+                    self.read_int()
+                        .map(|num| Token {
+                            kind: TokenKind::Integer(num),
+                            span: self.span(),
+                        })
+                        .map_err(|diag| (diag, self.span())),
+                );
+            }
             '\0' => return None,
             ch if ch.is_alphanumeric() || ch == '_' => {
                 let ident = self.read_ident();
@@ -233,9 +484,14 @@ impl<'src> Iterator for Lexer<'src> {
                         "const" => TokenKind::Const,
                         "true" => TokenKind::True,
                         "let" => TokenKind::Let,
+                        "static" => TokenKind::Static,
                         "fn" => TokenKind::Fn,
                         "enum" => TokenKind::Enum,
                         "struct" => TokenKind::Struct,
+                        "union" => TokenKind::Union,
+                        "restrict" => TokenKind::Restrict,
+                        "defer" => TokenKind::Defer,
+                        "type" => TokenKind::Type,
                         "false" => TokenKind::False,
                         "if" => TokenKind::If,
                         "while" => TokenKind::While,
@@ -245,6 +501,12 @@ impl<'src> Iterator for Lexer<'src> {
                         "as" => TokenKind::As,
                         "continue" => TokenKind::Continue,
                         "break" => TokenKind::Break,
+                        "goto" => TokenKind::Goto,
+                        "asm" => TokenKind::Asm,
+                        "extern" => TokenKind::Extern,
+                        "pub" => TokenKind::Pub,
+                        "alignof" => TokenKind::AlignOf,
+                        "sizeof" => TokenKind::SizeOf,
                         "u8" => TokenKind::U8,
                         "u16" => TokenKind::U16,
                         "u32" => TokenKind::U32,
@@ -266,7 +528,7 @@ impl<'src> Iterator for Lexer<'src> {
             _ => {
                 self.read_char();
 
-                return Some(Err(self.span()));
+                return Some(Err((Diagnostic::UnknownChar, self.span())));
             }
         };
 
@@ -281,8 +543,8 @@ impl<'src> Iterator for Lexer<'src> {
 
 #[cfg(test)]
 mod test {
-    use super::Lexer;
-    use crate::lexer::TokenKind;
+    use super::{parse_int_lit, Lexer};
+    use crate::{diagnostics::Diagnostic, lexer::TokenKind};
 
     #[test]
     fn source_into_tokens() {
@@ -297,6 +559,7 @@ mod test {
             !
             *
             /
+            %
             ->
             .
             ~
@@ -321,6 +584,7 @@ mod test {
             }
             [
             ]
+            ...
 
             // keywords
             // heyo :D
@@ -331,6 +595,10 @@ mod test {
             fn
             enum
             struct
+            union
+            restrict
+            defer
+            type
             if
             while
             for
@@ -339,6 +607,12 @@ mod test {
             as
             continue
             break
+            goto
+            asm
+            extern
+            pub
+            alignof
+            sizeof
 
             u8
             u16
@@ -365,6 +639,7 @@ mod test {
             TokenKind::Bang,
             TokenKind::Asterisk,
             TokenKind::Slash,
+            TokenKind::Percent,
             TokenKind::Arrow,
             TokenKind::Period,
             TokenKind::Tilde,
@@ -389,6 +664,7 @@ mod test {
             TokenKind::RBrace,
             TokenKind::LBracket,
             TokenKind::RBracket,
+            TokenKind::Ellipsis,
             TokenKind::Const,
             TokenKind::True,
             TokenKind::False,
@@ -396,6 +672,10 @@ mod test {
             TokenKind::Fn,
             TokenKind::Enum,
             TokenKind::Struct,
+            TokenKind::Union,
+            TokenKind::Restrict,
+            TokenKind::Defer,
+            TokenKind::Type,
             TokenKind::If,
             TokenKind::While,
             TokenKind::For,
@@ -404,6 +684,12 @@ mod test {
             TokenKind::As,
             TokenKind::Continue,
             TokenKind::Break,
+            TokenKind::Goto,
+            TokenKind::Asm,
+            TokenKind::Extern,
+            TokenKind::Pub,
+            TokenKind::AlignOf,
+            TokenKind::SizeOf,
             TokenKind::U8,
             TokenKind::U16,
             TokenKind::U32,
@@ -427,4 +713,227 @@ mod test {
             assert_eq!(kind, next_token.kind);
         }
     }
+
+    #[test]
+    fn int_lit_bases() {
+        let tests = [
+            ("69", 69),
+            ("1_000_000", 1_000_000),
+            ("0xFF", 255),
+            ("0xdead_beef", 0xdead_beef),
+            ("0o17", 15),
+            ("0b1010", 10),
+            ("0b1111_0000", 240),
+            ("10u8", 10),
+            ("0xffi64", 255),
+            ("1_000usize", 1000),
+        ];
+
+        for (input, expected) in tests {
+            let token = Lexer::new(input).next().unwrap().unwrap();
+
+            match token.kind {
+                TokenKind::Integer(lit) => assert_eq!(parse_int_lit(&lit), Ok(expected), "{input}"),
+                kind => panic!("Expected integer literal, got {kind}"),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_int_lit() {
+        let tests = [("0b2", 2), ("0o8", 8), ("0xfg", 16), ("12ab", 10)];
+
+        for (input, radix) in tests {
+            match Lexer::new(input).next().unwrap() {
+                Err((Diagnostic::InvalidDigit(base), span)) => {
+                    assert_eq!(base, radix, "{input}");
+                    assert_eq!((span.start, span.end), (0, input.len()), "{input}");
+                }
+                result => panic!("Expected invalid digit error for {input}, got {result:?}"),
+            }
+        }
+
+        assert!(matches!(
+            Lexer::new("0x").next().unwrap(),
+            Err((Diagnostic::MissingDigits, _))
+        ));
+        assert!(matches!(
+            Lexer::new("5u7").next().unwrap(),
+            Err((Diagnostic::InvalidIntSuffix(suffix), _)) if suffix == "u7"
+        ));
+    }
+
+    #[test]
+    fn comments() {
+        let input = r#"
+            // line comment
+            let/* block comment */foo // trailing comment
+            /* multiline
+             * block comment
+             */
+            =
+            /* nested /* block */ comment */ 69;
+            /**/ /***/
+            // comment at the end of file"#;
+
+        let tokens = vec![
+            TokenKind::Let,
+            TokenKind::Ident(String::from("foo")),
+            TokenKind::Assign,
+            TokenKind::Integer(String::from("69")),
+            TokenKind::Semicolon,
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for kind in tokens {
+            assert_eq!(kind, lexer.next().unwrap().unwrap().kind);
+        }
+
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let input = "let foo /* /* nested */ comment";
+        let mut lexer = Lexer::new(input);
+
+        lexer.next().unwrap().unwrap();
+        lexer.next().unwrap().unwrap();
+
+        match lexer.next().unwrap() {
+            Err((Diagnostic::UnterminatedComment, span)) => {
+                assert_eq!((span.start, span.end), (8, input.len()));
+            }
+            result => panic!("Expected unterminated comment error, got {result:?}"),
+        }
+
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn char_lits() {
+        let tests = [("'a'", b'a'), ("' '", b' '), ("'\"'", b'"')];
+
+        for (input, expected) in tests {
+            match Lexer::new(input).next().unwrap().unwrap().kind {
+                TokenKind::Char(ch) => assert_eq!(ch, expected, "{input}"),
+                kind => panic!("Expected character literal, got {kind}"),
+            }
+        }
+    }
+
+    #[test]
+    fn unterminated_literals() {
+        let input = "let s = \"abc;\nlet c = 'a;\nlet d = 0;";
+        let mut lexer = Lexer::new(input);
+
+        for _ in 0..3 {
+            lexer.next().unwrap().unwrap();
+        }
+
+        match lexer.next().unwrap() {
+            Err((Diagnostic::UnterminatedString, span)) => {
+                assert_eq!((span.start, span.end), (8, 13));
+            }
+            result => panic!("Expected unterminated string error, got {result:?}"),
+        }
+
+        // Lexing goes on with the next line
+        assert_eq!(lexer.next().unwrap().unwrap().kind, TokenKind::Let);
+
+        for _ in 0..2 {
+            lexer.next().unwrap().unwrap();
+        }
+
+        match lexer.next().unwrap() {
+            Err((Diagnostic::UnterminatedChar, span)) => {
+                assert_eq!((span.start, span.end), (22, 25));
+            }
+            result => panic!("Expected unterminated character error, got {result:?}"),
+        }
+
+        let tokens = [
+            TokenKind::Let,
+            TokenKind::Ident(String::from("d")),
+            TokenKind::Assign,
+            TokenKind::Integer(String::from("0")),
+            TokenKind::Semicolon,
+        ];
+
+        for kind in tokens {
+            assert_eq!(kind, lexer.next().unwrap().unwrap().kind);
+        }
+
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn escapes() {
+        let tests = [
+            (r#""a\n\t\r\0\\\"\'b""#, "a\n\t\r\0\\\"'b"),
+            (r#""\x41\x7f""#, "A\x7f"),
+            (r#""\u{41}\u{e9}\u{1F600}""#, "Aé😀"),
+        ];
+
+        for (input, expected) in tests {
+            match Lexer::new(input).next().unwrap().unwrap().kind {
+                TokenKind::String(string) => assert_eq!(string, expected, "{input}"),
+                kind => panic!("Expected string literal, got {kind}"),
+            }
+        }
+
+        let tests = [(r"'\n'", b'\n'), (r"'\''", b'\''), (r"'\xff'", 0xff)];
+
+        for (input, expected) in tests {
+            match Lexer::new(input).next().unwrap().unwrap().kind {
+                TokenKind::Char(ch) => assert_eq!(ch, expected, "{input}"),
+                kind => panic!("Expected character literal, got {kind}"),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        let tests = [
+            (r#""a\qb""#, (2, 4)),
+            (r#""\x4""#, (1, 3)),
+            (r#""\xff""#, (1, 5)),
+            (r#""\u41""#, (1, 3)),
+            (r#""\u{}""#, (1, 5)),
+            (r#""\u{d800}""#, (1, 9)),
+            (r#""\u{1234567}""#, (1, 12)),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+
+            match lexer.next().unwrap() {
+                Err((Diagnostic::InvalidEscape, span)) => {
+                    assert_eq!((span.start, span.end), expected, "{input}");
+                }
+                result => panic!("Expected invalid escape error for {input}, got {result:?}"),
+            }
+
+            // The whole literal is skipped
+            assert!(lexer.next().is_none(), "{input}");
+        }
+
+        assert!(matches!(
+            Lexer::new(r"'\u{e9}'").next().unwrap(),
+            Err((Diagnostic::InvalidCharLit, _))
+        ));
+    }
+
+    #[test]
+    fn invalid_char_lit() {
+        for input in ["''", "'ab'", "'é'"] {
+            match Lexer::new(input).next().unwrap() {
+                Err((Diagnostic::InvalidCharLit, span)) => {
+                    assert_eq!((span.start, span.end), (0, input.len()), "{input}");
+                }
+                result => panic!("Expected invalid character error for {input}, got {result:?}"),
+            }
+        }
+    }
 }
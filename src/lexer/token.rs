@@ -12,6 +12,8 @@ pub enum TokenKind {
     Integer(String),
     #[display("string literal")]
     String(String),
+    #[display("character literal")]
+    Char(u8),
 
     #[display("=")]
     Assign,
@@ -25,10 +27,14 @@ pub enum TokenKind {
     Asterisk,
     #[display("/")]
     Slash,
+    #[display("%")]
+    Percent,
     #[display("->")]
     Arrow,
     #[display(".")]
     Period,
+    #[display("...")]
+    Ellipsis,
     #[display("~")]
     Tilde,
     #[display("&")]
@@ -82,12 +88,22 @@ pub enum TokenKind {
     False,
     #[display("let")]
     Let,
+    #[display("static")]
+    Static,
     #[display("fn")]
     Fn,
     #[display("enum")]
     Enum,
     #[display("struct")]
     Struct,
+    #[display("union")]
+    Union,
+    #[display("restrict")]
+    Restrict,
+    #[display("defer")]
+    Defer,
+    #[display("type")]
+    Type,
     #[display("if")]
     If,
     #[display("while")]
@@ -104,6 +120,18 @@ pub enum TokenKind {
     Continue,
     #[display("break")]
     Break,
+    #[display("goto")]
+    Goto,
+    #[display("asm")]
+    Asm,
+    #[display("extern")]
+    Extern,
+    #[display("pub")]
+    Pub,
+    #[display("alignof")]
+    AlignOf,
+    #[display("sizeof")]
+    SizeOf,
 
     #[display("u8")]
     U8,
@@ -1,4 +1,5 @@
 pub mod ast;
+pub mod cache;
 pub mod codegen;
 pub mod compile;
 pub mod diagnostics;
@@ -26,11 +27,11 @@ impl<'ir> Context<'ir> {
         Self {
             allocator,
             ir: Ir::new(allocator),
-            ty_problem: TyProblem::new(),
+            ty_problem: TyProblem::new(allocator),
         }
     }
 
     pub fn resolve_ty(&self, ty: &'ir Ty<'ir>) -> &'ir Ty<'ir> {
-        self.ty_problem.resolve_ty(self, ty)
+        self.ty_problem.resolve_ty(ty)
     }
 }
@@ -0,0 +1,329 @@
+use crate::{
+    ast::{BinOp, UnOp},
+    ir::{Block, Expr, ExprKind, Id, Stmt, Ty},
+    ty_problem::TypeError,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Locals certainly holding a value at some point of the code, `None` where
+/// the code can't be reached.
+type Assigned = Option<HashSet<Id>>;
+
+/// Definite assignment analysis, makes sure a local declared without an
+/// initializer gets a value on every path leading to a read of it.
+#[derive(Debug)]
+pub struct Uninit<'a, 'ir> {
+    /// Locals declared without an initializer
+    locals: HashMap<Id, &'ir str>,
+    /// Locals assigned on every `goto` to each label found so far
+    labels: HashMap<&'ir str, Assigned>,
+    /// Functions which never return
+    noreturn: &'a HashSet<Id>,
+}
+
+impl<'a, 'ir> Uninit<'a, 'ir> {
+    pub fn check(block: &Block<'ir>, noreturn: &'a HashSet<Id>) -> Result<(), TypeError> {
+        let mut uninit = Self {
+            locals: HashMap::new(),
+            labels: HashMap::new(),
+            noreturn,
+        };
+
+        // A `goto` jumping backward is only walked after its label, so the
+        // body is walked again until what's known at every label settles
+        loop {
+            let labels = uninit.labels.clone();
+
+            uninit.block(block, &mut Some(HashSet::new()))?;
+
+            if uninit.labels == labels {
+                return Ok(());
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block<'ir>, assigned: &mut Assigned) -> Result<(), TypeError> {
+        for stmt in block.0 {
+            self.stmt(stmt, assigned)?;
+        }
+
+        if let Some(expr) = block.1 {
+            self.expr(expr, assigned)?;
+        }
+
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'ir>, assigned: &mut Assigned) -> Result<(), TypeError> {
+        match stmt {
+            Stmt::Local(var) => match &var.initializer {
+                Some(expr) => self.expr(expr, assigned)?,
+                // Statics are zeroed
+                None if var.static_ => (),
+                None => {
+                    self.locals.insert(var.id, var.name);
+
+                    // Running the declaration again, like in a loop, drops the value
+                    if let Some(assigned) = assigned {
+                        assigned.remove(&var.id);
+                    }
+                }
+            },
+            // Nested functions are checked on their own
+            Stmt::Item(_) => (),
+            Stmt::Expr(expr) => {
+                self.expr(expr, assigned)?;
+
+                if self.noreturn_call(expr) {
+                    *assigned = None;
+                }
+            }
+            Stmt::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr, assigned)?;
+                }
+
+                *assigned = None;
+            }
+            Stmt::Goto(label) => {
+                let target = self.labels.entry(label).or_default();
+
+                *target = join(target.take(), assigned.take());
+            }
+            // What's after them can still be reached through a label
+            Stmt::Continue | Stmt::Break => *assigned = None,
+            Stmt::For(initializer, condition, increment, block) => {
+                for stmt in *initializer {
+                    self.stmt(stmt, assigned)?;
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition, assigned)?;
+                }
+
+                // The body may not run at all, what it initializes doesn't count after the loop
+                let mut body = assigned.clone();
+
+                self.block(block, &mut body)?;
+                if let Some(increment) = increment {
+                    self.expr(increment, &mut body)?;
+                }
+            }
+            // Reached by falling through and by every `goto` to it, a `goto`
+            // not walked yet is accounted for on the next walk
+            Stmt::Label(label) => {
+                let target = self.labels.get(label).cloned().flatten();
+
+                *assigned = join(assigned.take(), target);
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                self.expr(condition, assigned)?;
+
+                let mut then = assigned.clone();
+
+                self.block(consequence, &mut then)?;
+                if let Some(alternative) = alternative {
+                    self.block(alternative, assigned)?;
+                }
+
+                // A local stays uninitialized if any branch leaves it so
+                *assigned = join(assigned.take(), then);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr<'ir>, assigned: &mut Assigned) -> Result<(), TypeError> {
+        match expr.kind {
+            ExprKind::Binary(BinOp::Assign, lhs, rhs) => {
+                self.expr(rhs, assigned)?;
+
+                match lhs.kind {
+                    ExprKind::Ident(id) => assign(assigned, id),
+                    _ => self.place(lhs, assigned)?,
+                }
+            }
+            // The right hand side isn't always evaluated
+            ExprKind::Binary(BinOp::LogicalAnd | BinOp::LogicalOr, lhs, rhs) => {
+                self.expr(lhs, assigned)?;
+                self.expr(rhs, &mut assigned.clone())?;
+            }
+            // An array decays into a pointer to its first element, which may
+            // be used to initialize it like `&arr`
+            ExprKind::Binary(BinOp::Add | BinOp::Sub, lhs, rhs) => {
+                for expr in [lhs, rhs] {
+                    match expr.kind {
+                        ExprKind::Ident(id) if matches!(expr.ty, Ty::Array(_)) => {
+                            assign(assigned, id)
+                        }
+                        _ => self.expr(expr, assigned)?,
+                    }
+                }
+            }
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Itoa(lhs, rhs) => {
+                self.expr(lhs, assigned)?;
+                self.expr(rhs, assigned)?;
+            }
+            // A pointer can be used to initialize the local, it's not tracked further
+            ExprKind::Unary(UnOp::Address, expr) => match expr.kind {
+                ExprKind::Ident(id) => assign(assigned, id),
+                _ => self.place(expr, assigned)?,
+            },
+            ExprKind::Unary(_, expr)
+            | ExprKind::Field(expr, _)
+            | ExprKind::Cast(expr, _)
+            | ExprKind::Assert(expr, _) => self.expr(expr, assigned)?,
+            ExprKind::Ident(id) => {
+                if let (Some(name), Some(assigned)) = (self.locals.get(&id), assigned) {
+                    if !assigned.contains(&id) {
+                        return Err(TypeError::at(
+                            format!("Use of possibly uninitialized variable `{name}`"),
+                            expr.span,
+                        ));
+                    }
+                }
+            }
+            ExprKind::Struct(fields) => {
+                for (_, expr) in fields {
+                    self.expr(expr, assigned)?;
+                }
+            }
+            ExprKind::Array(items) | ExprKind::Tuple(items) | ExprKind::Print(items) => {
+                for item in items {
+                    self.expr(item, assigned)?;
+                }
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.expr(callee, assigned)?;
+
+                for argument in arguments {
+                    self.expr(argument, assigned)?;
+                }
+            }
+            ExprKind::Block(block) => self.block(&block, assigned)?,
+            ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => (),
+        }
+
+        Ok(())
+    }
+
+    fn noreturn_call(&self, expr: &Expr<'ir>) -> bool {
+        match expr.kind {
+            ExprKind::Call(callee, _) => match callee.kind {
+                ExprKind::Unary(
+                    UnOp::Address,
+                    Expr {
+                        kind: ExprKind::Ident(id),
+                        ..
+                    },
+                ) => self.noreturn.contains(id),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Walks an expression which is written to, only the parts of it computing
+    /// the address are read.
+    fn place(&mut self, expr: &Expr<'ir>, assigned: &mut Assigned) -> Result<(), TypeError> {
+        match expr.kind {
+            ExprKind::Ident(_) => Ok(()),
+            ExprKind::Field(expr, _) => self.place(expr, assigned),
+            _ => self.expr(expr, assigned),
+        }
+    }
+}
+
+fn assign(assigned: &mut Assigned, id: Id) {
+    if let Some(assigned) = assigned {
+        assigned.insert(id);
+    }
+}
+
+/// Locals assigned on both of two paths meeting
+fn join(lhs: Assigned, rhs: Assigned) -> Assigned {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => Some(&lhs & &rhs),
+        (assigned, None) | (None, assigned) => assigned,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        diagnostics::Diagnostics, lexer::Lexer, lowering::Lowering, parser::Parser, Context,
+    };
+    use bumpalo::Bump;
+
+    fn lower(body: &str) {
+        let source =
+            format!("extern noreturn fn exit(status: i32) -> void; fn main() -> u8 {{ {body} }}");
+        let mut diagnostics = Diagnostics::new(&source);
+        let ast = Parser::new(Lexer::new(&source), &mut diagnostics)
+            .parse()
+            .unwrap();
+        let allocator = Bump::new();
+        let mut ctx = Context::new(&allocator);
+
+        Lowering::new(&mut ctx)
+            .lower(ast)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    #[test]
+    fn initialized() {
+        let tests = [
+            "let x: u8; x = 1; return x;",
+            "static let x: u8; return x;",
+            "let x: u8; let p: *u8 = &x; return x;",
+            "let x: u8; if true == true { x = 1; } else { x = 2; } return x;",
+            "let x: u8; if true == true { x = 1; } else { return 0; } return x;",
+            "let x: u8; if true == true { x = 1; } else { exit(1); } return x;",
+            "let x: u8; goto set; read: return x; set: x = 1; goto read;",
+            "let buf: [4]u8; buf[0] = 1; return buf[0];",
+        ];
+
+        for body in tests {
+            lower(body);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn uninitialized() {
+        lower("let x: u8; return x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn initialized_in_one_branch() {
+        lower("let x: u8; if true == true { x = 1; } return x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn read_in_own_assignment() {
+        lower("let x: u8; x = x + 1; return x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn read_after_goto() {
+        lower("let x: u8; goto done; done: return x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn uninitialized_on_one_goto() {
+        lower("let x: u8; if true == true { goto done; } x = 1; done: return x;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of possibly uninitialized variable `x`")]
+    fn uninitialized_on_backward_goto() {
+        lower("let x: u8; goto first; again: return x; first: goto again;");
+    }
+}
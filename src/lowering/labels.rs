@@ -0,0 +1,187 @@
+use crate::{
+    ir::{Block, Expr, ExprKind, Stmt},
+    ty_problem::TypeError,
+};
+use std::collections::HashSet;
+
+/// Labels of a function and the targets of its `goto`s. Labels are visible in
+/// the whole function body, so a `goto` can jump forward as well as backward.
+#[derive(Debug, Default)]
+pub struct Labels<'ir> {
+    labels: HashSet<&'ir str>,
+    gotos: Vec<&'ir str>,
+    /// Whether the statements walked are inside a block expression
+    in_expr: bool,
+}
+
+impl<'ir> Labels<'ir> {
+    pub fn check(block: &Block<'ir>) -> Result<(), TypeError> {
+        let mut labels = Self::default();
+
+        labels.block(block)?;
+
+        match labels
+            .gotos
+            .iter()
+            .find(|label| !labels.labels.contains(*label))
+        {
+            Some(label) => Err(TypeError::new(format!("Use of undeclared label `{label}`"))),
+            None => Ok(()),
+        }
+    }
+
+    fn block(&mut self, block: &Block<'ir>) -> Result<(), TypeError> {
+        for stmt in block.0 {
+            self.stmt(stmt)?;
+        }
+
+        match block.1 {
+            Some(expr) => self.expr(expr),
+            None => Ok(()),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'ir>) -> Result<(), TypeError> {
+        match stmt {
+            Stmt::Label(_) | Stmt::Goto(_) if self.in_expr => {
+                return Err(TypeError::new(
+                    "Labels and `goto` can't be used in a block expression",
+                ));
+            }
+            Stmt::Label(label) => {
+                if !self.labels.insert(label) {
+                    return Err(TypeError::new(format!(
+                        "Label `{label}` is already defined in this function"
+                    )));
+                }
+            }
+            Stmt::Goto(label) => self.gotos.push(label),
+            Stmt::Local(var) => {
+                if let Some(expr) = &var.initializer {
+                    self.expr(expr)?;
+                }
+            }
+            Stmt::Item(_) => (),
+            Stmt::Expr(expr) | Stmt::Return(Some(expr)) => self.expr(expr)?,
+            Stmt::Return(None) | Stmt::Continue | Stmt::Break => (),
+            Stmt::For(initializer, condition, increment, block) => {
+                for stmt in *initializer {
+                    self.stmt(stmt)?;
+                }
+                for expr in condition.iter().chain(increment) {
+                    self.expr(expr)?;
+                }
+
+                self.block(block)?;
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                self.expr(condition)?;
+                self.block(consequence)?;
+
+                if let Some(alternative) = alternative {
+                    self.block(alternative)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Jumping into or out of a block expression would leave the enclosing
+    /// expression half evaluated, so labels and `goto` are rejected there.
+    fn expr(&mut self, expr: &Expr<'ir>) -> Result<(), TypeError> {
+        match expr.kind {
+            ExprKind::Block(block) => {
+                let in_expr = std::mem::replace(&mut self.in_expr, true);
+
+                self.block(&block)?;
+                self.in_expr = in_expr;
+            }
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Itoa(lhs, rhs) => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+            }
+            ExprKind::Unary(_, expr)
+            | ExprKind::Field(expr, _)
+            | ExprKind::Cast(expr, _)
+            | ExprKind::Assert(expr, _) => self.expr(expr)?,
+            ExprKind::Struct(fields) => {
+                for (_, expr) in fields {
+                    self.expr(expr)?;
+                }
+            }
+            ExprKind::Array(items) | ExprKind::Tuple(items) | ExprKind::Print(items) => {
+                for item in items {
+                    self.expr(item)?;
+                }
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.expr(callee)?;
+
+                for argument in arguments {
+                    self.expr(argument)?;
+                }
+            }
+            ExprKind::Ident(_)
+            | ExprKind::Lit(_)
+            | ExprKind::InlineAsm(_)
+            | ExprKind::AlignOf(_)
+            | ExprKind::SizeOf(_) => (),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        diagnostics::Diagnostics, lexer::Lexer, lowering::Lowering, parser::Parser, Context,
+    };
+    use bumpalo::Bump;
+
+    fn lower(body: &str) {
+        let source = format!("fn main() -> u8 {{ {body} }}");
+        let mut diagnostics = Diagnostics::new(&source);
+        let ast = Parser::new(Lexer::new(&source), &mut diagnostics)
+            .parse()
+            .unwrap();
+        let allocator = Bump::new();
+        let mut ctx = Context::new(&allocator);
+
+        Lowering::new(&mut ctx)
+            .lower(ast)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    #[test]
+    fn labels() {
+        let tests = [
+            "goto end; end: return 0;",
+            "start: if true == true { goto start; } return 0;",
+            "if true == true { goto end; } else { a: goto a; } end: return 1;",
+        ];
+
+        for body in tests {
+            lower(body);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of undeclared label `end`")]
+    fn undeclared_label() {
+        lower("goto end; return 0;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Label `a` is already defined in this function")]
+    fn duplicate_label() {
+        lower("a: if true == true { a: return 1; } return 0;");
+    }
+
+    #[test]
+    #[should_panic(expected = "Labels and `goto` can't be used in a block expression")]
+    fn goto_in_block_expr() {
+        lower("a: let x: u8 = { goto a; 1 }; return x;");
+    }
+}
@@ -1,12 +1,20 @@
+mod init;
+mod labels;
 mod scopes;
 
 use crate::{
     ast::{self, BinOp, IntTy, Item, UintTy, UnOp, Variable},
+    diagnostics::Diagnostics,
     ir::{self, Id, OrderedMap, Stmt},
-    ty_problem, Context,
+    lexer::{span::Span, Token},
+    parser::Parser,
+    ty_problem::{self, TypeError},
+    Context,
 };
-use scopes::Scopes;
-use std::collections::HashMap;
+use init::Uninit;
+use labels::Labels;
+use scopes::{Overload, Scopes, Symbol};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct Lowering<'a, 'ir> {
@@ -16,8 +24,27 @@ pub struct Lowering<'a, 'ir> {
     nodes: Vec<ir::Node<'ir>>,
     globals: Vec<ir::Global<'ir>>,
     nodes_map: HashMap<Id, ir::Node<'ir>>,
+    fn_tys: HashMap<Id, &'ir ir::Ty<'ir>>,
+    /// Names shared by several top-level functions, their labels are mangled
+    overloaded: HashSet<String>,
+    /// Types named by `type` items, resolved the first time they're used
+    aliases: HashMap<String, ast::Ty>,
+    /// Aliases being resolved, meeting one of them again means it refers to
+    /// itself
+    resolving: HashSet<String>,
     id: Id,
     ret_ty: Option<&'ir ir::Ty<'ir>>,
+    /// How many loops the statements being lowered are nested in
+    loop_depth: usize,
+    /// Expressions deferred in each enclosing block, the innermost one last
+    defers: Vec<Vec<ir::Expr<'ir>>>,
+    /// How many of the blocks in `defers` are outside each enclosing loop,
+    /// `break` and `continue` run only the expressions deferred inside it
+    loop_defers: Vec<usize>,
+    /// Functions which never return, nothing after a call to one is reachable
+    noreturn: HashSet<Id>,
+    /// Name of the function being lowered if it's `noreturn`
+    noreturn_fn: Option<String>,
 }
 
 impl<'a, 'ir> Lowering<'a, 'ir> {
@@ -29,124 +56,403 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
             nodes: Vec::new(),
             globals: Vec::new(),
             nodes_map: HashMap::new(),
+            fn_tys: HashMap::new(),
+            overloaded: HashSet::new(),
+            aliases: HashMap::new(),
+            resolving: HashSet::new(),
             id: Id::default(),
             ret_ty: None,
+            loop_depth: 0,
+            defers: Vec::new(),
+            loop_defers: Vec::new(),
+            noreturn: HashSet::new(),
+            noreturn_fn: None,
         }
     }
 
-    pub fn lower(mut self, ast: Vec<Item>) {
+    /// Lowers the items of a single file, see `lower_files`.
+    pub fn lower(self, ast: Vec<Item>) -> Result<(), TypeError> {
+        self.lower_files(vec![ast])
+    }
+
+    /// Lowers the items of several files into a single translation unit.
+    /// Every item is declared before any function body or global initializer
+    /// is lowered, so an item can be used before it's declared, in the same
+    /// file or in another one. Errors tell which file they're in.
+    pub fn lower_files(mut self, files: Vec<Vec<Item>>) -> Result<(), TypeError> {
         self.scopes.enter();
 
-        ast.into_iter().for_each(|item| {
-            self.lower_item(item);
-        });
+        let mut items = Vec::new();
+        let mut fn_names = HashSet::new();
+
+        for (file, ast) in files.into_iter().enumerate() {
+            for item in ast {
+                match &item {
+                    Item::Fn { name, .. } if !fn_names.insert(name.clone()) => {
+                        self.overloaded.insert(name.clone());
+                    }
+                    // An alias can be used before it's declared
+                    Item::TypeAlias { name, span, ty } => {
+                        if self.aliases.insert(name.clone(), ty.clone()).is_some() {
+                            return Err(redeclared(name, span).in_file(file));
+                        }
+
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                // Items are numbered in the order they're written in
+                let id = Id {
+                    global_id: items.len(),
+                    node_id: 0,
+                };
+
+                items.push((file, id, item));
+            }
+        }
+
+        self.globals = vec![ir::Global(&[]); items.len()];
+
+        // Types first, the fields and signatures of the other items refer to them
+        for (file, id, item) in &items {
+            if let Item::Struct { name, span, .. } | Item::Union { name, span, .. } = item {
+                self.declare_struct(*id, name, span)
+                    .map_err(|err| err.in_file(*file))?;
+            }
+        }
+        for (file, id, item) in &items {
+            self.declare_item(*id, item)
+                .map_err(|err| err.in_file(*file))?;
+        }
+        for (file, id, item) in &items {
+            if let Item::Struct { name, span, .. } | Item::Union { name, span, .. } = item {
+                if self.embeds(*id, *id, &mut HashSet::new()) {
+                    return Err(TypeError::at(
+                        format!("recursive type `{name}` has infinite size"),
+                        span,
+                    )
+                    .in_file(*file));
+                }
+            }
+        }
+        for (file, id, item) in items {
+            self.id = id;
+            self.lower_item(item).map_err(|err| err.in_file(file))?;
+        }
+
+        // Unused aliases are resolved too, so a cycle is reported anyway
+        let mut aliases = self.aliases.keys().cloned().collect::<Vec<_>>();
+
+        aliases.sort();
+        for name in aliases {
+            self.lower_ty(ast::Ty::Ident(name))?;
+        }
 
         let globals = self.ctx.allocator.alloc_slice_copy(&self.globals);
         self.ctx.ir.set_globals(globals);
+
+        Ok(())
     }
 
-    pub fn lower_item(&mut self, item: Item) -> Option<ir::Item<'ir>> {
-        match item {
-            Item::Struct { name, fields } => {
-                let ty = self.ctx.allocator.alloc(ir::Ty::Struct(self.id));
-                self.types.insert(ast::Ty::Ident(name), ty);
+    /// Makes the name of a struct or a union a type, its fields are lowered
+    /// once every type is declared.
+    fn declare_struct(&mut self, id: Id, name: &str, span: &Span) -> Result<(), TypeError> {
+        let ty = self
+            .ctx
+            .allocator
+            .alloc(ir::Ty::Struct(id, self.ctx.allocator.alloc_str(name)));
 
+        if self.aliases.contains_key(name)
+            || self
+                .types
+                .insert(ast::Ty::Ident(name.to_string()), ty)
+                .is_some()
+        {
+            return Err(redeclared(name, span));
+        }
+
+        Ok(())
+    }
+
+    /// Declares everything about the item other code can refer to: the fields
+    /// of a struct, the signature of a function and the type of a global.
+    fn declare_item(&mut self, id: Id, item: &Item) -> Result<(), TypeError> {
+        match item {
+            Item::Struct { fields, .. } | Item::Union { fields, .. } => {
                 let fields = fields
-                    .into_iter()
-                    .map(|(field, ty)| (&*self.ctx.allocator.alloc_str(&field), self.lower_ty(ty)))
-                    .collect::<Vec<_>>();
+                    .iter()
+                    .map(|(field, ty)| {
+                        Ok((
+                            &*self.ctx.allocator.alloc_str(field),
+                            self.lower_ty(ty.clone())?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, TypeError>>()?;
                 let fields = self.ctx.allocator.alloc_slice_copy(&fields);
+                let item = match item {
+                    Item::Struct { packed, .. } => ir::Item::Struct(fields, *packed),
+                    _ => ir::Item::Union(fields),
+                };
 
-                self.globals.push(ir::Global(
+                self.globals[id.global_id] =
+                    ir::Global(self.ctx.allocator.alloc([ir::Node::Item(item)]));
+            }
+            Item::Fn {
+                noreturn,
+                ret_ty,
+                name,
+                span,
+                params,
+                variadic,
+                ..
+            } => {
+                let ret_ty = self.lower_ty(ret_ty.clone())?;
+                let param_tys = params
+                    .iter()
+                    .map(|(_, ty)| self.lower_ty(ty.clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let param_tys = &*self.ctx.allocator.alloc_slice_copy(&param_tys);
+
+                self.scopes
+                    .insert_fn(name.clone(), id, param_tys)
+                    .map_err(|err| TypeError::at(err.to_string(), span))?;
+                self.fn_tys.insert(
+                    id,
                     self.ctx
                         .allocator
-                        .alloc([ir::Node::Item(ir::Item::Struct(fields))]),
-                ));
-                self.id.global_id += 1;
+                        .alloc(ir::Ty::Fn(param_tys, ret_ty, *variadic)),
+                );
+                if *noreturn {
+                    self.noreturn.insert(id);
+                }
+            }
+            Item::Global(var) => {
+                let ty = self.lower_ty(var.ty.clone())?;
+                // Only the type is known until the initializer is lowered
+                let node =
+                    ir::Node::Item(ir::Item::Global(self.ctx.allocator.alloc(ir::Variable {
+                        id,
+                        name: self.ctx.allocator.alloc_str(&var.name),
+                        ty,
+                        initializer: None,
+                        static_: var.static_,
+                        thread_local: var.thread_local,
+                        pub_: var.pub_,
+                    })));
 
-                None
+                self.scopes
+                    .insert_symbol(var.name.clone(), id)
+                    .map_err(|err| TypeError::at(err.to_string(), &var.span))?;
+                self.nodes_map.insert(id, node);
+            }
+            Item::TypeAlias { .. } => (),
+        }
+
+        Ok(())
+    }
+
+    /// Whether a value of struct `ty` contains struct `id`, directly or in the
+    /// fields of the structs it contains. A struct may point to itself, but
+    /// containing itself by value would make it infinitely large.
+    fn embeds(&self, ty: Id, id: Id, seen: &mut HashSet<Id>) -> bool {
+        if !seen.insert(ty) {
+            return false;
+        }
+
+        let ir::Node::Item(ir::Item::Struct(fields, _) | ir::Item::Union(fields)) =
+            self.globals[ty.global_id].0[ty.node_id]
+        else {
+            unreachable!();
+        };
+
+        fields.iter().any(|(_, field)| {
+            let mut field = *field;
+
+            while let ir::Ty::Array(ir::TyArray { ty, .. }) = field {
+                field = ty;
+            }
+
+            match field {
+                ir::Ty::Struct(field, _) => *field == id || self.embeds(*field, id, seen),
+                _ => false,
             }
+        })
+    }
+
+    /// Lowers the body of a function or the initializer of a global declared
+    /// by `declare_item`.
+    pub fn lower_item(&mut self, item: Item) -> Result<Option<ir::Item<'ir>>, TypeError> {
+        Ok(match item {
+            Item::Struct { .. } | Item::Union { .. } | Item::TypeAlias { .. } => None,
             Item::Fn {
-                ret_ty,
+                pub_,
+                naked,
+                noreturn,
                 name,
                 params,
                 block,
+                ..
             } => {
+                let fn_id = self.id;
+                let ir::Ty::Fn(param_tys, ret_ty, _) = self.fn_tys[&fn_id] else {
+                    unreachable!();
+                };
+
+                // Without a stack frame only inline assembly can be used safely
+                if naked {
+                    let Some(block) = &block else {
+                        return Err(TypeError::new(format!(
+                            "`extern` function `{name}` can't be naked"
+                        )));
+                    };
+
+                    if !block.stmts.iter().all(|stmt| {
+                        matches!(
+                            stmt,
+                            ast::Stmt::Expr(ast::Expr {
+                                kind: ast::ExprKind::InlineAsm(_),
+                                ..
+                            })
+                        )
+                    }) {
+                        return Err(TypeError::new(format!(
+                            "Naked function `{name}` can only contain inline assembly"
+                        )));
+                    }
+                }
+
                 self.id.node_id = 1;
                 self.scopes.enter();
-
-                let ret_ty = self.lower_ty(ret_ty);
                 self.ret_ty = Some(ret_ty);
 
-                let stmts = if let Some(block) = block {
-                    block
-                        .stmts
-                        .into_iter()
-                        .map(|stmt| self.lower_stmt(stmt))
-                        .collect()
-                } else {
-                    Vec::new()
+                let mut ir_params = Vec::new();
+
+                for ((name, _), ty) in params.into_iter().zip(*param_tys) {
+                    let param = &*self.ctx.allocator.alloc(ir::Variable {
+                        id: self.id,
+                        name: self.ctx.allocator.alloc_str(&name),
+                        ty,
+                        initializer: None,
+                        static_: false,
+                        thread_local: false,
+                        pub_: false,
+                    });
+                    let node = ir::Node::Param(param);
+
+                    self.scopes.insert_param(name, self.id)?;
+                    self.nodes_map.insert(self.id, node);
+                    self.nodes.push(node);
+                    self.id.node_id += 1;
+                    ir_params.push(param);
+                }
+
+                // A nested function doesn't run the expressions deferred around it
+                let defers = std::mem::take(&mut self.defers);
+                let noreturn_fn =
+                    std::mem::replace(&mut self.noreturn_fn, noreturn.then(|| name.clone()));
+                let block = match block {
+                    Some(block) => Some(ir::Block(self.lower_stmts(block.stmts)?, None)),
+                    None => None,
                 };
+                self.defers = defers;
+                self.noreturn_fn = noreturn_fn;
+
+                if let Some(block) = &block {
+                    Uninit::check(block, &self.noreturn)?;
+                    Labels::check(block)?;
+                }
 
                 self.ret_ty = None;
                 self.scopes.leave();
 
-                let params: Vec<&'ir ir::Ty<'ir>> = params
-                    .into_iter()
-                    .map(|(_, ty)| self.lower_ty(ty))
-                    .collect();
                 let signature = ir::Signature {
-                    params: self.ctx.allocator.alloc_slice_copy(&params),
+                    params: param_tys,
                     ret_ty,
                 };
 
                 self.nodes.insert(
                     0,
                     ir::Node::Item(ir::Item::Fn(self.ctx.allocator.alloc(ir::ItemFn {
-                        id: Id {
-                            global_id: self.id.global_id,
-                            node_id: 0,
-                        },
-                        name: self.ctx.allocator.alloc_str(&name),
+                        id: fn_id,
+                        name: self.fn_label(name, param_tys, block.is_some()),
+                        params: self.ctx.allocator.alloc_slice_copy(&ir_params),
                         signature,
-                        block: ir::Block(self.ctx.allocator.alloc_slice_copy(&stmts)),
+                        pub_,
+                        naked,
+                        noreturn,
+                        block,
                     }))),
                 );
 
-                self.globals
-                    .push(ir::Global(self.ctx.allocator.alloc_slice_copy(&self.nodes)));
+                self.globals[fn_id.global_id] =
+                    ir::Global(self.ctx.allocator.alloc_slice_copy(&self.nodes));
                 self.nodes.clear();
-                self.id.global_id += 1;
-                self.id.node_id = 0;
 
                 None
             }
             Item::Global(var) => {
-                let name = var.name.clone();
-                let ir_var = self.lower_var_decl(var);
+                let ir::Node::Item(ir::Item::Global(declared)) = self.nodes_map[&self.id] else {
+                    unreachable!();
+                };
+                let ir_var = self.lower_var(var, declared.ty)?;
                 let node = ir::Node::Item(ir::Item::Global(ir_var));
 
-                self.scopes.insert_symbol(name, self.id);
                 self.nodes_map.insert(self.id, node);
-
-                self.globals
-                    .push(ir::Global(self.ctx.allocator.alloc_slice_copy(&[node])));
-                self.id.global_id += 1;
+                self.globals[self.id.global_id] =
+                    ir::Global(self.ctx.allocator.alloc_slice_copy(&[node]));
 
                 Some(ir::Item::Global(ir_var))
             }
+        })
+    }
+
+    /// Overloads of a function need distinct labels, so the types of their
+    /// parameters are appended to the name, like `abs.i32`. A `.` can't be part
+    /// of an identifier, so they can't clash with the label of another function.
+    /// `extern` functions keep the name they're defined with elsewhere.
+    fn fn_label(&self, name: String, params: &[&ir::Ty], defined: bool) -> &'ir str {
+        if !defined || !self.overloaded.contains(&name) {
+            return self.ctx.allocator.alloc_str(&name);
         }
+
+        let label = params
+            .iter()
+            .fold(name, |label, ty| format!("{label}.{}", ty.mangle()));
+
+        self.ctx.allocator.alloc_str(&label)
     }
 
-    fn lower_var_decl(&mut self, variable: Variable) -> &'ir ir::Variable<'ir> {
-        let ty = self.lower_ty(variable.ty);
+    fn declare_symbol(&mut self, name: String, id: Id) -> Result<(), TypeError> {
+        Ok(self.scopes.insert_symbol(name, id)?)
+    }
+
+    fn lower_var_decl(&mut self, variable: Variable) -> Result<&'ir ir::Variable<'ir>, TypeError> {
+        let ty = self.lower_ty(variable.ty.clone())?;
+
+        self.lower_var(variable, ty)
+    }
+
+    /// Lowers the initializer of a variable whose type is already lowered.
+    fn lower_var(
+        &mut self,
+        variable: Variable,
+        ty: &'ir ir::Ty<'ir>,
+    ) -> Result<&'ir ir::Variable<'ir>, TypeError> {
+        let inferred = variable.ty == ast::Ty::Infer;
 
         let initializer = if let Some(expr) = variable.value {
-            let expr = self.lower_expr(expr);
+            let expr = self.lower_expr(expr)?;
             let let_ty_var_id = self.tys_ty_var_id(ty);
             let expr_ty_var_id = self.tys_ty_var_id(expr.ty);
 
             self.ctx.ty_problem.eq(let_ty_var_id, expr_ty_var_id);
+            // Like in C, an integer literal nothing else constrains is an `int`
+            if inferred {
+                self.ctx
+                    .ty_problem
+                    .fallback(let_ty_var_id, &ir::Ty::Int(IntTy::I32));
+            }
 
             Some(expr)
         } else {
@@ -158,62 +464,371 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
             name: self.ctx.allocator.alloc_str(&variable.name),
             ty,
             initializer,
+            static_: variable.static_,
+            thread_local: variable.thread_local,
+            pub_: variable.pub_,
         });
 
-        ir_variable
+        Ok(ir_variable)
+    }
+
+    /// Lowers the statements of a block. Expressions deferred in it are copied
+    /// to every exit of the block: before a `return`, `break` or `continue`
+    /// and at its end when control falls off it. `goto` doesn't run them.
+    fn lower_stmts(&mut self, stmts: Vec<ast::Stmt>) -> Result<&'ir [ir::Stmt<'ir>], TypeError> {
+        let mut ir_stmts = Vec::new();
+
+        self.defers.push(Vec::new());
+
+        for stmt in stmts {
+            match stmt {
+                ast::Stmt::Destructure { names, value } => {
+                    ir_stmts.extend(self.lower_destructure(names, value)?)
+                }
+                ast::Stmt::Defer(expr) => {
+                    let expr = self.lower_expr(expr)?;
+
+                    self.defers.last_mut().unwrap().push(expr);
+                }
+                ast::Stmt::Return(expr) if self.defers.iter().any(|defers| !defers.is_empty()) => {
+                    ir_stmts.extend(self.lower_deferred_return(expr)?)
+                }
+                stmt @ (ast::Stmt::Break(_) | ast::Stmt::Continue(_)) => {
+                    let stmt = self.lower_stmt(stmt)?;
+                    let depth = self.loop_defers.last().copied().unwrap_or_default();
+
+                    ir_stmts.extend(self.deferred(depth));
+                    ir_stmts.push(stmt);
+                }
+                stmt => ir_stmts.push(self.lower_stmt(stmt)?),
+            }
+        }
+
+        let defers = self.defers.pop().unwrap();
+
+        if !matches!(
+            ir_stmts.last(),
+            Some(ir::Stmt::Return(_) | ir::Stmt::Break | ir::Stmt::Continue | ir::Stmt::Goto(_))
+        ) {
+            ir_stmts.extend(defers.into_iter().rev().map(ir::Stmt::Expr));
+        }
+
+        Ok(self.ctx.allocator.alloc_slice_copy(&ir_stmts))
+    }
+
+    /// Statements running the expressions deferred in the blocks from `depth`
+    /// inward, the last deferred first
+    fn deferred(&self, depth: usize) -> Vec<ir::Stmt<'ir>> {
+        self.defers[depth..]
+            .iter()
+            .rev()
+            .flat_map(|defers| defers.iter().rev())
+            .map(|&expr| ir::Stmt::Expr(expr))
+            .collect()
+    }
+
+    /// Deferred expressions run after the value of `return` is evaluated, so
+    /// it's kept in a hidden local meanwhile.
+    fn lower_deferred_return(
+        &mut self,
+        expr: Option<ast::Expr>,
+    ) -> Result<Vec<ir::Stmt<'ir>>, TypeError> {
+        let ir::Stmt::Return(expr) = self.lower_stmt(ast::Stmt::Return(expr))? else {
+            unreachable!();
+        };
+        let mut stmts = Vec::new();
+        let expr = match expr {
+            Some(expr) => {
+                let ty = self.ret_ty.unwrap();
+                let var = &*self.ctx.allocator.alloc(ir::Variable {
+                    id: self.id,
+                    name: "(return)",
+                    ty,
+                    initializer: Some(expr),
+                    static_: false,
+                    thread_local: false,
+                    pub_: false,
+                });
+
+                self.declare_local(var, None)?;
+                stmts.push(ir::Stmt::Local(var));
+
+                Some(ir::Expr {
+                    ty,
+                    kind: ir::ExprKind::Ident(var.id),
+                    span: expr.span,
+                })
+            }
+            None => None,
+        };
+
+        stmts.extend(self.deferred(0));
+        stmts.push(ir::Stmt::Return(expr));
+
+        Ok(stmts)
     }
 
-    fn lower_stmt(&mut self, stmt: ast::Stmt) -> ir::Stmt<'ir> {
-        match stmt {
+    fn lower_stmt(&mut self, stmt: ast::Stmt) -> Result<ir::Stmt<'ir>, TypeError> {
+        Ok(match stmt {
             ast::Stmt::Local(var) => {
                 let name = var.name.clone();
-                let ir_var = self.lower_var_decl(var);
-                let node = ir::Node::Stmt(ir::Stmt::Local(ir_var));
+                let ir_var = self.lower_var_decl(var)?;
 
-                self.scopes.insert_symbol(name, self.id);
-                self.nodes_map.insert(self.id, node);
-
-                self.nodes.push(node);
-                self.id.node_id += 1;
+                self.declare_local(ir_var, Some(name))?;
 
                 ir::Stmt::Local(ir_var)
             }
-            ast::Stmt::Item(item) => ir::Stmt::Item(self.lower_item(item).unwrap()),
-            ast::Stmt::Expr(expr) => ir::Stmt::Expr(self.lower_expr(expr)),
+            ast::Stmt::Item(Item::Fn { name, span, .. }) => {
+                return Err(TypeError::at(
+                    format!("`{name}` can only be declared at the top level"),
+                    &span,
+                ));
+            }
+            ast::Stmt::Item(_) => unreachable!("Only functions are parsed in blocks"),
+            ast::Stmt::Expr(expr) => ir::Stmt::Expr(self.lower_expr(expr)?),
             ast::Stmt::Return(expr) => {
-                let expr = expr.map(|expr| {
-                    let expr = self.lower_expr(expr);
-                    let expr_ty_var_id = self.tys_ty_var_id(expr.ty);
-                    let ret_ty_var = self.tys_ty_var_id(self.ret_ty.unwrap());
+                if let Some(name) = &self.noreturn_fn {
+                    return Err(TypeError::new(format!(
+                        "`noreturn` function `{name}` can't return"
+                    )));
+                }
 
-                    self.ctx.ty_problem.eq(expr_ty_var_id, ret_ty_var);
+                let expr = match expr {
+                    Some(expr) => {
+                        let expr = self.lower_expr(expr)?;
+                        let expr_ty_var_id = self.tys_ty_var_id(expr.ty);
+                        let ret_ty_var = self.tys_ty_var_id(self.ret_ty.unwrap());
 
-                    expr
-                });
+                        self.ctx.ty_problem.eq(expr_ty_var_id, ret_ty_var);
+
+                        Some(expr)
+                    }
+                    None => None,
+                };
 
                 ir::Stmt::Return(expr)
             }
-            _ => todo!(),
+            ast::Stmt::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let condition = self.lower_condition(condition)?;
+                let consequence = self.lower_block(consequence)?;
+                let alternative = match alternative {
+                    Some(alternative) => Some(self.lower_block(alternative)?),
+                    None => None,
+                };
+
+                ir::Stmt::If(condition, consequence, alternative)
+            }
+            ast::Stmt::While { condition, block } => {
+                let condition = self.lower_condition(condition)?;
+
+                ir::Stmt::For(&[], Some(condition), None, self.lower_loop_block(block)?)
+            }
+            ast::Stmt::For {
+                initializer,
+                condition,
+                increment,
+                block,
+            } => {
+                // Locals declared by the initializer are visible only in the loop
+                self.scopes.enter();
+
+                let initializer =
+                    self.lower_stmts(initializer.into_iter().map(|stmt| *stmt).collect())?;
+                let condition = match condition {
+                    Some(condition) => Some(self.lower_condition(condition)?),
+                    None => None,
+                };
+                let increment = match increment {
+                    Some(increment) => Some(self.lower_expr(increment)?),
+                    None => None,
+                };
+                let block = self.lower_loop_block(block)?;
+
+                self.scopes.leave();
+
+                ir::Stmt::For(initializer, condition, increment, block)
+            }
+            ast::Stmt::Continue(span) if self.loop_depth == 0 => {
+                return Err(TypeError::at("`continue` outside of a loop", &span));
+            }
+            ast::Stmt::Continue(_) => ir::Stmt::Continue,
+            ast::Stmt::Break(span) if self.loop_depth == 0 => {
+                return Err(TypeError::at("`break` outside of a loop", &span));
+            }
+            ast::Stmt::Break(_) => ir::Stmt::Break,
+            ast::Stmt::Label(label) => ir::Stmt::Label(self.ctx.allocator.alloc_str(&label)),
+            ast::Stmt::Goto(label) => ir::Stmt::Goto(self.ctx.allocator.alloc_str(&label)),
+            ast::Stmt::Destructure { .. } | ast::Stmt::Defer(_) => {
+                unreachable!("Lowered by `lower_stmts`")
+            }
+        })
+    }
+
+    /// `let (a, b) = value;` is lowered into a hidden local holding the tuple
+    /// and a local for each name, initialized with the element at its index.
+    /// The arity is checked when the type of the hidden local, a tuple of as
+    /// many elements as there are names, is unified with the one of the value.
+    fn lower_destructure(
+        &mut self,
+        names: Vec<(String, Span)>,
+        value: ast::Expr,
+    ) -> Result<Vec<ir::Stmt<'ir>>, TypeError> {
+        let value = self.lower_expr(value)?;
+        let elem_tys = names
+            .iter()
+            .map(|_| self.lower_ty(ast::Ty::Infer))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ty = &*self.ctx.allocator.alloc(ir::Ty::Tuple(
+            self.ctx.allocator.alloc_slice_copy(&elem_tys),
+        ));
+        let ty_var = self.tys_ty_var_id(ty);
+        let value_ty_var = self.tys_ty_var_id(value.ty);
+
+        self.ctx.ty_problem.eq(ty_var, value_ty_var);
+
+        let tuple_name = format!(
+            "({})",
+            names
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let tuple = &*self.ctx.allocator.alloc(ir::Variable {
+            id: self.id,
+            name: self.ctx.allocator.alloc_str(&tuple_name),
+            ty,
+            initializer: Some(value),
+            static_: false,
+            thread_local: false,
+            pub_: false,
+        });
+        let tuple_expr = &*self.ctx.allocator.alloc(ir::Expr {
+            ty,
+            kind: ir::ExprKind::Ident(tuple.id),
+            span: value.span,
+        });
+        let mut stmts = vec![ir::Stmt::Local(tuple)];
+
+        self.declare_local(tuple, None)?;
+
+        for (i, ((name, _), elem_ty)) in names.into_iter().zip(elem_tys).enumerate() {
+            let elem_ty_var = self.tys_ty_var_id(elem_ty);
+            let var = &*self.ctx.allocator.alloc(ir::Variable {
+                id: self.id,
+                name: self.ctx.allocator.alloc_str(&name),
+                ty: elem_ty,
+                initializer: Some(ir::Expr {
+                    span: value.span,
+                    ty: elem_ty,
+                    kind: ir::ExprKind::Field(
+                        tuple_expr,
+                        self.ctx.allocator.alloc_str(&i.to_string()),
+                    ),
+                }),
+                static_: false,
+                thread_local: false,
+                pub_: false,
+            });
+
+            // Like an inferred `let`, an integer literal nothing else constrains is an `int`
+            self.ctx
+                .ty_problem
+                .fallback(elem_ty_var, &ir::Ty::Int(IntTy::I32));
+            self.declare_local(var, Some(name))?;
+            stmts.push(ir::Stmt::Local(var));
         }
+
+        Ok(stmts)
     }
 
-    fn lower_expr(&mut self, expr: ast::Expr) -> ir::Expr<'ir> {
-        match expr.kind {
+    /// Registers the local, it can be referred to by `name` if it has one.
+    fn declare_local(
+        &mut self,
+        var: &'ir ir::Variable<'ir>,
+        name: Option<String>,
+    ) -> Result<(), TypeError> {
+        let node = ir::Node::Stmt(ir::Stmt::Local(var));
+
+        if let Some(name) = name {
+            self.declare_symbol(name, self.id)?;
+        }
+        self.nodes_map.insert(self.id, node);
+
+        self.nodes.push(node);
+        self.id.node_id += 1;
+
+        Ok(())
+    }
+
+    /// Lowers the condition of an `if` or a loop, which has to be a `bool`.
+    fn lower_condition(&mut self, condition: ast::Expr) -> Result<ir::Expr<'ir>, TypeError> {
+        let condition = self.lower_expr(condition)?;
+        let condition_ty_var = self.tys_ty_var_id(condition.ty);
+        let bool_ty_var = self.tys_ty_var_id(&ir::Ty::Bool);
+
+        self.ctx.ty_problem.eq(condition_ty_var, bool_ty_var);
+
+        Ok(condition)
+    }
+
+    fn lower_loop_block(&mut self, block: ast::Block) -> Result<ir::Block<'ir>, TypeError> {
+        self.loop_depth += 1;
+        self.loop_defers.push(self.defers.len());
+        let block = self.lower_block(block);
+        self.loop_defers.pop();
+        self.loop_depth -= 1;
+
+        block
+    }
+
+    fn lower_block(&mut self, block: ast::Block) -> Result<ir::Block<'ir>, TypeError> {
+        self.scopes.enter();
+
+        let stmts = self.lower_stmts(block.stmts)?;
+        let expr = match block.expr {
+            Some(expr) => Some(&*self.ctx.allocator.alloc(self.lower_expr(*expr)?)),
+            None => None,
+        };
+
+        self.scopes.leave();
+
+        Ok(ir::Block(stmts, expr))
+    }
+
+    fn lower_expr(&mut self, expr: ast::Expr) -> Result<ir::Expr<'ir>, TypeError> {
+        let span = &*self.ctx.allocator.alloc(expr.span.clone());
+
+        Ok(match expr.kind {
             ast::ExprKind::Binary {
                 op,
                 ref left,
                 ref right,
             } => {
+                // Only a constant divisor is known to be zero, others fault at runtime
+                if matches!(op, BinOp::Div | BinOp::Rem) && right.const_eval() == Ok(0) {
+                    let message = match op {
+                        BinOp::Div => "Division by zero",
+                        _ => "Remainder of a division by zero",
+                    };
+
+                    return Err(TypeError::at(message, span));
+                }
+
                 // TODO: remove clones
-                let lhs = self.lower_expr(*left.clone());
-                let rhs = self.lower_expr(*right.clone());
+                let lhs = self.lower_expr(*left.clone())?;
+                let rhs = self.lower_expr(*right.clone())?;
 
                 let lhs_ty_var_id = self.tys_ty_var_id(lhs.ty);
                 let rhs_ty_var_id = self.tys_ty_var_id(rhs.ty);
 
                 let ty = match op {
                     BinOp::Add => {
-                        let ty = self.expr_ty(&expr);
+                        let ty = self.expr_ty(&expr)?;
                         let expr = self.tys_ty_var_id(ty);
 
                         self.ctx
@@ -223,7 +838,7 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                         ty
                     }
                     BinOp::Sub => {
-                        let ty = self.expr_ty(&expr);
+                        let ty = self.expr_ty(&expr)?;
                         let expr = self.tys_ty_var_id(ty);
 
                         self.ctx
@@ -232,6 +847,16 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
 
                         ty
                     }
+                    BinOp::Equal
+                    | BinOp::NotEqual
+                    | BinOp::LessThan
+                    | BinOp::LessEqual
+                    | BinOp::GreaterThan
+                    | BinOp::GreaterEqual => {
+                        self.ctx.ty_problem.cmp(lhs_ty_var_id, rhs_ty_var_id);
+
+                        &ir::Ty::Bool
+                    }
                     _ => {
                         self.ctx.ty_problem.eq(lhs_ty_var_id, rhs_ty_var_id);
 
@@ -240,6 +865,7 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                 };
 
                 ir::Expr {
+                    span,
                     ty,
                     kind: ir::ExprKind::Binary(
                         op,
@@ -249,19 +875,19 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                 }
             }
             ast::ExprKind::Ident(ref ident) => {
-                let id = self.scopes.get_symbol(ident).unwrap();
-                let ty = self.expr_ty(&expr);
+                let id = self.lookup(ident, span)?;
+                let ty = self.expr_ty(&expr)?;
 
-                ir::Expr {
-                    ty,
-                    kind: ir::ExprKind::Ident(id),
-                }
+                self.ident(id, ty, span)
             }
+            ast::ExprKind::Lit(ast::ExprLit::UInt(lit)) => self.int_lit_expr(lit, false, span),
             ast::ExprKind::Lit(ref lit) => {
-                let ty = self.expr_ty(&expr);
+                let ty = self.expr_ty(&expr)?;
                 let kind = match lit {
                     ast::ExprLit::Int(lit) => ir::ExprKind::Lit(ir::ExprLit::Int(*lit)),
-                    ast::ExprLit::UInt(lit) => ir::ExprKind::Lit(ir::ExprLit::UInt(*lit)),
+                    ast::ExprLit::UInt(lit) | ast::ExprLit::Suffixed(lit, _) => {
+                        ir::ExprKind::Lit(ir::ExprLit::UInt(*lit))
+                    }
                     ast::ExprLit::Bool(lit) => ir::ExprKind::Lit(ir::ExprLit::Bool(*lit)),
                     ast::ExprLit::String(lit) => {
                         ir::ExprKind::Lit(ir::ExprLit::String(self.ctx.allocator.alloc_str(&lit)))
@@ -269,11 +895,24 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                     ast::ExprLit::Null => ir::ExprKind::Lit(ir::ExprLit::Null),
                 };
 
-                ir::Expr { ty, kind }
+                ir::Expr { ty, kind, span }
             }
             ast::ExprKind::Unary { op, expr } => {
-                let ir_expr = self.lower_expr(*expr);
+                let ir_expr = match (op, &expr.kind) {
+                    (UnOp::Negative, ast::ExprKind::Lit(ast::ExprLit::UInt(lit))) => {
+                        self.int_lit_expr(*lit, true, span)
+                    }
+                    _ => self.lower_expr(*expr)?,
+                };
                 let ty = match op {
+                    // A function name has already decayed into its address
+                    UnOp::Address if ir_expr.fn_ptr() => return Ok(ir_expr),
+                    UnOp::Address if !ir_expr.lvalue() => {
+                        return Err(TypeError::at(
+                            "Can't take the address of a temporary value",
+                            span,
+                        ));
+                    }
                     UnOp::Address => self.ctx.allocator.alloc(ir::Ty::Ptr(ir_expr.ty)),
                     UnOp::Deref => {
                         let deref = &*self
@@ -290,59 +929,80 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
 
                         deref
                     }
-                    _ => ir_expr.ty,
+                    UnOp::LogicalNot => {
+                        let expr_ty_var_id = self.tys_ty_var_id(ir_expr.ty);
+                        let bool_ty_var = self.tys_ty_var_id(&ir::Ty::Bool);
+
+                        self.ctx.ty_problem.eq(expr_ty_var_id, bool_ty_var);
+
+                        &ir::Ty::Bool
+                    }
+                    UnOp::BitwiseNot => {
+                        let expr_ty_var_id = self.tys_ty_var_id(ir_expr.ty);
+
+                        self.ctx.ty_problem.int(expr_ty_var_id);
+
+                        ir_expr.ty
+                    }
+                    UnOp::Negative => ir_expr.ty,
                 };
 
                 ir::Expr {
+                    span,
                     ty,
                     kind: ir::ExprKind::Unary(op, self.ctx.allocator.alloc(ir_expr)),
                 }
             }
             ast::ExprKind::Struct { name, fields } => {
-                let ty = self.lower_ty(ast::Ty::Ident(name));
-                let ir::Ty::Struct(id) = ty else {
+                let ty = self.lower_ty(ast::Ty::Ident(name.clone()))?;
+                let ir::Ty::Struct(id, name) = ty else {
+                    return Err(TypeError::at(format!("`{name}` isn't a struct"), span));
+                };
+                let ir::Node::Item(ir::Item::Struct(item_fields, _) | ir::Item::Union(item_fields)) =
+                    self.globals[id.global_id].0[id.node_id]
+                else {
                     unreachable!();
                 };
-                let fields = &*self.ctx.allocator.alloc_slice_copy(
-                    fields
-                        .into_iter()
-                        .map(|(field, expr)| {
-                            let expr = self.lower_expr(expr);
-
-                            match self.globals[id.global_id].0[id.node_id] {
-                                ir::Node::Item(item) => match item {
-                                    ir::Item::Struct(fields) => {
-                                        match OrderedMap::get(&fields, &field.as_str()) {
-                                            Some(ty) => {
-                                                let expr_ty_var_id = self.tys_ty_var_id(expr.ty);
-                                                let field_ty_var_id = self.tys_ty_var_id(*ty);
-
-                                                self.ctx
-                                                    .ty_problem
-                                                    .eq(expr_ty_var_id, field_ty_var_id);
-                                            }
-                                            None => unreachable!(),
-                                        }
-                                    }
-                                    _ => unreachable!(),
-                                },
-                                _ => unreachable!(),
-                            };
-
-                            (&*self.ctx.allocator.alloc_str(&field), expr)
-                        })
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                );
+
+                // The fields of a union overlap, only one of them can be set
+                if let ir::Node::Item(ir::Item::Union(_)) = self.globals[id.global_id].0[id.node_id]
+                {
+                    if fields.len() > 1 {
+                        return Err(TypeError::at(
+                            format!("Union expression of `{name}` sets more than one field"),
+                            span,
+                        ));
+                    }
+                }
+
+                let mut ir_fields = Vec::new();
+
+                for (field, expr) in fields {
+                    let expr = self.lower_expr(expr)?;
+                    let Some(ty) = OrderedMap::get(&item_fields, &field.as_str()) else {
+                        return Err(TypeError::at(
+                            format!("Struct has no field `{field}`"),
+                            span,
+                        ));
+                    };
+                    let expr_ty_var_id = self.tys_ty_var_id(expr.ty);
+                    let field_ty_var_id = self.tys_ty_var_id(*ty);
+
+                    self.ctx.ty_problem.eq(expr_ty_var_id, field_ty_var_id);
+                    ir_fields.push((&*self.ctx.allocator.alloc_str(&field), expr));
+                }
+
+                let fields = &*self.ctx.allocator.alloc_slice_copy(&ir_fields);
 
                 ir::Expr {
+                    span,
                     ty,
                     kind: ir::ExprKind::Struct(fields),
                 }
             }
             ast::ExprKind::Field { expr, field } => {
-                let expr = self.lower_expr(*expr);
-                let ty = self.lower_ty(ast::Ty::Infer);
+                let expr = self.lower_expr(*expr)?;
+                let ty = self.lower_ty(ast::Ty::Infer)?;
                 let field = self.ctx.allocator.alloc_str(field.as_str());
                 let expr_ty_var = self.tys_ty_var_id(expr.ty);
                 let field_ty_var = self.tys_ty_var_id(ty);
@@ -350,26 +1010,467 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                 self.ctx.ty_problem.field(expr_ty_var, field_ty_var, field);
 
                 ir::Expr {
+                    span,
                     ty,
                     kind: ir::ExprKind::Field(self.ctx.allocator.alloc(expr), field),
                 }
             }
             ast::ExprKind::Cast { expr, ty } => {
-                let expr = self.lower_expr(*expr);
-                let ty = self.lower_ty(ty);
+                let expr = self.lower_expr(*expr)?;
+                let ty = self.lower_ty(ty)?;
+                let expr_ty_var = self.tys_ty_var_id(expr.ty);
+                let ty_var = self.tys_ty_var_id(ty);
+
+                self.ctx.ty_problem.cast(expr_ty_var, ty_var);
 
                 ir::Expr {
+                    span,
                     ty,
                     kind: ir::ExprKind::Cast(self.ctx.allocator.alloc(expr), ty),
                 }
             }
+            ast::ExprKind::Array(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| self.lower_expr(item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let elem_ty = self.lower_ty(ast::Ty::Infer)?;
+                let elem_ty_var = self.tys_ty_var_id(elem_ty);
+                let items_ty_vars = items
+                    .iter()
+                    .map(|item| self.tys_ty_var_id(item.ty))
+                    .collect();
+
+                self.ctx.ty_problem.array(elem_ty_var, items_ty_vars);
+
+                ir::Expr {
+                    span,
+                    ty: self.ctx.allocator.alloc(ir::Ty::Array(ir::TyArray {
+                        ty: elem_ty,
+                        len: items.len(),
+                    })),
+                    kind: ir::ExprKind::Array(self.ctx.allocator.alloc_slice_copy(&items)),
+                }
+            }
+            ast::ExprKind::Tuple(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| self.lower_expr(item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let tys = items.iter().map(|item| item.ty).collect::<Vec<_>>();
+
+                ir::Expr {
+                    span,
+                    ty: self
+                        .ctx
+                        .allocator
+                        .alloc(ir::Ty::Tuple(self.ctx.allocator.alloc_slice_copy(&tys))),
+                    kind: ir::ExprKind::Tuple(self.ctx.allocator.alloc_slice_copy(&items)),
+                }
+            }
+            ast::ExprKind::FunctionCall { expr, arguments } => {
+                let arguments = arguments
+                    .into_iter()
+                    .map(|argument| self.lower_expr(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let callee = match &expr.kind {
+                    ast::ExprKind::Ident(name) => match self.scopes.get_symbol(name) {
+                        Some(Symbol::Fn(overloads)) if overloads.len() > 1 => {
+                            let id = self.resolve_overload(name, overloads, &arguments, span)?;
+
+                            self.ident(id, self.fn_tys[&id], span)
+                        }
+                        _ => self.lower_expr(*expr)?,
+                    },
+                    _ => self.lower_expr(*expr)?,
+                };
+                let Some(ir::Ty::Ptr(ir::Ty::Fn(params, ret_ty, variadic))) =
+                    self.ctx.ty_problem.try_resolve(callee.ty)
+                else {
+                    return Err(TypeError::at(
+                        format!("Expression of type {} is not callable", callee.ty),
+                        span,
+                    ));
+                };
+
+                if *variadic && arguments.len() < params.len() {
+                    return Err(TypeError::at(
+                        format!(
+                            "Function takes at least {} arguments but {} were supplied",
+                            params.len(),
+                            arguments.len()
+                        ),
+                        span,
+                    ));
+                } else if !*variadic && arguments.len() != params.len() {
+                    return Err(TypeError::at(
+                        format!(
+                            "Function takes {} arguments but {} were supplied",
+                            params.len(),
+                            arguments.len()
+                        ),
+                        span,
+                    ));
+                }
+
+                let arguments = arguments
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, argument)| {
+                        let argument_ty_var = self.tys_ty_var_id(argument.ty);
+
+                        match params.get(i) {
+                            Some(param) => {
+                                let param_ty_var = self.tys_ty_var_id(param);
+
+                                self.ctx.ty_problem.eq(argument_ty_var, param_ty_var);
+                            }
+                            // Arguments past the fixed parameters aren't checked against anything
+                            None => self.ctx.ty_problem.variadic_arg(argument_ty_var),
+                        }
+
+                        argument
+                    })
+                    .collect::<Vec<_>>();
+
+                ir::Expr {
+                    span,
+                    ty: ret_ty,
+                    kind: ir::ExprKind::Call(
+                        self.ctx.allocator.alloc(callee),
+                        self.ctx.allocator.alloc_slice_copy(&arguments),
+                    ),
+                }
+            }
+            ast::ExprKind::InlineAsm(code) => ir::Expr {
+                span,
+                ty: &ir::Ty::Void,
+                kind: ir::ExprKind::InlineAsm(self.ctx.allocator.alloc_str(&code)),
+            },
+            ast::ExprKind::AlignOf(ty) => ir::Expr {
+                span,
+                ty: &ir::Ty::UInt(UintTy::Usize),
+                kind: ir::ExprKind::AlignOf(self.lower_ty(ty)?),
+            },
+            ast::ExprKind::Block(block) => {
+                // The value of the block is evaluated after its end, where
+                // deferred expressions run
+                if block.expr.is_some()
+                    && block
+                        .stmts
+                        .iter()
+                        .any(|stmt| matches!(stmt, ast::Stmt::Defer(_)))
+                {
+                    return Err(TypeError::at(
+                        "`defer` can't be used in a block expression with a value",
+                        span,
+                    ));
+                }
+
+                // Leaving a block expression early would leave the enclosing
+                // expression half evaluated, so loops around it don't count
+                let loop_depth = std::mem::take(&mut self.loop_depth);
+                let block = self.lower_block(block);
+                self.loop_depth = loop_depth;
+                let block = block?;
+
+                ir::Expr {
+                    span,
+                    ty: block.1.map_or(&ir::Ty::Void, |expr| expr.ty),
+                    kind: ir::ExprKind::Block(block),
+                }
+            }
+            ast::ExprKind::SizeOf(ty) => ir::Expr {
+                span,
+                ty: &ir::Ty::UInt(UintTy::Usize),
+                kind: ir::ExprKind::SizeOf(self.lower_ty(ty)?),
+            },
+            ast::ExprKind::ArrayAccess { expr, index } => {
+                // `arr[i]` is lowered as `*(arr + i)`, so both forms generate the same code,
+                // but only an array or a pointer can be indexed and only by an integer
+                let base = self.lower_expr(*expr)?;
+                let index = self.lower_expr(*index)?;
+                let elem_ty_var = self.ctx.ty_problem.new_infer_ty_var();
+                let elem = &*self.ctx.allocator.alloc(ir::Ty::Infer(elem_ty_var));
+                let base_ty_var = self.tys_ty_var_id(base.ty);
+                let index_ty_var = self.tys_ty_var_id(index.ty);
+
+                self.ctx
+                    .ty_problem
+                    .index(elem_ty_var, base_ty_var, index_ty_var);
+
+                let add = ir::Expr {
+                    span,
+                    ty: self.ctx.allocator.alloc(ir::Ty::Ptr(elem)),
+                    kind: ir::ExprKind::Binary(
+                        BinOp::Add,
+                        self.ctx.allocator.alloc(base),
+                        self.ctx.allocator.alloc(index),
+                    ),
+                };
+
+                ir::Expr {
+                    span,
+                    ty: elem,
+                    kind: ir::ExprKind::Unary(UnOp::Deref, self.ctx.allocator.alloc(add)),
+                }
+            }
+            ast::ExprKind::MacroCall { name, tokens } => match name.as_str() {
+                "print" => self.lower_print(tokens, span)?,
+                "itoa" => self.lower_itoa(tokens, span)?,
+                "assert" => self.lower_assert(tokens, span)?,
+                _ => return Err(TypeError::at(format!("Macro `{name}` doesn't exist"), span)),
+            },
             _ => todo!(),
+        })
+    }
+
+    /// Id of the symbol `name` refers to. A function name used on its own
+    /// can't tell which overload it means.
+    fn lookup(&self, name: &str, span: &Span) -> Result<Id, TypeError> {
+        match self.scopes.get_symbol(name) {
+            Some(Symbol::Var(id)) => Ok(id),
+            Some(Symbol::Fn([(id, _)])) => Ok(*id),
+            Some(Symbol::Fn(_)) => Err(TypeError::at(
+                format!("`{name}` is overloaded, call it to pick an overload"),
+                span,
+            )),
+            None => Err(TypeError::at(
+                format!("Cannot find `{name}` in this scope"),
+                span,
+            )),
+        }
+    }
+
+    fn ident(&mut self, id: Id, ty: &'ir ir::Ty<'ir>, span: &'ir Span) -> ir::Expr<'ir> {
+        let expr = ir::Expr {
+            ty,
+            kind: ir::ExprKind::Ident(id),
+            span,
+        };
+
+        // Functions aren't values, their names decay into a pointer to them
+        if self.fn_tys.contains_key(&id) {
+            ir::Expr {
+                ty: self.ctx.allocator.alloc(ir::Ty::Ptr(ty)),
+                kind: ir::ExprKind::Unary(UnOp::Address, self.ctx.allocator.alloc(expr)),
+                span,
+            }
+        } else {
+            expr
+        }
+    }
+
+    /// Picks the overload of `name` the arguments can be passed to. Arguments
+    /// have to match the parameter types exactly, but the type of some of them
+    /// isn't inferred yet: an integer literal fits any integer parameter and
+    /// other ones fit anything. The call is ambiguous unless exactly one
+    /// overload fits.
+    fn resolve_overload(
+        &self,
+        name: &str,
+        overloads: &[Overload<'ir>],
+        arguments: &[ir::Expr<'ir>],
+        span: &Span,
+    ) -> Result<Id, TypeError> {
+        let fitting = overloads
+            .iter()
+            .filter(|(id, params)| {
+                let ir::Ty::Fn(_, _, variadic) = self.fn_tys[id] else {
+                    unreachable!();
+                };
+                let arity = if *variadic {
+                    arguments.len() >= params.len()
+                } else {
+                    arguments.len() == params.len()
+                };
+
+                arity
+                    && params
+                        .iter()
+                        .zip(arguments)
+                        .all(|(param, argument)| self.fits(argument, param))
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        match fitting.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(TypeError::at(
+                format!("No overload of `{name}` takes arguments of these types"),
+                span,
+            )),
+            _ => Err(TypeError::at(
+                format!("Call to overloaded function `{name}` is ambiguous"),
+                span,
+            )),
+        }
+    }
+
+    fn fits(&self, argument: &ir::Expr<'ir>, param: &ir::Ty<'ir>) -> bool {
+        match self.known_ty(argument.ty) {
+            Some(ty) => self.ty_fits(ty, param),
+            None if Self::int_lit(argument) => param.int(),
+            None => true,
+        }
+    }
+
+    fn ty_fits(&self, ty: &ir::Ty<'ir>, param: &ir::Ty<'ir>) -> bool {
+        match (ty, param) {
+            (ir::Ty::Ptr(ty), ir::Ty::Ptr(param)) => match self.known_ty(ty) {
+                Some(ty) => self.ty_fits(ty, param),
+                None => true,
+            },
+            (ir::Ty::Null, ir::Ty::Ptr(_)) => true,
+            (ir::Ty::Array(ty), ir::Ty::Array(param)) => {
+                ty.len == param.len
+                    && match self.known_ty(ty.ty) {
+                        Some(ty) => self.ty_fits(ty, param.ty),
+                        None => true,
+                    }
+            }
+            (ty, param) => ty == param,
+        }
+    }
+
+    /// The type as far as it's known during lowering, before the type problem
+    /// is solved.
+    fn known_ty(&self, ty: &'ir ir::Ty<'ir>) -> Option<&'ir ir::Ty<'ir>> {
+        match ty {
+            ir::Ty::Infer(id) => self.ctx.ty_problem.get_ty_var(*id).ty(),
+            ty => Some(ty),
+        }
+    }
+
+    /// Unsuffixed integer literal, whose type is inferred and then checked to
+    /// hold it. A negated literal is checked with its sign, `span` covering it.
+    fn int_lit_expr(&mut self, lit: u64, negative: bool, span: &'ir Span) -> ir::Expr<'ir> {
+        let id = self.ctx.ty_problem.new_infer_ty_var();
+
+        self.ctx.ty_problem.int_lit(id, lit, negative, span.clone());
+
+        ir::Expr {
+            ty: self.ctx.allocator.alloc(ir::Ty::Infer(id)),
+            kind: ir::ExprKind::Lit(ir::ExprLit::UInt(lit)),
+            span,
         }
     }
 
-    fn lower_ty(&mut self, ty: ast::Ty) -> &'ir ir::Ty<'ir> {
+    fn int_lit(expr: &ir::Expr) -> bool {
+        match expr.kind {
+            ir::ExprKind::Lit(ir::ExprLit::Int(_) | ir::ExprLit::UInt(_)) => true,
+            ir::ExprKind::Unary(UnOp::Negative, expr) => Self::int_lit(expr),
+            _ => false,
+        }
+    }
+
+    /// `print!` is an intrinsic, it takes integers and strings and writes them
+    /// to stdout with the `write` syscall, so programs can produce output
+    /// without linking libc. It's here only until there's a standard library.
+    fn lower_print(
+        &mut self,
+        tokens: Vec<Token>,
+        span: &'ir Span,
+    ) -> Result<ir::Expr<'ir>, TypeError> {
+        let mut diagnostics = Diagnostics::new("");
+        let args = Parser::new(tokens.into_iter().map(Ok), &mut diagnostics)
+            .parse_macro_args()
+            .map_err(|_| TypeError::at("Failed to parse arguments of `print!`", span))?;
+        let args = args
+            .into_iter()
+            .map(|arg| {
+                let arg = self.lower_expr(arg)?;
+                let arg_ty_var = self.tys_ty_var_id(arg.ty);
+
+                self.ctx.ty_problem.printable(arg_ty_var);
+
+                Ok(arg)
+            })
+            .collect::<Result<Vec<_>, TypeError>>()?;
+
+        Ok(ir::Expr {
+            ty: &ir::Ty::Void,
+            kind: ir::ExprKind::Print(self.ctx.allocator.alloc_slice_copy(&args)),
+            span,
+        })
+    }
+
+    /// `itoa!(value, buf)` writes the decimal digits of integer `value`, with a
+    /// minus sign if it's negative, into `buf` followed by a zero and evaluates
+    /// to the number of characters written. 21 bytes of `buf` fit any value.
+    fn lower_itoa(
+        &mut self,
+        tokens: Vec<Token>,
+        span: &'ir Span,
+    ) -> Result<ir::Expr<'ir>, TypeError> {
+        let mut diagnostics = Diagnostics::new("");
+        let args = Parser::new(tokens.into_iter().map(Ok), &mut diagnostics)
+            .parse_macro_args()
+            .map_err(|_| TypeError::at("Failed to parse arguments of `itoa!`", span))?;
+        let [value, buf]: [ast::Expr; 2] = args.try_into().map_err(|args: Vec<_>| {
+            TypeError::at(
+                format!("`itoa!` takes 2 arguments but {} were supplied", args.len()),
+                span,
+            )
+        })?;
+        let value = self.lower_expr(value)?;
+        let buf = self.lower_expr(buf)?;
+        let value_ty_var = self.tys_ty_var_id(value.ty);
+        let buf_ty_var = self.tys_ty_var_id(buf.ty);
+        let ptr_ty_var = self
+            .ctx
+            .ty_problem
+            .new_typed_ty_var(&ir::Ty::Ptr(&ir::Ty::UInt(UintTy::U8)));
+
+        self.ctx.ty_problem.int(value_ty_var);
+        self.ctx.ty_problem.eq(buf_ty_var, ptr_ty_var);
+
+        Ok(ir::Expr {
+            ty: &ir::Ty::UInt(UintTy::Usize),
+            kind: ir::ExprKind::Itoa(
+                self.ctx.allocator.alloc(value),
+                self.ctx.allocator.alloc(buf),
+            ),
+            span,
+        })
+    }
+
+    /// `assert!(condition)` aborts the program when the condition is false,
+    /// reporting the span of the assertion.
+    fn lower_assert(
+        &mut self,
+        tokens: Vec<Token>,
+        span: &'ir Span,
+    ) -> Result<ir::Expr<'ir>, TypeError> {
+        let mut diagnostics = Diagnostics::new("");
+        let args = Parser::new(tokens.into_iter().map(Ok), &mut diagnostics)
+            .parse_macro_args()
+            .map_err(|_| TypeError::at("Failed to parse arguments of `assert!`", span))?;
+        let [condition]: [ast::Expr; 1] = args.try_into().map_err(|args: Vec<_>| {
+            TypeError::at(
+                format!(
+                    "`assert!` takes 1 argument but {} were supplied",
+                    args.len()
+                ),
+                span,
+            )
+        })?;
+        let condition = self.lower_condition(condition)?;
+        let message = format!("assertion failed at {}..{}", span.start, span.end);
+
+        Ok(ir::Expr {
+            ty: &ir::Ty::Void,
+            kind: ir::ExprKind::Assert(
+                self.ctx.allocator.alloc(condition),
+                self.ctx.allocator.alloc_str(&message),
+            ),
+            span,
+        })
+    }
+
+    fn lower_ty(&mut self, ty: ast::Ty) -> Result<&'ir ir::Ty<'ir>, TypeError> {
         match self.types.get(&ty) {
-            Some(ty) => *ty,
+            Some(ty) => Ok(*ty),
             None => {
                 let ir_ty = match &ty {
                     ast::Ty::Null => self.ctx.allocator.alloc(ir::Ty::Null),
@@ -389,32 +1490,69 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                         ast::UintTy::U64 => self.ctx.allocator.alloc(ir::Ty::UInt(UintTy::U64)),
                         ast::UintTy::Usize => self.ctx.allocator.alloc(ir::Ty::UInt(UintTy::Usize)),
                     },
-                    ast::Ty::Ptr(ref ty) => self
+                    // `*fn` is the same as `fn`, which is a pointer already
+                    ast::Ty::Ptr(ref ty, _) if matches!(**ty, ast::Ty::Fn(..)) => {
+                        self.lower_ty(*ty.clone())?
+                    }
+                    ast::Ty::Ptr(ref ty, _) => self
                         .ctx
                         .allocator
-                        .alloc(ir::Ty::Ptr(self.lower_ty(*ty.clone()))),
+                        .alloc(ir::Ty::Ptr(self.lower_ty(*ty.clone())?)),
                     ast::Ty::Array { ty, len } => {
                         self.ctx.allocator.alloc(ir::Ty::Array(ir::TyArray {
                             len: *len,
-                            ty: self.lower_ty(*ty.clone()),
+                            ty: self.lower_ty(*ty.clone())?,
                         }))
                     }
+                    // A function isn't a value, so a function type in the
+                    // source is a pointer to one, which is what a function
+                    // name decays into
                     ast::Ty::Fn(ref params, ref ret_ty) => {
                         let mut alloced_params = Vec::new();
 
                         for ty in params {
-                            alloced_params.push(self.lower_ty(ty.clone()));
+                            alloced_params.push(self.lower_ty(ty.clone())?);
                         }
 
                         let params = &*self.ctx.allocator.alloc_slice_copy(&alloced_params);
+                        let ty = self.ctx.allocator.alloc(ir::Ty::Fn(
+                            params,
+                            self.lower_ty(*ret_ty.clone())?,
+                            false,
+                        ));
+
+                        self.ctx.allocator.alloc(ir::Ty::Ptr(ty))
+                    }
+                    ast::Ty::Tuple(tys) => {
+                        let tys = tys
+                            .iter()
+                            .map(|ty| self.lower_ty(ty.clone()))
+                            .collect::<Result<Vec<_>, _>>()?;
 
                         self.ctx
                             .allocator
-                            .alloc(ir::Ty::Fn(params, self.lower_ty(*ret_ty.clone())))
-                    }
-                    ast::Ty::Ident(ident) => {
-                        return self.scopes.get_type(ident).unwrap();
+                            .alloc(ir::Ty::Tuple(self.ctx.allocator.alloc_slice_copy(&tys)))
                     }
+                    ast::Ty::Ident(ident) => match self.aliases.get(ident).cloned() {
+                        Some(aliased) => {
+                            if !self.resolving.insert(ident.clone()) {
+                                return Err(TypeError::new(format!(
+                                    "Type alias `{ident}` refers to itself"
+                                )));
+                            }
+
+                            let ty = self.lower_ty(aliased)?;
+
+                            self.resolving.remove(ident);
+
+                            ty
+                        }
+                        None => {
+                            return self.scopes.get_type(ident).ok_or_else(|| {
+                                TypeError::new(format!("Cannot find type `{ident}` in this scope"))
+                            })
+                        }
+                    },
                     ast::Ty::Infer => self
                         .ctx
                         .allocator
@@ -425,13 +1563,13 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
                     self.types.insert(ty, ir_ty);
                 }
 
-                ir_ty
+                Ok(ir_ty)
             }
         }
     }
 
-    fn expr_ty(&mut self, expr: &ast::Expr) -> &'ir ir::Ty<'ir> {
-        match &expr.kind {
+    fn expr_ty(&mut self, expr: &ast::Expr) -> Result<&'ir ir::Ty<'ir>, TypeError> {
+        Ok(match &expr.kind {
             ast::ExprKind::Binary { .. } => self
                 .ctx
                 .allocator
@@ -439,24 +1577,29 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
             ast::ExprKind::Lit(lit) => match lit {
                 ast::ExprLit::Bool(_) => &ir::Ty::Bool,
                 ast::ExprLit::String(_) => &ir::Ty::Ptr(&ir::Ty::UInt(UintTy::U8)),
+                ast::ExprLit::Null => &ir::Ty::Null,
+                ast::ExprLit::Suffixed(_, ty) => self.lower_ty(ty.clone())?,
                 _ => self
                     .ctx
                     .allocator
                     .alloc(ir::Ty::Infer(self.ctx.ty_problem.new_infer_ty_var())),
             },
             ast::ExprKind::Ident(ident) => {
-                let id = self.scopes.get_symbol(ident).unwrap();
+                let id = self.lookup(ident, &expr.span)?;
 
-                match self.nodes_map.get(&id).unwrap() {
-                    ir::Node::Stmt(stmt) => match stmt {
+                match self.nodes_map.get(&id) {
+                    Some(ir::Node::Stmt(stmt)) => match stmt {
                         Stmt::Local(stmt) => stmt.ty,
                         _ => unreachable!(),
                     },
-                    _ => panic!("nono"),
+                    Some(ir::Node::Param(param)) => param.ty,
+                    Some(ir::Node::Item(ir::Item::Global(var))) => var.ty,
+                    Some(_) => panic!("nono"),
+                    None => self.fn_tys[&id],
                 }
             }
             _ => todo!(),
-        }
+        })
     }
 
     fn tys_ty_var_id(&mut self, ty: &'ir ir::Ty<'ir>) -> ty_problem::Id {
@@ -466,3 +1609,7 @@ impl<'a, 'ir> Lowering<'a, 'ir> {
         }
     }
 }
+
+fn redeclared(name: &str, span: &Span) -> TypeError {
+    TypeError::at(format!("`{name}` is already declared in this scope"), span)
+}
@@ -1,10 +1,43 @@
-use crate::ir::{Id, Ty};
-use std::collections::HashMap;
+use crate::{
+    ir::{Id, Ty},
+    ty_problem::TypeError,
+};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RedeclarationError {
+    #[error("`{0}` is already declared in this scope")]
+    Symbol(String),
+    #[error("duplicate parameter `{0}`")]
+    Param(String),
+    #[error("`{0}` shadows a parameter, it can only be redeclared in an inner block")]
+    ShadowsParam(String),
+}
+
+impl From<RedeclarationError> for TypeError {
+    fn from(err: RedeclarationError) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+/// Function and the types of its parameters, which tell apart overloads
+pub type Overload<'ir> = (Id, &'ir [&'ir Ty<'ir>]);
+
+#[derive(Debug, PartialEq)]
+pub enum Symbol<'a, 'ir> {
+    Var(Id),
+    /// Every overload of a function declared in the scope
+    Fn(&'a [Overload<'ir>]),
+}
 
 #[derive(Debug)]
 pub struct Scope<'ir> {
     pub type_table: HashMap<String, &'ir Ty<'ir>>,
     pub symbol_table: HashMap<String, Id>,
+    pub fn_table: HashMap<String, Vec<Overload<'ir>>>,
+    /// Parameters of the function whose top scope this is
+    pub params: HashSet<String>,
 }
 
 impl<'ir> Scope<'ir> {
@@ -12,8 +45,14 @@ impl<'ir> Scope<'ir> {
         Self {
             type_table: HashMap::new(),
             symbol_table: HashMap::new(),
+            fn_table: HashMap::new(),
+            params: HashSet::new(),
         }
     }
+
+    fn contains(&self, name: &str) -> bool {
+        self.symbol_table.contains_key(name) || self.fn_table.contains_key(name)
+    }
 }
 
 #[derive(Debug)]
@@ -36,7 +75,8 @@ impl<'ir> Scopes<'ir> {
     where
         T: Fn(&Scope<'ir>) -> Option<O>,
     {
-        for scope in &self.0 {
+        // Inner scopes shadow the outer ones
+        for scope in self.0.iter().rev() {
             if let item @ Some(_) = f(scope) {
                 return item;
             }
@@ -53,15 +93,240 @@ impl<'ir> Scopes<'ir> {
         self.find(|scope| scope.type_table.get(name).map(|&ty| ty))
     }
 
-    pub fn insert_symbol(&mut self, name: String, id: Id) {
-        self.0.last_mut().unwrap().symbol_table.insert(name, id);
+    /// Declares `name` in the innermost scope. A name may shadow a symbol of an
+    /// outer scope, but not one declared in the same scope.
+    pub fn insert_symbol(&mut self, name: String, id: Id) -> Result<(), RedeclarationError> {
+        let scope = self.0.last_mut().unwrap();
+
+        if scope.params.contains(&name) {
+            return Err(RedeclarationError::ShadowsParam(name));
+        }
+        if scope.contains(&name) {
+            return Err(RedeclarationError::Symbol(name));
+        }
+
+        scope.symbol_table.insert(name, id);
+
+        Ok(())
     }
 
-    pub fn get_symbol(&self, name: &str) -> Option<Id> {
-        self.find(|scope| scope.symbol_table.get(name).map(|&id| id))
+    /// Declares parameter `name` in the innermost scope, which is the top scope
+    /// of its function. Locals of the same scope can't shadow it, those of an
+    /// inner block can.
+    pub fn insert_param(&mut self, name: String, id: Id) -> Result<(), RedeclarationError> {
+        let scope = self.0.last_mut().unwrap();
+
+        if !scope.params.insert(name.clone()) {
+            return Err(RedeclarationError::Param(name));
+        }
+        scope.symbol_table.insert(name, id);
+
+        Ok(())
+    }
+
+    /// Declares function `name` in the innermost scope. Functions of the same
+    /// scope may share a name as long as their parameter types differ.
+    pub fn insert_fn(
+        &mut self,
+        name: String,
+        id: Id,
+        params: &'ir [&'ir Ty<'ir>],
+    ) -> Result<(), RedeclarationError> {
+        let scope = self.0.last_mut().unwrap();
+
+        if scope.symbol_table.contains_key(&name)
+            || scope
+                .fn_table
+                .get(&name)
+                .is_some_and(|overloads| overloads.iter().any(|(_, tys)| *tys == params))
+        {
+            return Err(RedeclarationError::Symbol(name));
+        }
+
+        scope.fn_table.entry(name).or_default().push((id, params));
+
+        Ok(())
+    }
+
+    pub fn get_symbol(&self, name: &str) -> Option<Symbol<'_, 'ir>> {
+        // Overloads borrow from the scope, which `Self::find` can't express
+        for scope in self.0.iter().rev() {
+            if let Some(&id) = scope.symbol_table.get(name) {
+                return Some(Symbol::Var(id));
+            }
+
+            if let Some(overloads) = scope.fn_table.get(name) {
+                return Some(Symbol::Fn(overloads));
+            }
+        }
+
+        None
     }
 
     pub fn is_global(&self) -> bool {
         self.0.len() < 1
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{RedeclarationError, Scopes, Symbol};
+    use crate::{
+        ast::{IntTy, UintTy},
+        ir::{Id, Ty},
+    };
+    use std::time::{Duration, Instant};
+
+    fn id(node_id: usize) -> Id {
+        Id {
+            global_id: 0,
+            node_id,
+        }
+    }
+
+    #[test]
+    fn shadowing() {
+        let mut scopes = Scopes::new();
+
+        scopes.enter();
+        scopes.insert_symbol(String::from("foo"), id(0)).unwrap();
+        scopes.insert_symbol(String::from("bar"), id(1)).unwrap();
+
+        scopes.enter();
+        scopes.insert_symbol(String::from("foo"), id(2)).unwrap();
+        assert_eq!(scopes.get_symbol("foo"), Some(Symbol::Var(id(2))));
+        assert_eq!(scopes.get_symbol("bar"), Some(Symbol::Var(id(1))));
+
+        scopes.leave();
+        assert_eq!(scopes.get_symbol("foo"), Some(Symbol::Var(id(0))));
+        assert_eq!(scopes.get_symbol("baz"), None);
+    }
+
+    #[test]
+    fn many_symbols() {
+        let mut scopes = Scopes::new();
+
+        scopes.enter();
+        for i in 0..10_000 {
+            scopes.insert_symbol(format!("x{i}"), id(i)).unwrap();
+        }
+
+        // Each scope is a hash map, looking a name up doesn't walk its symbols
+        scopes.enter();
+        for i in 0..10_000 {
+            assert_eq!(
+                scopes.get_symbol(&format!("x{i}")),
+                Some(Symbol::Var(id(i)))
+            );
+        }
+        assert_eq!(scopes.get_symbol("x10000"), None);
+    }
+
+    #[test]
+    fn lookup_time_independent_of_symbols() {
+        // Fastest of a few rounds of looking up the same number of names in a
+        // scope of `size` symbols
+        fn lookup_time(size: usize) -> Duration {
+            let mut scopes = Scopes::new();
+
+            scopes.enter();
+            for i in 0..size {
+                scopes.insert_symbol(format!("x{i}"), id(i)).unwrap();
+            }
+
+            let names: Vec<_> = (0..10_000)
+                .map(|i| format!("x{}", i * size / 10_000))
+                .collect();
+
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+
+                    for name in &names {
+                        assert!(scopes.get_symbol(name).is_some());
+                    }
+
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        }
+
+        let small = lookup_time(1_000);
+        let large = lookup_time(100_000);
+
+        // Scanning a scope 100 times as large would take about 100 times as long
+        assert!(large < small * 10, "{small:?} then {large:?}");
+    }
+
+    #[test]
+    fn redeclaration() {
+        let mut scopes = Scopes::new();
+
+        scopes.enter();
+        scopes.insert_symbol(String::from("foo"), id(0)).unwrap();
+
+        assert_eq!(
+            scopes.insert_symbol(String::from("foo"), id(1)),
+            Err(RedeclarationError::Symbol(String::from("foo")))
+        );
+        assert_eq!(scopes.get_symbol("foo"), Some(Symbol::Var(id(0))));
+    }
+
+    #[test]
+    fn params() {
+        let mut scopes = Scopes::new();
+
+        scopes.enter();
+        scopes.insert_param(String::from("foo"), id(0)).unwrap();
+        scopes.insert_param(String::from("bar"), id(1)).unwrap();
+
+        assert_eq!(
+            scopes.insert_param(String::from("foo"), id(2)),
+            Err(RedeclarationError::Param(String::from("foo")))
+        );
+        assert_eq!(
+            scopes.insert_symbol(String::from("bar"), id(3)),
+            Err(RedeclarationError::ShadowsParam(String::from("bar")))
+        );
+        assert_eq!(scopes.get_symbol("bar"), Some(Symbol::Var(id(1))));
+
+        // An inner block may shadow a parameter
+        scopes.enter();
+        scopes.insert_symbol(String::from("bar"), id(4)).unwrap();
+        assert_eq!(scopes.get_symbol("bar"), Some(Symbol::Var(id(4))));
+    }
+
+    #[test]
+    fn overloads() {
+        let mut scopes = Scopes::new();
+        let i32_params: &[&Ty] = &[&Ty::Int(IntTy::I32)];
+        let u8_params: &[&Ty] = &[&Ty::UInt(UintTy::U8)];
+
+        scopes.enter();
+        scopes
+            .insert_fn(String::from("abs"), id(0), i32_params)
+            .unwrap();
+        scopes
+            .insert_fn(String::from("abs"), id(1), u8_params)
+            .unwrap();
+
+        assert_eq!(
+            scopes.insert_fn(String::from("abs"), id(2), u8_params),
+            Err(RedeclarationError::Symbol(String::from("abs")))
+        );
+        assert_eq!(
+            scopes.insert_symbol(String::from("abs"), id(3)),
+            Err(RedeclarationError::Symbol(String::from("abs")))
+        );
+        assert_eq!(
+            scopes.get_symbol("abs"),
+            Some(Symbol::Fn(&[(id(0), i32_params), (id(1), u8_params)]))
+        );
+
+        // A local hides every overload of the outer scope
+        scopes.enter();
+        scopes.insert_symbol(String::from("abs"), id(4)).unwrap();
+        assert_eq!(scopes.get_symbol("abs"), Some(Symbol::Var(id(4))));
+    }
+}
@@ -1,14 +1,19 @@
 use clap::Parser;
-use meraki::compile::{compile, CompileArgs};
+use meraki::compile::{compile, CompileArgs, CompileError};
 
 fn main() {
     let options = CompileArgs::parse();
-    if let Err(e) = compile(options) {
-        die(e);
+    match compile(options) {
+        Ok(_) => (),
+        Err(err @ (CompileError::Diagnostics(_) | CompileError::Type(_))) => {
+            println!("{err}");
+            std::process::exit(0x45);
+        }
+        Err(e) => die(e),
     }
 }
 
-fn die(err: Box<dyn std::error::Error>) -> ! {
+fn die(err: CompileError) -> ! {
     eprintln!("{}", err);
     std::process::exit(1);
 }
@@ -4,7 +4,7 @@ use crate::{
     lexer::{span::Span, Token, TokenKind},
 };
 
-impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
+impl<'a, 'src, T: Iterator<Item = Result<Token, (Diagnostic, Span)>>> Parser<'a, 'src, T> {
     pub fn expected(&mut self, tokens: &[&TokenKind]) {
         let mut msg = match tokens {
             [token] => format!("`{token}`"),
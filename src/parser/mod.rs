@@ -6,29 +6,32 @@ pub use precedence::Precedence;
 use crate::{
     ast::{BinOp, Block, Expr, ExprKind, ExprLit, IntTy, Item, Stmt, Ty, UintTy, UnOp, Variable},
     diagnostics::{Diagnostic, Diagnostics},
-    lexer::{span::Span, Token, TokenKind},
+    lexer::{parse_int_lit, span::Span, split_int_lit_suffix, Token, TokenKind},
 };
 use std::collections::HashMap;
 
 type PrefixFn<'a, 'src, T> = fn(&mut Parser<'a, 'src, T>) -> Result<Expr, ()>;
 type InfixFn<'a, 'src, T> = fn(&mut Parser<'a, 'src, T>, left: Expr) -> Result<Expr, ()>;
 
-pub struct Parser<'a, 'src, T: Iterator<Item = Result<Token, Span>>> {
+pub struct Parser<'a, 'src, T: Iterator<Item = Result<Token, (Diagnostic, Span)>>> {
     lexer: T,
     diag: &'a mut Diagnostics<'src>,
     prev_token: Option<Token>,
     cur_token: Option<Token>,
     peek_token: Option<Token>,
+    /// How many braces the tokens consumed so far leave open
+    depth: usize,
     prefix_fns: HashMap<TokenKind, PrefixFn<'a, 'src, T>>,
     infix_fns: HashMap<TokenKind, InfixFn<'a, 'src, T>>,
 }
 
-impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
+impl<'a, 'src, T: Iterator<Item = Result<Token, (Diagnostic, Span)>>> Parser<'a, 'src, T> {
     pub fn new(lexer: T, diag: &'a mut Diagnostics<'src>) -> Self {
         let mut parser = Self {
             prev_token: None,
             cur_token: None,
             peek_token: None,
+            depth: 0,
             lexer,
             diag,
             prefix_fns: HashMap::from([
@@ -44,6 +47,10 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                     TokenKind::Integer(Default::default()),
                     Self::parse_int_lit_expr,
                 ),
+                (
+                    TokenKind::Char(Default::default()),
+                    Self::parse_char_lit_expr,
+                ),
                 (TokenKind::Null, Self::parse_null_expr),
                 (TokenKind::True, Self::parse_bool_expr),
                 (TokenKind::False, Self::parse_bool_expr),
@@ -54,12 +61,17 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                 (TokenKind::Asterisk, Self::parse_unary_expr),
                 (TokenKind::Tilde, Self::parse_unary_expr),
                 (TokenKind::LBracket, Self::parse_array_expr),
+                (TokenKind::Asm, Self::parse_inline_asm_expr),
+                (TokenKind::AlignOf, Self::parse_alignof_expr),
+                (TokenKind::SizeOf, Self::parse_sizeof_expr),
+                (TokenKind::LBrace, Self::parse_block_expr),
             ]),
             infix_fns: HashMap::from([
                 (TokenKind::Plus, Self::parse_bin_expr as InfixFn<T>),
                 (TokenKind::Minus, Self::parse_bin_expr),
                 (TokenKind::Asterisk, Self::parse_bin_expr),
                 (TokenKind::Slash, Self::parse_bin_expr),
+                (TokenKind::Percent, Self::parse_bin_expr),
                 (TokenKind::Assign, Self::parse_bin_expr),
                 (TokenKind::LessThan, Self::parse_bin_expr),
                 (TokenKind::LessEqual, Self::parse_bin_expr),
@@ -92,12 +104,18 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     fn bump(&mut self) {
         match self.lexer.next().transpose() {
             Ok(mut token) => {
+                match self.cur_token.as_ref().map(|token| &token.kind) {
+                    Some(TokenKind::LBrace) => self.depth += 1,
+                    Some(TokenKind::RBrace) => self.depth = self.depth.saturating_sub(1),
+                    _ => (),
+                }
+
                 std::mem::swap(&mut self.prev_token, &mut self.cur_token);
                 std::mem::swap(&mut self.cur_token, &mut self.peek_token);
                 std::mem::swap(&mut token, &mut self.peek_token);
             }
-            Err(span) => {
-                self.diag.error(Diagnostic::UnknownChar, span);
+            Err((diag, span)) => {
+                self.diag.error(diag, span);
 
                 self.bump()
             }
@@ -133,25 +151,106 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
         while let Some(token) = &self.cur_token {
             let item = match token.kind {
-                TokenKind::Struct => self.parse_struct_item().ok(),
-                TokenKind::Let => self.parse_global_item().ok(),
-                TokenKind::Fn => self.parse_function_item(true).ok(),
+                TokenKind::Struct | TokenKind::Union => self.parse_struct_item().ok(),
+                _ if self.packed_struct() => self.parse_struct_item().ok(),
+                TokenKind::Type => self.parse_type_alias_item().ok(),
+                TokenKind::Let => self.parse_global_item(false).ok(),
+                _ if self.thread_local_global() => self.parse_global_item(false).ok(),
+                TokenKind::Fn | TokenKind::Extern => self.parse_function_item(true, false).ok(),
+                _ if self.fn_attr() => self.parse_function_item(true, false).ok(),
+                TokenKind::Pub => self.parse_pub_item().ok(),
+                // The statement is parsed anyway to skip past it as a whole
+                TokenKind::Return => {
+                    let span = token.span.clone();
+
+                    if self.parse_return_stmt().is_ok() {
+                        self.diag.error(Diagnostic::ReturnOutsideFn, span);
+                    }
+
+                    None
+                }
                 _ => {
-                    self.expected(&[&TokenKind::Struct, &TokenKind::Let, &TokenKind::Fn]);
+                    self.expected(&[
+                        &TokenKind::Struct,
+                        &TokenKind::Union,
+                        &TokenKind::Type,
+                        &TokenKind::Let,
+                        &TokenKind::Fn,
+                        &TokenKind::Extern,
+                        &TokenKind::Pub,
+                    ]);
                     self.bump();
+                    self.synchronize_item();
 
                     None
                 }
             };
 
-            if let Some(item) = item {
-                items.push(item);
+            match item {
+                Some(item) => items.push(item),
+                None => self.synchronize_item(),
             }
         }
 
         Ok(items)
     }
 
+    /// Skips the rest of a statement which failed to parse in the block whose
+    /// braces leave `depth` open, up to and including the next `;` or up to
+    /// the `}` closing the block. Braces the statement opened, before or after
+    /// the mistake, are skipped as a whole, so parsing resumes at the next
+    /// statement and every mistake is reported once.
+    fn synchronize(&mut self, depth: usize) {
+        while let Some(token) = &self.cur_token {
+            match token.kind {
+                TokenKind::Semicolon if self.depth == depth => {
+                    self.bump();
+
+                    return;
+                }
+                TokenKind::RBrace if self.depth <= depth => return,
+                TokenKind::RBrace if self.depth == depth + 1 => {
+                    self.bump();
+
+                    return;
+                }
+                _ => (),
+            }
+
+            self.bump();
+        }
+    }
+
+    /// Skips tokens up to the start of the next top-level item, outside of any
+    /// braces.
+    fn synchronize_item(&mut self) {
+        while let Some(token) = &self.cur_token {
+            let depth = self.depth;
+
+            match token.kind {
+                _ if depth == 0
+                    && (self.packed_struct() || self.fn_attr() || self.thread_local_global()) =>
+                {
+                    return
+                }
+                TokenKind::Struct
+                | TokenKind::Union
+                | TokenKind::Type
+                | TokenKind::Let
+                | TokenKind::Fn
+                | TokenKind::Extern
+                | TokenKind::Pub
+                    if depth == 0 =>
+                {
+                    return
+                }
+                _ => (),
+            }
+
+            self.bump();
+        }
+    }
+
     pub fn parse_expr(&mut self, precedence: Precedence) -> Result<Expr, ()> {
         let token = self.cur_token_unchecked();
 
@@ -184,10 +283,70 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         left
     }
 
+    /// Parses the tokens of a macro invocation as a comma separated list of
+    /// expressions.
+    pub fn parse_macro_args(&mut self) -> Result<Vec<Expr>, ()> {
+        let mut exprs = Vec::new();
+
+        while self.cur_token.is_some() {
+            exprs.push(self.parse_expr(Precedence::default())?);
+            if self.cur_token.is_some() {
+                self.expect(&TokenKind::Comma)?;
+            }
+        }
+
+        Ok(exprs)
+    }
+
+    /// Whether the current token is `packed` followed by `struct`. It's only a
+    /// keyword there, elsewhere it's still an identifier.
+    fn packed_struct(&self) -> bool {
+        matches!(
+            self.cur_token.as_ref().map(|token| &token.kind),
+            Some(TokenKind::Ident(ident)) if ident == "packed"
+        ) && self.peek_token_is(&TokenKind::Struct)
+    }
+
+    /// Whether the current token is `thread_local` followed by `let`, it's only
+    /// a keyword there.
+    fn thread_local_global(&self) -> bool {
+        matches!(
+            self.cur_token.as_ref().map(|token| &token.kind),
+            Some(TokenKind::Ident(ident)) if ident == "thread_local"
+        ) && self.peek_token_is(&TokenKind::Let)
+    }
+
+    /// Whether the current token is a function attribute, `naked` or
+    /// `noreturn`, followed by `fn` or another attribute. Like `packed`, they're
+    /// only keywords there.
+    fn fn_attr(&self) -> bool {
+        let attr = |token: Option<&Token>| {
+            matches!(
+                token.map(|token| &token.kind),
+                Some(TokenKind::Ident(ident)) if ident == "naked" || ident == "noreturn"
+            )
+        };
+
+        attr(self.cur_token.as_ref())
+            && (self.peek_token_is(&TokenKind::Fn) || attr(self.peek_token.as_ref()))
+    }
+
+    /// A struct or a union, they're declared the same way. Only a struct can be
+    /// `packed`.
     fn parse_struct_item(&mut self) -> Result<Item, ()> {
-        self.expect(&TokenKind::Struct)?;
+        let packed = self.packed_struct();
+        if packed {
+            self.bump();
+        }
+        let union = !packed && self.cur_token_is(&TokenKind::Union);
+
+        self.expect(if union {
+            &TokenKind::Union
+        } else {
+            &TokenKind::Struct
+        })?;
 
-        let (name, _) = self.parse_ident()?;
+        let (name, name_span) = self.parse_ident()?;
 
         self.expect(&TokenKind::LBrace)?;
 
@@ -211,7 +370,30 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
         self.expect(&TokenKind::RBrace)?;
 
-        Ok(Item::Struct { name, fields })
+        Ok(if union {
+            Item::Union {
+                name,
+                span: name_span,
+                fields,
+            }
+        } else {
+            Item::Struct {
+                name,
+                span: name_span,
+                fields,
+                packed,
+            }
+        })
+    }
+
+    fn parse_type_alias_item(&mut self) -> Result<Item, ()> {
+        self.expect(&TokenKind::Type)?;
+        let (name, span) = self.parse_ident()?;
+        self.expect(&TokenKind::Assign)?;
+        let ty = self.parse_type()?;
+        self.expect(&TokenKind::Semicolon)?;
+
+        Ok(Item::TypeAlias { name, span, ty })
     }
 
     fn parse_stmt(&mut self) -> Result<Stmt, ()> {
@@ -220,20 +402,43 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
             Some(TokenKind::If) => self.parse_if_stmt(),
             Some(TokenKind::While) => self.parse_while_stmt(),
             Some(TokenKind::For) => self.parse_for_stmt(),
-            Some(TokenKind::Let) => self.parse_local_stmt(),
+            Some(TokenKind::Let | TokenKind::Static) => self.parse_local_stmt(),
             Some(TokenKind::Continue) => {
-                self.expect(&TokenKind::Continue)?;
-                self.expect(&TokenKind::Semicolon)?;
+                let span = self.expect(&TokenKind::Continue)?;
+                let end = self.expect(&TokenKind::Semicolon)?;
 
-                Ok(Stmt::Continue)
+                Ok(Stmt::Continue(span.to(end)))
             }
             Some(TokenKind::Break) => {
-                self.expect(&TokenKind::Break)?;
+                let span = self.expect(&TokenKind::Break)?;
+                let end = self.expect(&TokenKind::Semicolon)?;
+
+                Ok(Stmt::Break(span.to(end)))
+            }
+            Some(TokenKind::Goto) => {
+                self.expect(&TokenKind::Goto)?;
+                let (label, _) = self.parse_ident()?;
+                self.expect(&TokenKind::Semicolon)?;
+
+                Ok(Stmt::Goto(label))
+            }
+            Some(TokenKind::Defer) => {
+                self.expect(&TokenKind::Defer)?;
+                let expr = self.parse_expr(Precedence::default())?;
                 self.expect(&TokenKind::Semicolon)?;
 
-                Ok(Stmt::Break)
+                Ok(Stmt::Defer(expr))
             }
-            Some(TokenKind::Fn) => Ok(Stmt::Item(self.parse_function_item(false)?)),
+            Some(TokenKind::Ident(_)) if self.peek_token_is(&TokenKind::Colon) => {
+                let (label, _) = self.parse_ident()?;
+                self.expect(&TokenKind::Colon)?;
+
+                Ok(Stmt::Label(label))
+            }
+            Some(TokenKind::Fn | TokenKind::Extern) => {
+                Ok(Stmt::Item(self.parse_function_item(false, false)?))
+            }
+            Some(_) if self.fn_attr() => Ok(Stmt::Item(self.parse_function_item(false, false)?)),
             Some(_) => {
                 let expr = Stmt::Expr(self.parse_expr(Precedence::default())?);
 
@@ -248,8 +453,11 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                     &TokenKind::While,
                     &TokenKind::For,
                     &TokenKind::Let,
+                    &TokenKind::Static,
                     &TokenKind::Continue,
                     &TokenKind::Break,
+                    &TokenKind::Goto,
+                    &TokenKind::Defer,
                 ]);
 
                 Err(())
@@ -258,13 +466,44 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     }
 
     fn parse_block_stmt(&mut self) -> Result<Block, ()> {
+        self.parse_block(false)
+    }
+
+    fn parse_block_expr(&mut self) -> Result<Expr, ()> {
+        let block = self.parse_block(true)?;
+
+        Ok(Expr {
+            span: block.open_brace.clone().to(block.close_brace.clone()),
+            kind: ExprKind::Block(block),
+        })
+    }
+
+    /// With `value` the block is an expression, and an expression right before
+    /// the closing brace without a semicolon after it is its value.
+    fn parse_block(&mut self, value: bool) -> Result<Block, ()> {
         let mut stmts = Vec::new();
+        let mut expr = None;
 
         let open_brace = self.expect(&TokenKind::LBrace)?;
-
-        while !self.cur_token_is(&TokenKind::RBrace) {
-            if let Ok(stmt) = self.parse_stmt() {
-                stmts.push(stmt);
+        let depth = self.depth;
+
+        // A missing `}` is reported by `expect` below
+        while !self.cur_token_is(&TokenKind::RBrace) && self.cur_token.is_some() {
+            if value && !self.stmt_keyword() {
+                match self.parse_expr(Precedence::default()) {
+                    Ok(value) if self.cur_token_is(&TokenKind::RBrace) => {
+                        expr = Some(Box::new(value));
+                    }
+                    Ok(value) if self.expect(&TokenKind::Semicolon).is_ok() => {
+                        stmts.push(Stmt::Expr(value));
+                    }
+                    _ => self.synchronize(depth),
+                }
+            } else {
+                match self.parse_stmt() {
+                    Ok(stmt) => stmts.push(stmt),
+                    Err(_) => self.synchronize(depth),
+                }
             }
         }
 
@@ -273,10 +512,39 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         Ok(Block {
             open_brace,
             stmts,
+            expr,
             close_brace,
         })
     }
 
+    /// Whether the current token starts a statement other than an expression
+    fn stmt_keyword(&self) -> bool {
+        let label = matches!(
+            self.cur_token.as_ref().map(|token| &token.kind),
+            Some(TokenKind::Ident(_))
+        ) && self.peek_token_is(&TokenKind::Colon);
+
+        label
+            || self.fn_attr()
+            || matches!(
+                self.cur_token.as_ref().map(|token| &token.kind),
+                Some(
+                    TokenKind::Return
+                        | TokenKind::If
+                        | TokenKind::While
+                        | TokenKind::For
+                        | TokenKind::Let
+                        | TokenKind::Static
+                        | TokenKind::Continue
+                        | TokenKind::Break
+                        | TokenKind::Goto
+                        | TokenKind::Defer
+                        | TokenKind::Fn
+                        | TokenKind::Extern
+                ) | None
+            )
+    }
+
     // This function is used only by macro expansion
     pub fn parse_stmts(&mut self) -> Result<Vec<Stmt>, ()> {
         let mut stmts = Vec::new();
@@ -292,40 +560,39 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
     fn parse_type(&mut self) -> Result<Ty, ()> {
         let ty = match self.cur_token.as_ref().map(|token| &token.kind) {
-            Some(TokenKind::Asterisk) => Ty::Ptr(Box::new(self.parse_type()?)),
+            Some(TokenKind::Asterisk) => {
+                self.bump();
+                let restrict = self.cur_token_is(&TokenKind::Restrict);
+                if restrict {
+                    self.bump();
+                }
+
+                return Ok(Ty::Ptr(Box::new(self.parse_type()?), restrict));
+            }
             Some(TokenKind::LBracket) => {
                 self.bump();
 
-                match &self.cur_token {
-                    Some(Token {
-                        kind: TokenKind::Integer(int),
-                        span,
-                    }) => {
-                        let length: usize = match str::parse(int) {
-                            Ok(value) => value,
-                            Err(_) => {
-                                let span = span.clone();
-
-                                self.bump();
-                                self.diag.error(Diagnostic::IntegerLitralTooLong, span);
-
-                                return Err(());
-                            }
-                        };
-                        self.expect(&TokenKind::RBracket)?;
-
-                        Ty::Array {
-                            ty: Box::new(self.parse_type()?),
-                            len: length,
-                        }
-                    }
-                    _ => {
-                        self.expected(&[&TokenKind::Integer(Default::default())]);
-                        self.bump();
+                // The length can be any constant expression, like `[2 * 8]u8`
+                let expr = self.parse_expr(Precedence::default())?;
+                self.expect(&TokenKind::RBracket)?;
+                let ty = self.parse_type()?;
+                let len = match expr.const_eval() {
+                    Ok(len) => usize::try_from(len).map_err(|_| {
+                        self.diag
+                            .error(Diagnostic::NegativeArrayLen(len), expr.span.clone());
+                    })?,
+                    Err(err) => {
+                        self.diag
+                            .error(Diagnostic::NonConstArrayLen(err), expr.span.clone());
 
-                        return Err(())?;
+                        return Err(());
                     }
-                }
+                };
+
+                return Ok(Ty::Array {
+                    ty: Box::new(ty),
+                    len,
+                });
             }
             Some(TokenKind::U8) => Ty::UInt(UintTy::U8),
             Some(TokenKind::U16) => Ty::UInt(UintTy::U16),
@@ -359,6 +626,24 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
                 return Ok(Ty::Fn(params, Box::new(self.parse_type()?)));
             }
+            // `(T)` is just `T`, a tuple has at least two elements
+            Some(TokenKind::LParen) => {
+                self.bump();
+
+                let mut tys = vec![self.parse_type()?];
+
+                while !self.cur_token_is(&TokenKind::RParen) {
+                    self.expect(&TokenKind::Comma)?;
+                    tys.push(self.parse_type()?);
+                }
+
+                self.expect(&TokenKind::RParen)?;
+
+                return Ok(match tys.len() {
+                    1 => tys.pop().unwrap(),
+                    _ => Ty::Tuple(tys),
+                });
+            }
             _ => {
                 self.diag.error(
                     Diagnostic::ParseExpected("type".to_string()),
@@ -427,16 +712,18 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     fn parse_for_stmt(&mut self) -> Result<Stmt, ()> {
         self.expect(&TokenKind::For)?;
 
-        let initializer = if self.cur_token_is(&TokenKind::Semicolon) {
+        // A local declaration brings its own semicolon
+        let initializer = if self.cur_token_is(&TokenKind::Let) {
+            Some(self.parse_local_stmt()?)
+        } else if self.cur_token_is(&TokenKind::Semicolon) {
+            self.expect(&TokenKind::Semicolon)?;
+
             None
         } else {
-            let stmt = if self.cur_token_is(&TokenKind::Let) {
-                self.parse_local_stmt()?
-            } else {
-                Stmt::Expr(self.parse_expr(Precedence::default())?)
-            };
+            let expr = self.parse_expr(Precedence::default())?;
+            self.expect(&TokenKind::Semicolon)?;
 
-            Some(stmt)
+            Some(Stmt::Expr(expr))
         };
 
         let condition = if self.cur_token_is(&TokenKind::Semicolon) {
@@ -463,10 +750,21 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     }
 
     fn parse_local_stmt(&mut self) -> Result<Stmt, ()> {
+        let static_ = self.cur_token_is(&TokenKind::Static);
+
+        if static_ {
+            self.expect(&TokenKind::Static)?;
+        }
+
         self.expect(&TokenKind::Let)?;
 
-        let (name, _) = self.parse_ident()?;
-        let ty = if self.cur_token_is(&TokenKind::Colon) {
+        if !static_ && self.cur_token_is(&TokenKind::LParen) {
+            return self.parse_destructure_stmt();
+        }
+
+        let (name, span) = self.parse_ident()?;
+        let hinted = self.cur_token_is(&TokenKind::Colon);
+        let ty = if hinted {
             self.expect(&TokenKind::Colon)?;
 
             self.parse_type()?
@@ -482,19 +780,71 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
             None
         };
 
+        // There's nothing to infer the type from
+        if !hinted && expr.is_none() {
+            self.diag.error(Diagnostic::TypeHintRequired, span.clone());
+        }
+
         self.expect(&TokenKind::Semicolon)?;
 
         Ok(Stmt::Local(Variable {
             name,
+            span,
             ty,
             value: expr,
+            static_,
+            thread_local: false,
+            pub_: false,
         }))
     }
 
-    fn parse_global_item(&mut self) -> Result<Item, ()> {
+    /// The part of `let (a, b) = value;` after `let`. The names take the
+    /// types of the elements, so there's no type hint.
+    fn parse_destructure_stmt(&mut self) -> Result<Stmt, ()> {
+        self.expect(&TokenKind::LParen)?;
+
+        let mut names = vec![self.parse_ident()?];
+
+        while !self.cur_token_is(&TokenKind::RParen) {
+            self.expect(&TokenKind::Comma)?;
+            names.push(self.parse_ident()?);
+        }
+
+        self.expect(&TokenKind::RParen)?;
+        self.expect(&TokenKind::Assign)?;
+        let value = self.parse_expr(Precedence::default())?;
+        self.expect(&TokenKind::Semicolon)?;
+
+        Ok(Stmt::Destructure { names, value })
+    }
+
+    /// Parses a function or a global exported from the object file, `extern`
+    /// functions are defined elsewhere so they can't be `pub`.
+    fn parse_pub_item(&mut self) -> Result<Item, ()> {
+        self.expect(&TokenKind::Pub)?;
+
+        match self.cur_token.as_ref().map(|token| &token.kind) {
+            Some(TokenKind::Let) => self.parse_global_item(true),
+            Some(_) if self.thread_local_global() => self.parse_global_item(true),
+            Some(TokenKind::Fn) => self.parse_function_item(true, true),
+            Some(_) if self.fn_attr() => self.parse_function_item(true, true),
+            _ => {
+                self.expected(&[&TokenKind::Let, &TokenKind::Fn]);
+
+                Err(())
+            }
+        }
+    }
+
+    /// A global, each thread gets its own copy of it if it's `thread_local`.
+    fn parse_global_item(&mut self, pub_: bool) -> Result<Item, ()> {
+        let thread_local = self.thread_local_global();
+        if thread_local {
+            self.bump();
+        }
         self.expect(&TokenKind::Let)?;
 
-        let (name, _) = self.parse_ident()?;
+        let (name, span) = self.parse_ident()?;
         self.expect(&TokenKind::Colon)?;
 
         let ty = self.parse_type()?;
@@ -511,26 +861,48 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
         Ok(Item::Global(Variable {
             name,
+            span,
             ty,
             value: expr,
+            static_: false,
+            thread_local,
+            pub_,
         }))
     }
 
-    fn parse_function_item(&mut self, func_definition: bool) -> Result<Item, ()> {
+    /// Parses either a function definition or, prefixed with `extern`, a
+    /// declaration of a function defined elsewhere. Attributes go right
+    /// before `fn`.
+    fn parse_function_item(&mut self, func_definition: bool, pub_: bool) -> Result<Item, ()> {
+        let extern_ = self.cur_token_is(&TokenKind::Extern);
+        let mut naked = false;
+        let mut noreturn = false;
+
+        if extern_ {
+            self.bump();
+        }
+
+        while self.fn_attr() {
+            match self.parse_ident()?.0.as_str() {
+                "naked" => naked = true,
+                _ => noreturn = true,
+            }
+        }
+
         self.expect(&TokenKind::Fn)?;
-        let (name, _) = self.parse_ident()?;
+        let (name, span) = self.parse_ident()?;
         self.expect(&TokenKind::LParen)?;
-        let params = self.parse_params(TokenKind::Comma, TokenKind::RParen)?;
+        let (params, variadic) = self.parse_params(TokenKind::Comma, TokenKind::RParen)?;
         self.expect(&TokenKind::Arrow)?;
 
         let ty = self.parse_type()?;
-        let block = if self.cur_token_is(&TokenKind::LBrace) {
-            Some(self.parse_block_stmt()?)
-        } else {
+        let block = if extern_ {
+            self.expect(&TokenKind::Semicolon)?;
+
             None
-        };
+        } else {
+            let block = self.parse_block_stmt()?;
 
-        if let Some(block) = &block {
             if !func_definition {
                 self.diag.error(
                     Diagnostic::IllegalFunctionDefinition,
@@ -539,22 +911,41 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
                 return Err(());
             }
-        } else {
-            self.expect(&TokenKind::Semicolon)?;
-        }
+
+            Some(block)
+        };
 
         Ok(Item::Fn {
+            pub_,
+            naked,
+            noreturn,
             ret_ty: ty,
             name,
+            span,
             params,
+            variadic,
             block,
         })
     }
 
-    fn parse_params(&mut self, delim: TokenKind, end: TokenKind) -> Result<Vec<(String, Ty)>, ()> {
+    /// Parses parameters up to `end`, returns them along with whether the list
+    /// ends with a variadic marker `...`.
+    fn parse_params(
+        &mut self,
+        delim: TokenKind,
+        end: TokenKind,
+    ) -> Result<(Vec<(String, Ty)>, bool), ()> {
         let mut params = Vec::new();
+        let mut variadic = false;
 
         while !self.cur_token_is(&end) {
+            if self.cur_token_is(&TokenKind::Ellipsis) {
+                self.bump();
+                variadic = true;
+
+                break;
+            }
+
             let (name, span) = self.parse_ident()?;
             self.expect(&TokenKind::Colon)?;
             let ty = self.parse_type()?;
@@ -572,7 +963,7 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
 
         self.expect(&end)?;
 
-        Ok(params)
+        Ok((params, variadic))
     }
 
     fn parse_ident_expr(&mut self) -> Result<Expr, ()> {
@@ -598,7 +989,7 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         let mut fields = Vec::new();
 
         while !self.cur_token_is(&TokenKind::RBrace) {
-            let (field, _) = self.parse_ident()?;
+            let (field, span) = self.parse_ident()?;
             self.expect(&TokenKind::Colon)?;
             let expr = self.parse_expr(Precedence::Lowest)?;
 
@@ -606,7 +997,11 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                 self.expect(&TokenKind::Comma)?;
             }
 
-            fields.push((field, expr));
+            if fields.iter().any(|(field_name, _)| field_name == &field) {
+                self.diag.error(Diagnostic::RepeatingField(field), span);
+            } else {
+                fields.push((field, expr));
+            }
         }
 
         let end = self.expect(&TokenKind::RBrace)?;
@@ -639,13 +1034,59 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         }
     }
 
+    /// A character literal is the `u8` value of its ASCII character.
+    fn parse_char_lit_expr(&mut self) -> Result<Expr, ()> {
+        match self.cur_token.clone() {
+            Some(Token {
+                kind: TokenKind::Char(ch),
+                span,
+            }) => {
+                self.bump();
+
+                Ok(Expr {
+                    kind: ExprKind::Lit(ExprLit::Suffixed(ch.into(), Ty::UInt(UintTy::U8))),
+                    span,
+                })
+            }
+            _ => {
+                self.expected(&[&TokenKind::Char(Default::default())]);
+                self.bump();
+
+                Err(())
+            }
+        }
+    }
+
     fn parse_int_lit_expr(&mut self) -> Result<Expr, ()> {
+        self.parse_signed_int_lit_expr(false)
+    }
+
+    /// Parses an integer literal which is negated if `negative`, a minus sign
+    /// widens the range of a signed suffix by one, like `-128i8`.
+    fn parse_signed_int_lit_expr(&mut self, negative: bool) -> Result<Expr, ()> {
+        let suffix = match self.cur_token.as_ref().map(|token| &token.kind) {
+            Some(TokenKind::Integer(lit)) => split_int_lit_suffix(lit).1.map(int_lit_suffix_ty),
+            _ => None,
+        };
         let (lit, span) = self.parse_int_lit()?;
 
-        Ok(Expr {
-            kind: ExprKind::Lit(ExprLit::UInt(lit)),
-            span,
-        })
+        match suffix {
+            Some(ty) => {
+                if !ty.fits_int_lit(lit, negative) {
+                    self.diag
+                        .error(Diagnostic::IntLitOutOfRange(ty.to_string()), span.clone());
+                }
+
+                Ok(Expr {
+                    kind: ExprKind::Lit(ExprLit::Suffixed(lit, ty)),
+                    span,
+                })
+            }
+            None => Ok(Expr {
+                kind: ExprKind::Lit(ExprLit::UInt(lit)),
+                span,
+            }),
+        }
     }
 
     fn parse_null_expr(&mut self) -> Result<Expr, ()> {
@@ -668,7 +1109,7 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                 })
             }
             Some(TokenKind::False) => {
-                let span = self.expect(&TokenKind::True)?;
+                let span = self.expect(&TokenKind::False)?;
 
                 Ok(Expr {
                     kind: ExprKind::Lit(ExprLit::Bool(false)),
@@ -711,13 +1152,23 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         self.expect(&TokenKind::LParen)?;
 
         let mut tokens = Vec::new();
+        // Parentheses inside of the invocation, like in `foo!(bar())`, don't end it
+        let mut depth = 0;
 
-        while !self.cur_token_is(&TokenKind::RParen) {
-            tokens.push(self.cur_token.clone().ok_or_else(|| {
+        while depth > 0 || !self.cur_token_is(&TokenKind::RParen) {
+            let token = self.cur_token.clone().ok_or_else(|| {
                 self.expected(&[&TokenKind::RParen]);
 
                 ()
-            })?);
+            })?;
+
+            match token.kind {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen => depth -= 1,
+                _ => (),
+            }
+
+            tokens.push(token);
             self.bump();
         }
 
@@ -746,7 +1197,7 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         })?;
 
         Ok(Expr {
-            span: span.to(right.span.clone()),
+            span: left.span.clone().to(right.span.clone()),
             kind: ExprKind::Binary {
                 op,
                 left: Box::new(left),
@@ -756,9 +1207,10 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     }
 
     fn parse_pointer_access_expr(&mut self, left: Expr) -> Result<Expr, ()> {
-        let start = self.expect(&TokenKind::Arrow)?;
+        self.expect(&TokenKind::Arrow)?;
 
         let (field, end) = self.parse_ident()?;
+        let start = left.span.clone();
 
         Ok(Expr {
             kind: ExprKind::Field {
@@ -820,15 +1272,18 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     }
 
     fn parse_cast_expr(&mut self, expr: Expr) -> Result<Expr, ()> {
-        let dummy = self.expect(&TokenKind::As)?;
+        self.expect(&TokenKind::As)?;
+
+        let ty = self.parse_type()?;
+        // Types don't have spans, the cast ends with the last token of the type
+        let end = self.prev_token.as_ref().unwrap().span.clone();
 
         Ok(Expr {
+            span: expr.span.clone().to(end),
             kind: ExprKind::Cast {
                 expr: Box::new(expr),
-                //FIXME: types should also return span
-                ty: self.parse_type()?,
+                ty,
             },
-            span: dummy,
         })
     }
 
@@ -839,7 +1294,12 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
             self.diag
                 .error(Diagnostic::ExpressionInfix(kind), span.clone());
         })?;
-        let expr = self.parse_expr(Precedence::Prefix)?;
+        let expr = match (op, self.cur_token.as_ref().map(|token| &token.kind)) {
+            (UnOp::Negative, Some(TokenKind::Integer(_))) => {
+                self.parse_signed_int_lit_expr(true)?
+            }
+            _ => self.parse_expr(Precedence::Prefix)?,
+        };
 
         Ok(Expr {
             span: span.to(expr.span.clone()),
@@ -865,12 +1325,89 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
         Ok((exprs, end))
     }
 
-    fn parse_grouped_expr(&mut self) -> Result<Expr, ()> {
+    fn parse_inline_asm_expr(&mut self) -> Result<Expr, ()> {
+        let start = self.expect(&TokenKind::Asm)?;
+
         self.expect(&TokenKind::LParen)?;
+
+        let code = match self.cur_token.clone() {
+            Some(Token {
+                kind: TokenKind::String(code),
+                ..
+            }) => {
+                self.bump();
+
+                code
+            }
+            _ => {
+                self.expected(&[&TokenKind::String(Default::default())]);
+
+                return Err(());
+            }
+        };
+        let end = self.expect(&TokenKind::RParen)?;
+
+        Ok(Expr {
+            kind: ExprKind::InlineAsm(code),
+            span: start.to(end),
+        })
+    }
+
+    fn parse_alignof_expr(&mut self) -> Result<Expr, ()> {
+        let start = self.expect(&TokenKind::AlignOf)?;
+
+        self.expect(&TokenKind::LParen)?;
+        let ty = self.parse_type()?;
+        let end = self.expect(&TokenKind::RParen)?;
+
+        Ok(Expr {
+            kind: ExprKind::AlignOf(ty),
+            span: start.to(end),
+        })
+    }
+
+    fn parse_sizeof_expr(&mut self) -> Result<Expr, ()> {
+        let start = self.expect(&TokenKind::SizeOf)?;
+
+        self.expect(&TokenKind::LParen)?;
+        let ty = self.parse_type()?;
+        let end = self.expect(&TokenKind::RParen)?;
+
+        Ok(Expr {
+            kind: ExprKind::SizeOf(ty),
+            span: start.to(end),
+        })
+    }
+
+    /// A parenthesized expression, or a tuple if there are several of them
+    /// separated by commas.
+    fn parse_grouped_expr(&mut self) -> Result<Expr, ()> {
+        let start = self.expect(&TokenKind::LParen)?;
         let expr = self.parse_expr(Precedence::default())?;
-        self.expect(&TokenKind::RParen)?;
 
-        Ok(expr)
+        // The parentheses are part of the expression
+        if self.cur_token_is(&TokenKind::RParen) {
+            let end = self.expect(&TokenKind::RParen)?;
+
+            return Ok(Expr {
+                span: start.to(end),
+                ..expr
+            });
+        }
+
+        let mut items = vec![expr];
+
+        while !self.cur_token_is(&TokenKind::RParen) {
+            self.expect(&TokenKind::Comma)?;
+            items.push(self.parse_expr(Precedence::default())?);
+        }
+
+        let end = self.expect(&TokenKind::RParen)?;
+
+        Ok(Expr {
+            kind: ExprKind::Tuple(items),
+            span: start.to(end),
+        })
     }
 
     fn parse_array_expr(&mut self) -> Result<Expr, ()> {
@@ -900,7 +1437,7 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
                 span,
             }) => {
                 let span = span.clone();
-                let lit = match num_str.parse() {
+                let lit = match parse_int_lit(num_str) {
                     Ok(value) => value,
                     Err(_) => {
                         let span = span.clone();
@@ -954,13 +1491,31 @@ impl<'a, 'src, T: Iterator<Item = Result<Token, Span>>> Parser<'a, 'src, T> {
     }
 }
 
+/// Type of an integer literal suffix along with the largest value it can hold.
+fn int_lit_suffix_ty(suffix: &str) -> Ty {
+    match suffix {
+        "u8" => Ty::UInt(UintTy::U8),
+        "u16" => Ty::UInt(UintTy::U16),
+        "u32" => Ty::UInt(UintTy::U32),
+        "u64" => Ty::UInt(UintTy::U64),
+        "usize" => Ty::UInt(UintTy::Usize),
+        "i8" => Ty::Int(IntTy::I8),
+        "i16" => Ty::Int(IntTy::I16),
+        "i32" => Ty::Int(IntTy::I32),
+        "i64" => Ty::Int(IntTy::I64),
+        "isize" => Ty::Int(IntTy::Isize),
+        // The lexer rejects any other suffix
+        _ => unreachable!(),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::Parser;
+    use super::{Parser, Precedence};
     use crate::{
-        ast::{BinOp, ExprKind, ExprLit, IntTy, Stmt, Ty, UintTy, UnOp, Variable},
+        ast::{BinOp, Expr, ExprKind, ExprLit, Item, Stmt, Ty, UintTy, UnOp, Variable},
         diagnostics::Diagnostics,
-        lexer::Lexer,
+        lexer::{span::Span, Lexer},
     };
 
     #[test]
@@ -1124,4 +1679,493 @@ mod test {
         //    );
         //}
     }
+
+    #[test]
+    fn int_lit_suffix_range() {
+        let tests = [
+            ("fn main() -> void { 255u8; }", false),
+            ("fn main() -> void { 300u8; }", true),
+            ("fn main() -> void { 127i8; }", false),
+            ("fn main() -> void { 128i8; }", true),
+            ("fn main() -> void { 0xffff_ffffu32; }", false),
+            ("fn main() -> void { -128i8; }", false),
+            ("fn main() -> void { -129i8; }", true),
+            ("fn main() -> void { 1 - 128i8; }", true),
+            ("fn main() -> void { -0u8; }", false),
+            ("fn main() -> void { -1u8; }", true),
+            ("fn main() -> void { -9223372036854775808i64; }", false),
+        ];
+
+        for (input, has_errors) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let mut parser = Parser::new(Lexer::new(input), &mut diagnostics);
+
+            parser.parse().unwrap();
+
+            assert_eq!(diagnostics.has_errors(), has_errors, "{input}");
+        }
+    }
+
+    #[test]
+    fn error_recovery() {
+        let tests = [
+            (
+                "fn main() -> u8 { let a: u8 = ; let b: u8 = 1 +; return 0 }",
+                3,
+                1,
+            ),
+            (
+                "fn main() -> u8 { if a == 1 { b = ; } c(; let = 5; return 0; }",
+                3,
+                1,
+            ),
+            (
+                "fn f( -> u8 { return 0; } struct { } fn main() -> u8 { return 0 }",
+                3,
+                1,
+            ),
+            ("fn main() -> u8 { let x: u8 = 1", 2, 0),
+            // `b {` starts a struct expression, the error is inside of its braces
+            (
+                "fn main() -> u8 { if a < b { return 1; } return 0; }",
+                1,
+                1,
+            ),
+            (
+                "fn main() -> u8 { while true { if a { let = 1; } } return 0; } fn f() -> u8 { return 1; }",
+                1,
+                2,
+            ),
+        ];
+
+        for (input, errors, items) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let parsed = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse()
+                .unwrap();
+
+            assert_eq!(diagnostics.error_count(), errors, "{input}: {diagnostics}");
+            assert_eq!(parsed.len(), items, "{input}");
+        }
+    }
+
+    #[test]
+    fn return_outside_fn() {
+        let input = "return 5;\nfn main() -> u8 { return 0; }";
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert!(diagnostics.has_errors());
+        assert!(
+            diagnostics
+                .to_string()
+                .contains("`return` outside of function"),
+            "{diagnostics}"
+        );
+    }
+
+    #[test]
+    fn union_item() {
+        let input = "union Bits { word: u32; bytes: [4]u8; }";
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert!(
+            matches!(&items[..], [Item::Union { name, fields, .. }] if name == "Bits" && fields.len() == 2),
+            "{items:?}"
+        );
+    }
+
+    #[test]
+    fn duplicate_param() {
+        let input = "fn add(x: u8, y: u8, x: u16) -> u8 { return x + y; }";
+        let mut diagnostics = Diagnostics::new(input);
+        Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert_eq!(diagnostics.error_count(), 1, "{diagnostics}");
+        assert!(
+            diagnostics.to_string().contains("duplicate parameter `x`"),
+            "{diagnostics}"
+        );
+    }
+
+    #[test]
+    fn restrict_ptr() {
+        let tests = [
+            "*restrict u8",
+            "**restrict i32",
+            "*restrict *restrict Foo",
+            "*u8",
+        ];
+
+        for input in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let ty = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse_type()
+                .unwrap();
+
+            assert!(!diagnostics.has_errors(), "{input}: {diagnostics}");
+            assert_eq!(ty.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn defer_stmt() {
+        let input = "defer f(); { defer g(); 1 };";
+        let mut diagnostics = Diagnostics::new(input);
+        let stmts = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse_stmts()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert!(matches!(stmts[0], Stmt::Defer(_)), "{stmts:?}");
+        match &stmts[1] {
+            Stmt::Expr(Expr {
+                kind: ExprKind::Block(block),
+                ..
+            }) => {
+                assert!(matches!(block.stmts[..], [Stmt::Defer(_)]), "{block:?}");
+                assert!(block.expr.is_some(), "{block:?}");
+            }
+            stmt => panic!("expected a block, got {stmt:?}"),
+        }
+    }
+
+    #[test]
+    fn packed_struct_item() {
+        let tests = [
+            ("packed struct Foo { a: u8; }", Ok(true)),
+            ("struct Foo { a: u8; }", Ok(false)),
+            ("packed union Foo { a: u8; }", Err(())),
+        ];
+
+        for (input, expected) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let items = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse()
+                .unwrap();
+
+            match expected {
+                Ok(packed) => {
+                    assert!(!diagnostics.has_errors(), "{input}: {diagnostics}");
+                    assert!(
+                        matches!(&items[..], [Item::Struct { packed: p, .. }] if *p == packed),
+                        "{input}: {items:?}"
+                    );
+                }
+                Err(()) => assert!(diagnostics.has_errors(), "{input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let tests = [
+            ("f(a, b)", true),
+            ("f(a, b,)", true),
+            ("f()", true),
+            ("f(,)", false),
+            ("f(a,,)", false),
+            ("[1, 2, 3]", true),
+            ("[1, 2, 3,]", true),
+            ("[]", true),
+            ("[,]", false),
+            ("Foo { a: 1, b: 2 }", true),
+            ("Foo { a: 1, b: 2, }", true),
+            ("Foo {}", true),
+            ("Foo { , }", false),
+        ];
+
+        for (input, ok) in tests {
+            let source = format!("fn main() -> void {{ {input}; }}");
+            let mut diagnostics = Diagnostics::new(&source);
+            let result = Parser::new(Lexer::new(&source), &mut diagnostics).parse();
+
+            assert_eq!(
+                result.is_ok() && !diagnostics.has_errors(),
+                ok,
+                "{input}: {diagnostics}"
+            );
+        }
+
+        let input = "fn f(a: u8, b: u8,) -> void {}";
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert!(
+            matches!(&items[..], [Item::Fn { params, .. }] if params.len() == 2),
+            "{items:?}"
+        );
+    }
+
+    #[test]
+    fn fn_attrs() {
+        let tests = [
+            ("naked fn f() -> void {}", Some((true, false))),
+            ("noreturn fn f() -> void {}", Some((false, true))),
+            ("naked noreturn fn f() -> void {}", Some((true, true))),
+            (
+                "extern noreturn fn exit(status: i32) -> void;",
+                Some((false, true)),
+            ),
+            ("pub naked fn f() -> void {}", Some((true, false))),
+            ("fn f() -> void {}", Some((false, false))),
+            ("naked noreturn -> void {}", None),
+        ];
+
+        for (input, expected) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let items = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse()
+                .unwrap();
+
+            match expected {
+                Some((naked, noreturn)) => {
+                    assert!(!diagnostics.has_errors(), "{input}: {diagnostics}");
+                    assert!(
+                        matches!(
+                            &items[..],
+                            [Item::Fn { naked: n, noreturn: r, .. }] if *n == naked && *r == noreturn
+                        ),
+                        "{input}: {items:?}"
+                    );
+                }
+                None => assert!(diagnostics.has_errors(), "{input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn type_alias_item() {
+        let input = "type Bytes = *[4]u8;";
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert_eq!(
+            items,
+            vec![Item::TypeAlias {
+                name: String::from("Bytes"),
+                span: Span { start: 5, end: 10 },
+                ty: Ty::Ptr(
+                    Box::new(Ty::Array {
+                        ty: Box::new(Ty::UInt(UintTy::U8)),
+                        len: 4,
+                    }),
+                    false,
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn let_without_type() {
+        let tests = [
+            ("fn main() -> void { let x = 5; }", false),
+            ("fn main() -> void { let x: u8; }", false),
+            ("fn main() -> void { let x; }", true),
+            ("fn main() -> void { static let x; }", true),
+        ];
+
+        for (input, error) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse()
+                .unwrap();
+
+            assert_eq!(diagnostics.has_errors(), error, "{input}: {diagnostics}");
+            if error {
+                assert!(
+                    diagnostics.to_string().contains("type hint required"),
+                    "{diagnostics}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn block_expr() {
+        let tests = [
+            ("{ let t: u8 = f(); t * 2 }", 1, true),
+            ("{ f(); g(); }", 2, false),
+            ("{ f(); { 1 } }", 1, true),
+            ("{}", 0, false),
+        ];
+
+        for (input, stmts, value) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let expr = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse_expr(Precedence::default())
+                .unwrap();
+
+            assert!(!diagnostics.has_errors(), "{input}: {diagnostics}");
+            match expr.kind {
+                ExprKind::Block(block) => {
+                    assert_eq!(block.stmts.len(), stmts, "{input}");
+                    assert_eq!(block.expr.is_some(), value, "{input}");
+                }
+                kind => panic!("{input}: expected a block, got {kind:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn block_stmt_without_semicolon() {
+        let input = "fn main() -> u8 { let x: u8 = 1; x }";
+        let mut diagnostics = Diagnostics::new(input);
+        let _ = Parser::new(Lexer::new(input), &mut diagnostics).parse();
+
+        // Only block expressions evaluate to their last expression
+        assert!(diagnostics.has_errors());
+    }
+
+    #[test]
+    fn label_and_goto() {
+        let input = "start: goto start; x: y;";
+        let mut diagnostics = Diagnostics::new(input);
+        let stmts = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse_stmts()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+        assert_eq!(
+            &stmts[..3],
+            &[
+                Stmt::Label("start".to_owned()),
+                Stmt::Goto("start".to_owned()),
+                Stmt::Label("x".to_owned()),
+            ]
+        );
+        assert!(
+            matches!(&stmts[3], Stmt::Expr(expr) if expr.kind == ExprKind::Ident("y".to_owned()))
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        let input = "fn f() -> (u8, (i32, bool)) { let (a, b) = (1, (2, true)); return (a, (b)); }";
+        let mut diagnostics = Diagnostics::new(input);
+        let items = Parser::new(Lexer::new(input), &mut diagnostics)
+            .parse()
+            .unwrap();
+
+        assert!(!diagnostics.has_errors(), "{diagnostics}");
+
+        let Item::Fn {
+            ret_ty,
+            block: Some(block),
+            ..
+        } = &items[0]
+        else {
+            panic!("{items:?}");
+        };
+
+        assert_eq!(ret_ty.to_string(), "(u8, (i32, bool))");
+        assert!(matches!(
+            &block.stmts[0],
+            Stmt::Destructure { names, value: Expr { kind: ExprKind::Tuple(items), .. } }
+                if names.len() == 2 && matches!(items[1].kind, ExprKind::Tuple(_))
+        ));
+        // A single parenthesized expression isn't a tuple
+        assert!(matches!(
+            &block.stmts[1],
+            Stmt::Return(Some(Expr { kind: ExprKind::Tuple(items), .. }))
+                if items[1].kind == ExprKind::Ident("b".to_owned())
+        ));
+    }
+
+    #[test]
+    fn pub_items() {
+        let tests = [
+            ("pub fn f() -> void {}", false),
+            ("pub let x: u8 = 1;", false),
+            ("pub extern fn f() -> void;", true),
+            ("pub struct Foo { x: u8 }", true),
+        ];
+
+        for (input, expected_error) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let items = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse()
+                .unwrap();
+
+            assert_eq!(diagnostics.has_errors(), expected_error, "{input}");
+
+            if !expected_error {
+                assert!(
+                    matches!(
+                        &items[..],
+                        [Item::Fn { pub_: true, .. }] | [Item::Global(Variable { pub_: true, .. })]
+                    ),
+                    "{items:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn for_header() {
+        let tests = [
+            ("for let i: u8 = 0; i < 5; i = i + 1 {}", true, true, true),
+            ("for i = 0; i < 5; {}", true, true, false),
+            ("for ; i < 5; i = i + 1 {}", false, true, true),
+            ("for ;; {}", false, false, false),
+        ];
+
+        for (input, has_initializer, has_condition, has_increment) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let stmts = Parser::new(Lexer::new(input), &mut diagnostics)
+                .parse_stmts()
+                .unwrap();
+
+            assert!(!diagnostics.has_errors(), "{diagnostics}");
+            assert!(
+                matches!(
+                    &stmts[..],
+                    [Stmt::For { initializer, condition, increment, .. }]
+                        if initializer.is_some() == has_initializer
+                            && condition.is_some() == has_condition
+                            && increment.is_some() == has_increment
+                ),
+                "{input}: {stmts:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn struct_expr_repeating_field() {
+        let tests = [
+            (
+                "fn main() -> void { let foo: Foo = Foo { x: 1, y: 2 }; }",
+                false,
+            ),
+            ("fn main() -> void { let foo: Foo = Foo { y: 2 }; }", false),
+            (
+                "fn main() -> void { let foo: Foo = Foo { x: 1, x: 2 }; }",
+                true,
+            ),
+        ];
+
+        for (input, has_errors) in tests {
+            let mut diagnostics = Diagnostics::new(input);
+            let mut parser = Parser::new(Lexer::new(input), &mut diagnostics);
+
+            parser.parse().unwrap();
+
+            assert_eq!(diagnostics.has_errors(), has_errors, "{input}");
+        }
+    }
 }
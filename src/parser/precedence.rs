@@ -24,7 +24,7 @@ impl From<&TokenKind> for Precedence {
     fn from(value: &TokenKind) -> Self {
         match value {
             TokenKind::Plus | TokenKind::Minus => Self::Sum,
-            TokenKind::Asterisk | TokenKind::Slash => Self::Product,
+            TokenKind::Asterisk | TokenKind::Slash | TokenKind::Percent => Self::Product,
             TokenKind::LessThan
             | TokenKind::LessEqual
             | TokenKind::GreaterThan
@@ -1,8 +1,48 @@
 use crate::{
-    ast::IntTy,
-    ir::{Ir, Item, Node, OrderedMap, Ty},
-    Context,
+    ast::{IntTy, UintTy},
+    ir::{Ir, Item, Node, OrderedMap, Ty, TyArray},
+    lexer::span::Span,
 };
+use bumpalo::Bump;
+use thiserror::Error;
+
+/// Error in a program which parses fine, like a type mismatch or a name
+/// which isn't declared
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{message}")]
+pub struct TypeError {
+    pub message: String,
+    /// Code the error is about, if it's known
+    pub span: Option<Span>,
+    /// Index of the file the span is in, when several files are compiled
+    /// together
+    pub file: Option<usize>,
+}
+
+impl TypeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+            file: None,
+        }
+    }
+
+    pub fn at(message: impl Into<String>, span: &Span) -> Self {
+        Self {
+            message: message.into(),
+            span: Some(span.clone()),
+            file: None,
+        }
+    }
+
+    /// Tells the error is in `file`, unless it knows already
+    pub fn in_file(mut self, file: usize) -> Self {
+        self.file.get_or_insert(file);
+
+        self
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Id(usize);
@@ -49,17 +89,48 @@ enum Constraint<'ir> {
         field_ty: Id,
         field: &'ir str,
     },
+    Array {
+        elem: Id,
+        items: Vec<Id>,
+    },
+    Cast {
+        expr: Id,
+        ty: Id,
+    },
+    /// `base[index]` evaluating to an element of type `elem`
+    Index {
+        elem: Id,
+        base: Id,
+        index: Id,
+    },
+    Cmp(Id, Id),
+    VariadicArg(Id),
+    Int(Id),
+    /// Unsuffixed integer literal, negated if `negative`, which has to be in
+    /// range of the type it gets
+    IntLit {
+        id: Id,
+        lit: u64,
+        negative: bool,
+        span: Span,
+    },
+    Printable(Id),
+    /// Type the variable gets if nothing else decides it, weaker than any
+    /// other default
+    Fallback(Id, &'ir Ty<'ir>),
 }
 
 #[derive(Debug)]
 pub struct TyProblem<'ir> {
+    allocator: &'ir Bump,
     ty_vars: Vec<TyVar<'ir>>,
     constraints: Vec<Constraint<'ir>>,
 }
 
 impl<'ir> TyProblem<'ir> {
-    pub fn new() -> Self {
+    pub fn new(allocator: &'ir Bump) -> Self {
         Self {
+            allocator,
             ty_vars: Vec::new(),
             constraints: Vec::new(),
         }
@@ -111,8 +182,50 @@ impl<'ir> TyProblem<'ir> {
         });
     }
 
-    fn unify(&mut self, lhs: TyVar<'ir>, rhs: TyVar<'ir>) -> bool {
-        match (lhs, rhs) {
+    pub fn array(&mut self, elem: Id, items: Vec<Id>) {
+        self.constraints.push(Constraint::Array { elem, items });
+    }
+
+    pub fn cast(&mut self, expr: Id, ty: Id) {
+        self.constraints.push(Constraint::Cast { expr, ty });
+    }
+
+    pub fn index(&mut self, elem: Id, base: Id, index: Id) {
+        self.constraints
+            .push(Constraint::Index { elem, base, index });
+    }
+
+    pub fn cmp(&mut self, lhs: Id, rhs: Id) {
+        self.constraints.push(Constraint::Cmp(lhs, rhs));
+    }
+
+    pub fn variadic_arg(&mut self, arg: Id) {
+        self.constraints.push(Constraint::VariadicArg(arg));
+    }
+
+    pub fn int(&mut self, id: Id) {
+        self.constraints.push(Constraint::Int(id));
+    }
+
+    pub fn int_lit(&mut self, id: Id, lit: u64, negative: bool, span: Span) {
+        self.constraints.push(Constraint::IntLit {
+            id,
+            lit,
+            negative,
+            span,
+        });
+    }
+
+    pub fn printable(&mut self, id: Id) {
+        self.constraints.push(Constraint::Printable(id));
+    }
+
+    pub fn fallback(&mut self, id: Id, ty: &'ir Ty<'ir>) {
+        self.constraints.push(Constraint::Fallback(id, ty));
+    }
+
+    fn unify(&mut self, lhs: TyVar<'ir>, rhs: TyVar<'ir>) -> Result<bool, TypeError> {
+        Ok(match (lhs, rhs) {
             (TyVar::Infer(lhs), TyVar::Infer(rhs)) => {
                 self.eq(lhs, rhs);
 
@@ -124,47 +237,137 @@ impl<'ir> TyProblem<'ir> {
                 true
             }
             (TyVar::Typed(lhs), TyVar::Typed(rhs)) => match (lhs, rhs) {
-                (Ty::Ptr(lhs), Ty::Ptr(rhs)) => self.unify((*lhs).into(), (*rhs).into()),
-                _ => {
-                    assert_eq!(lhs, rhs, "Failed to unify {lhs} and {rhs}");
+                (Ty::Ptr(lhs), Ty::Ptr(rhs)) => self.unify((*lhs).into(), (*rhs).into())?,
+                (Ty::Ptr(_), Ty::Null) | (Ty::Null, Ty::Ptr(_)) => false,
+                (Ty::Array(lhs), Ty::Array(rhs)) => {
+                    if lhs.len != rhs.len {
+                        return Err(TypeError::new(format!(
+                            "Failed to unify arrays of {} and {} elements",
+                            lhs.len, rhs.len
+                        )));
+                    }
 
-                    false
+                    self.unify(lhs.ty.into(), rhs.ty.into())?
+                }
+                (Ty::Tuple(lhs), Ty::Tuple(rhs)) => {
+                    if lhs.len() != rhs.len() {
+                        return Err(TypeError::new(format!(
+                            "Tuples of {} and {} elements can't be unified",
+                            lhs.len(),
+                            rhs.len()
+                        )));
+                    }
+
+                    let mut progress = false;
+
+                    for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+                        progress |= self.unify((*lhs).into(), (*rhs).into())?;
+                    }
+
+                    progress
                 }
+                _ if lhs == rhs => false,
+                _ => return Err(TypeError::new(format!("Failed to unify {lhs} and {rhs}"))),
             },
+        })
+    }
+
+    /// Promotes integer type `ty` with the type of the other operand. If the
+    /// other operand isn't typed yet, it's an integer literal and it adopts `ty`.
+    /// An operand that isn't an integer has to be of the same type, like in
+    /// any other expression.
+    fn promote(&mut self, ty: &'ir Ty<'ir>, other: Id) -> Result<&'ir Ty<'ir>, TypeError> {
+        match self.get_ty_var(other).ty() {
+            Some(other) => match ty.common_ty(other) {
+                Some(ty) => Ok(ty),
+                None => {
+                    self.unify(TyVar::Typed(ty), TyVar::Typed(other))?;
+
+                    Ok(ty)
+                }
+            },
+            None => {
+                *self.get_ty_var_mut(other) = TyVar::Typed(ty);
+
+                Ok(ty)
+            }
         }
     }
 
-    fn apply_constraints(&mut self, ir: &Ir<'ir>) -> bool {
-        let mut constraints = std::mem::take(&mut self.constraints);
+    fn apply_constraints(&mut self, ir: &Ir<'ir>) -> Result<bool, TypeError> {
+        let constraints = std::mem::take(&mut self.constraints);
+        let mut retained = Vec::new();
         let mut progress = false;
 
-        constraints.retain(|constraint| match constraint {
+        for constraint in constraints {
+            if self.apply_constraint(ir, &constraint, &mut progress)? {
+                retained.push(constraint);
+            }
+        }
+        self.constraints.append(&mut retained);
+
+        Ok(progress)
+    }
+
+    /// Applies the constraint as far as the types known so far allow, returns
+    /// whether it has to be applied again once more of them are known.
+    fn apply_constraint(
+        &mut self,
+        ir: &Ir<'ir>,
+        constraint: &Constraint<'ir>,
+        progress: &mut bool,
+    ) -> Result<bool, TypeError> {
+        Ok(match constraint {
             Constraint::Eq(lhs, rhs) => {
-                progress |=
-                    self.unify(self.get_ty_var(*lhs).clone(), self.get_ty_var(*rhs).clone());
+                *progress |=
+                    self.unify(self.get_ty_var(*lhs).clone(), self.get_ty_var(*rhs).clone())?;
 
                 false
             }
             Constraint::BinAdd { expr, lhs, rhs } => {
-                let (lhs, rhs) = if let Some(Ty::Ptr(_)) = self.get_ty_var(*rhs).ty() {
-                    (rhs, lhs)
-                } else {
-                    (lhs, rhs)
+                let (lhs, rhs) = match (self.get_ty_var(*lhs).ty(), self.get_ty_var(*rhs).ty()) {
+                    (_, Some(Ty::Ptr(_) | Ty::Array(_))) | (None, Some(_)) => (rhs, lhs),
+                    _ => (lhs, rhs),
                 };
 
                 if let Some(ty) = self.get_ty_var(*lhs).ty() {
                     match ty {
-                        Ty::Ptr(_) => {
-                            *self.get_ty_var_mut(*rhs) = TyVar::Typed(&Ty::Int(IntTy::Isize));
-                            *self.get_ty_var_mut(*expr) = TyVar::Typed(&Ty::Int(IntTy::Isize));
-                            progress |= true;
+                        // Arrays decay into a pointer to their first element
+                        Ty::Ptr(_) | Ty::Array(_) => {
+                            let ty = match ty {
+                                Ty::Array(TyArray { ty, .. }) => ir.alloc_ty(Ty::Ptr(ty)),
+                                ty => ty,
+                            };
+
+                            sized_pointee(ty)?;
+
+                            match self.get_ty_var(*rhs).ty() {
+                                Some(offset) if !offset.int() => {
+                                    return Err(TypeError::new(format!(
+                                        "Can't offset a pointer by a value of type {offset}"
+                                    )));
+                                }
+                                Some(_) => (),
+                                None => {
+                                    *self.get_ty_var_mut(*rhs) =
+                                        TyVar::Typed(&Ty::Int(IntTy::Isize))
+                                }
+                            }
+
+                            self.unify(self.get_ty_var(*expr).clone(), TyVar::Typed(ty))?;
+                            *progress |= true;
                         }
                         Ty::Int(_) | Ty::UInt(_) => {
-                            *self.get_ty_var_mut(*rhs) = TyVar::Typed(ty);
+                            let ty = self.promote(ty, *rhs)?;
+
                             *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
-                            progress |= true;
+                            *progress |= true;
+                        }
+                        _ => {
+                            return Err(TypeError::new(format!(
+                                "Expected an integer or a pointer, got {ty}"
+                            )))
                         }
-                        _ => unreachable!("Bad type, expected integer or pointer, got {}", ty),
                     };
 
                     false
@@ -182,15 +385,43 @@ impl<'ir> TyProblem<'ir> {
                 };
 
                 match (self.get_ty_var(*lhs).ty(), self.get_ty_var(*rhs).ty()) {
-                    (Some(Ty::Ptr(_)), Some(Ty::Ptr(_))) => {
+                    (Some(lhs @ Ty::Ptr(_)), Some(rhs @ Ty::Ptr(_))) => {
+                        sized_pointee(lhs)?;
+                        sized_pointee(rhs)?;
                         *self.get_ty_var_mut(*expr) = TyVar::Typed(&Ty::Int(IntTy::Isize));
-                        progress |= true;
+                        *progress |= true;
 
                         false
                     }
                     (Some(ty @ Ty::Ptr(_)), Some(Ty::Int(_) | Ty::UInt(_))) => {
+                        sized_pointee(ty)?;
+                        *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    // A literal offset is signed like in `BinAdd`
+                    (Some(ty @ Ty::Ptr(_)), None) => {
+                        sized_pointee(ty)?;
+                        *self.get_ty_var_mut(*rhs) = TyVar::Typed(&Ty::Int(IntTy::Isize));
+                        *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    (Some(ty @ (Ty::Int(_) | Ty::UInt(_))), _) => {
+                        let ty = self.promote(ty, *rhs)?;
+
+                        *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    (None, Some(ty @ (Ty::Int(_) | Ty::UInt(_)))) => {
+                        let ty = self.promote(ty, *lhs)?;
+
                         *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
-                        progress |= true;
+                        *progress |= true;
 
                         false
                     }
@@ -198,7 +429,7 @@ impl<'ir> TyProblem<'ir> {
                         self.eq(*expr, *lhs);
                         self.eq(*lhs, *rhs);
 
-                        progress |= true;
+                        *progress |= true;
 
                         false
                     }
@@ -210,48 +441,305 @@ impl<'ir> TyProblem<'ir> {
                 field_ty,
                 field,
             } => match self.get_ty_var(*expr) {
-                TyVar::Typed(ty) => match ty {
-                    Ty::Struct(id) => match ir.get_node(*id) {
-                        Node::Item(item) => match item {
-                            Item::Struct(fields) => {
-                                let ty = OrderedMap::get(fields, field).unwrap();
+                TyVar::Typed(ty @ Ty::Struct(id, name)) => match ir.get_node(*id) {
+                    Node::Item(Item::Struct(fields, _) | Item::Union(fields)) => {
+                        let Some(ty) = OrderedMap::get(fields, field) else {
+                            return Err(TypeError::new(format!("`{name}` has no field `{field}`")));
+                        };
 
-                                *self.get_ty_var_mut(*field_ty) = TyVar::Typed(ty);
+                        *self.get_ty_var_mut(*field_ty) = TyVar::Typed(ty);
+                        *progress |= true;
 
-                                progress |= true;
-
-                                false
-                            }
-                            _ => unreachable!(),
-                        },
-                        _ => unreachable!(),
-                    },
-                    _ => unreachable!(),
+                        false
+                    }
+                    _ => unreachable!("{ty} isn't a struct"),
                 },
+                TyVar::Typed(ty) => {
+                    return Err(TypeError::new(format!(
+                        "Can't access field `{field}` of a value of type {ty}"
+                    )))
+                }
                 TyVar::Infer(_) => true,
             },
-        });
-        self.constraints.append(&mut constraints);
+            Constraint::Array { elem, items } => {
+                let mut elem_ty = self.get_ty_var(*elem).ty();
+
+                if elem_ty.is_none() {
+                    for ty in items.iter().filter_map(|item| self.get_ty_var(*item).ty()) {
+                        elem_ty = Some(match elem_ty {
+                            Some(lhs) if lhs != ty => lhs.common_ty(ty).ok_or_else(|| {
+                                TypeError::new(format!(
+                                    "Array items of types {lhs} and {ty} have no common type"
+                                ))
+                            })?,
+                            _ => ty,
+                        });
+                    }
+                }
 
-        progress
-    }
+                match elem_ty {
+                    Some(elem_ty) => {
+                        for item in items {
+                            match self.get_ty_var(*item).ty() {
+                                Some(ty) if !ty.coercible_to(elem_ty) => {
+                                    return Err(TypeError::new(format!(
+                                        "Array item of type {ty} can't be stored in an array of {elem_ty}"
+                                    )));
+                                }
+                                Some(_) => (),
+                                None => *self.get_ty_var_mut(*item) = TyVar::Typed(elem_ty),
+                            }
+                        }
 
-    pub fn solve(&mut self, ir: &Ir<'ir>) {
-        loop {
-            if !self.apply_constraints(ir) {
-                break;
+                        *self.get_ty_var_mut(*elem) = TyVar::Typed(elem_ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Constraint::Cast { expr, ty } => {
+                // Whether an array can be cast depends on its element type too
+                let expr = self
+                    .get_ty_var(*expr)
+                    .ty()
+                    .and_then(|ty| self.try_resolve(ty));
+                let ty = self
+                    .get_ty_var(*ty)
+                    .ty()
+                    .and_then(|ty| self.try_resolve(ty));
+
+                match (expr, ty) {
+                    (Some(expr), Some(ty)) if !expr.castable_to(ty) => {
+                        return Err(TypeError::new(format!("Can't cast {expr} into {ty}")));
+                    }
+                    (Some(_), Some(_)) => false,
+                    _ => true,
+                }
             }
+            Constraint::Index { elem, base, index } => match self.get_ty_var(*base).ty() {
+                Some(base) => {
+                    let Some(ty) = base.elem() else {
+                        return Err(TypeError::new(format!(
+                            "Can't index a value of type {base}"
+                        )));
+                    };
+
+                    // `p[i]` is `*(p + i)`
+                    sized_pointee(base)?;
+
+                    match self.get_ty_var(*index).ty() {
+                        Some(index) if !index.int() => {
+                            return Err(TypeError::new(format!(
+                                "Can't index with a value of type {index}"
+                            )));
+                        }
+                        Some(_) => (),
+                        None => *self.get_ty_var_mut(*index) = TyVar::Typed(&Ty::Int(IntTy::Isize)),
+                    }
+
+                    self.unify(self.get_ty_var(*elem).clone(), ty.into())?;
+                    *progress |= true;
+
+                    false
+                }
+                None => true,
+            },
+            Constraint::Cmp(lhs, rhs) => {
+                match (self.get_ty_var(*lhs).ty(), self.get_ty_var(*rhs).ty()) {
+                    (Some(Ty::Ptr(lhs)), Some(Ty::Ptr(rhs))) => {
+                        *progress |= self.unify((*lhs).into(), (*rhs).into())?;
+
+                        false
+                    }
+                    (Some(Ty::Ptr(_)), Some(Ty::Null)) | (Some(Ty::Null), Some(Ty::Ptr(_))) => {
+                        false
+                    }
+                    (Some(Ty::Bool), Some(Ty::Bool)) => false,
+                    (Some(lhs @ (Ty::Int(_) | Ty::UInt(_))), Some(rhs))
+                        if lhs.common_ty(rhs).is_some() =>
+                    {
+                        false
+                    }
+                    (Some(ty @ (Ty::Int(_) | Ty::UInt(_))), None) => {
+                        *self.get_ty_var_mut(*rhs) = TyVar::Typed(ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    (None, Some(ty @ (Ty::Int(_) | Ty::UInt(_)))) => {
+                        *self.get_ty_var_mut(*lhs) = TyVar::Typed(ty);
+                        *progress |= true;
+
+                        false
+                    }
+                    (Some(lhs), Some(rhs)) => {
+                        return Err(TypeError::new(format!("Can't compare {lhs} with {rhs}")))
+                    }
+                    _ => true,
+                }
+            }
+            Constraint::VariadicArg(arg) => match self.get_ty_var(*arg).ty() {
+                Some(ty @ Ty::Void) => {
+                    return Err(TypeError::new(format!(
+                        "Can't pass a value of type {ty} as an argument"
+                    )))
+                }
+                Some(_) => false,
+                None => true,
+            },
+            Constraint::Int(id) => match self.get_ty_var(*id).ty() {
+                Some(ty) if !ty.int() => {
+                    return Err(TypeError::new(format!("Expected an integer, got {ty}")))
+                }
+                Some(_) => false,
+                None => true,
+            },
+            Constraint::IntLit {
+                id,
+                lit,
+                negative,
+                span,
+            } => match self.get_ty_var(*id).ty() {
+                Some(ty) if ty.int() && !ty.fits_int_lit(*lit, *negative) => {
+                    let sign = if *negative { "-" } else { "" };
+                    let message = match Ty::int_lit(*lit, *negative) {
+                        Some(wider) => format!(
+                            "Literal `{sign}{lit}` is out of range for {ty}, it needs {wider}"
+                        ),
+                        None => format!("Literal `{sign}{lit}` doesn't fit in any integer type"),
+                    };
+
+                    return Err(TypeError::at(message, span));
+                }
+                Some(_) => false,
+                None => true,
+            },
+            Constraint::Printable(id) => match self.get_ty_var(*id).ty() {
+                Some(ty)
+                    if !ty.int()
+                        && !matches!(ty, Ty::Ptr(Ty::Int(IntTy::I8) | Ty::UInt(UintTy::U8))) =>
+                {
+                    return Err(TypeError::new(format!("Can't print a value of type {ty}")))
+                }
+                Some(_) => false,
+                None => true,
+            },
+            Constraint::Fallback(id, _) => self.get_ty_var(*id).ty().is_none(),
+        })
+    }
+
+    /// Expressions which are constrained only by a cast, like `1 as u8`, get
+    /// the type they're casted into. Integer literals passed as variadic
+    /// arguments default to `i32`, like `int` in C, and the ones printed by
+    /// `print!` or only required to be integers, like the value of `itoa!`, to
+    /// `i64`. Fallbacks, like the one of a local without a type, apply only
+    /// once no other default is left, since those may decide the type too.
+    fn apply_defaults(&mut self) -> bool {
+        let mut defaults = self
+            .constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                Constraint::Cast { expr, ty } => match self.get_ty_var(*expr) {
+                    TyVar::Infer(_) => self.get_ty_var(*ty).ty().map(|ty| (*expr, ty)),
+                    TyVar::Typed(_) => None,
+                },
+                Constraint::VariadicArg(arg) => match self.get_ty_var(*arg) {
+                    TyVar::Infer(_) => Some((*arg, &Ty::Int(IntTy::I32))),
+                    TyVar::Typed(_) => None,
+                },
+                Constraint::Printable(arg) | Constraint::Int(arg) => match self.get_ty_var(*arg) {
+                    TyVar::Infer(_) => Some((*arg, &Ty::Int(IntTy::I64))),
+                    TyVar::Typed(_) => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if defaults.is_empty() {
+            defaults = self
+                .constraints
+                .iter()
+                .filter_map(|constraint| match constraint {
+                    Constraint::Fallback(id, ty) => match self.get_ty_var(*id) {
+                        TyVar::Infer(_) => Some((*id, *ty)),
+                        TyVar::Typed(_) => None,
+                    },
+                    // Comparing two literals, the other operand follows once one is known
+                    Constraint::Cmp(lhs, rhs) => {
+                        match (self.get_ty_var(*lhs), self.get_ty_var(*rhs)) {
+                            (TyVar::Infer(_), TyVar::Infer(_)) => {
+                                Some((*lhs, &Ty::Int(IntTy::I32)))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .collect();
         }
 
-        assert!(self.constraints.is_empty());
+        for (expr, ty) in &defaults {
+            *self.get_ty_var_mut(*expr) = TyVar::Typed(ty);
+        }
+
+        !defaults.is_empty()
     }
 
-    pub fn resolve_ty(&self, ctx: &Context<'ir>, ty: &'ir Ty<'ir>) -> &'ir Ty<'ir> {
+    pub fn solve(&mut self, ir: &Ir<'ir>) -> Result<(), TypeError> {
+        while self.apply_constraints(ir)? || self.apply_defaults() {}
+
+        // Nothing decides the type of a literal evaluated only for its effects,
+        // like `1;`, so there's no range to check
+        self.constraints
+            .retain(|constraint| !matches!(constraint, Constraint::IntLit { .. }));
+
+        if self.constraints.is_empty() {
+            Ok(())
+        } else {
+            Err(TypeError::new(
+                "Can't infer the type of an expression, it needs a type annotation",
+            ))
+        }
+    }
+
+    pub fn resolve_ty(&self, ty: &'ir Ty<'ir>) -> &'ir Ty<'ir> {
+        self.try_resolve(ty).unwrap()
+    }
+
+    /// The type with every inference variable in it replaced by the type it
+    /// was inferred as, `None` if any of them isn't known yet.
+    pub fn try_resolve(&self, ty: &'ir Ty<'ir>) -> Option<&'ir Ty<'ir>> {
         // TODO: check if there already exist such a type instead of allocationg a new one
-        match ty {
-            Ty::Infer(id) => self.resolve_ty(ctx, self.get_ty_var(*id).ty().unwrap()),
-            Ty::Ptr(ty) => ctx.allocator.alloc(Ty::Ptr(self.resolve_ty(ctx, *ty))),
+        Some(match ty {
+            Ty::Infer(id) => self.try_resolve(self.get_ty_var(*id).ty()?)?,
+            Ty::Ptr(ty) => self.allocator.alloc(Ty::Ptr(self.try_resolve(ty)?)),
+            Ty::Array(TyArray { ty, len }) => self.allocator.alloc(Ty::Array(TyArray {
+                ty: self.try_resolve(ty)?,
+                len: *len,
+            })),
+            Ty::Tuple(tys) => {
+                let tys = tys
+                    .iter()
+                    .map(|ty| self.try_resolve(ty))
+                    .collect::<Option<Vec<_>>>()?;
+
+                self.allocator
+                    .alloc(Ty::Tuple(self.allocator.alloc_slice_copy(&tys)))
+            }
             ty => ty,
-        }
+        })
+    }
+}
+
+/// Pointer arithmetic counts in elements of the pointee, `void` has no size to
+/// scale by. Like C, `*void` has to be cast to a sized pointer first.
+fn sized_pointee(ty: &Ty) -> Result<(), TypeError> {
+    match ty {
+        Ty::Ptr(Ty::Void) => Err(TypeError::new(format!(
+            "Can't do arithmetic on a pointer of type {ty}, cast it to *u8 first"
+        ))),
+        _ => Ok(()),
     }
 }
@@ -1,13 +1,28 @@
 mod run;
 
-use run::run;
+use indoc::indoc;
+use meraki::{
+    cache::CacheStatus,
+    codegen::{Overflow, Syntax},
+    compile::{compile, compile_str, CompileError, Emit, Target},
+};
+use run::run_with;
 use serde::Deserialize;
-use std::io::BufReader;
+use std::{
+    io::{BufReader, Write},
+    num::NonZeroUsize,
+    path::Path,
+    process::Stdio,
+};
 
 #[derive(Debug, Deserialize)]
 struct Expected {
     exit_code: i32,
-    // Here should also be stdout but rn I can't print anything out xd
+    stdout: Option<String>,
+    /// Optimization level the program is compiled with, one needing `-O1`
+    /// doesn't run without it
+    #[serde(default)]
+    opt_level: u8,
 }
 
 #[test]
@@ -16,12 +31,17 @@ fn programs() -> Result<(), Box<dyn std::error::Error>> {
         if let Ok(entry) = path {
             if let Some(extension) = entry.path().extension() {
                 if extension.to_str().unwrap() == "mk" {
-                    let output = run(&entry.path())?;
                     let file = std::fs::File::open(entry.path().with_extension("expect"))?;
                     let reader = BufReader::new(file);
                     let expected: Expected = serde_json::from_reader(reader)?;
+                    let output = run_with(&entry.path(), "", |args| {
+                        args.opt_level = expected.opt_level
+                    })?;
 
                     assert_eq!(output.status.code().unwrap(), expected.exit_code);
+                    if let Some(stdout) = expected.stdout {
+                        assert_eq!(String::from_utf8(output.stdout)?, stdout);
+                    }
                 }
             }
         }
@@ -29,3 +49,426 @@ fn programs() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn att_syntax() -> Result<(), Box<dyn std::error::Error>> {
+    for path in std::fs::read_dir("./programs")? {
+        let path = path?.path();
+
+        if path.extension().is_some_and(|extension| extension == "mk") {
+            let file = std::fs::File::open(path.with_extension("expect"))?;
+            let expected: Expected = serde_json::from_reader(BufReader::new(file))?;
+            let output = run_with(&path, "att", |args| {
+                args.syntax = Syntax::Att;
+                args.opt_level = expected.opt_level;
+            })?;
+
+            assert_eq!(
+                output.status.code().unwrap(),
+                expected.exit_code,
+                "{path:?}"
+            );
+            if let Some(stdout) = expected.stdout {
+                assert_eq!(String::from_utf8(output.stdout)?, stdout, "{path:?}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn overflow_trap() -> Result<(), Box<dyn std::error::Error>> {
+    let output = run_with(Path::new("./programs/overflow.mk"), "trap", |args| {
+        args.overflow = Overflow::Trap
+    })?;
+
+    assert_eq!(output.status.code(), Some(134));
+    assert_eq!(String::from_utf8(output.stderr)?, "integer overflow\n");
+
+    Ok(())
+}
+
+#[test]
+fn mul_signedness_trap() -> Result<(), Box<dyn std::error::Error>> {
+    let output = run_with(Path::new("./programs/mul_signedness.mk"), "trap", |args| {
+        args.overflow = Overflow::Trap
+    })?;
+
+    assert_eq!(output.status.code(), Some(241));
+
+    Ok(())
+}
+
+#[test]
+fn constant_propagation() -> Result<(), Box<dyn std::error::Error>> {
+    // Same result as without optimizations, which the programs test checks
+    let output = run_with(Path::new("./programs/const_prop.mk"), "opt", |args| {
+        args.opt_level = 1
+    })?;
+
+    assert_eq!(output.status.code(), Some(38));
+
+    Ok(())
+}
+
+#[test]
+fn align_loops() -> Result<(), Box<dyn std::error::Error>> {
+    let output = run_with(Path::new("./programs/loops.mk"), "aligned", |args| {
+        args.align_loops = true
+    })?;
+
+    assert_eq!(output.status.code(), Some(10));
+
+    Ok(())
+}
+
+#[test]
+fn annotate() -> Result<(), Box<dyn std::error::Error>> {
+    // Comments are added to generated and inline assembly alike, neither may break
+    for (path, exit_code) in [
+        ("./programs/const_prop.mk", 38),
+        ("./programs/inline_asm.mk", 42),
+        ("./programs/fn_attrs.mk", 7),
+    ] {
+        let output = run_with(Path::new(path), "annotate", |args| args.annotate = true)?;
+
+        assert_eq!(output.status.code(), Some(exit_code), "{path}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn staticlib() -> Result<(), Box<dyn std::error::Error>> {
+    let archive = Path::new("./programs/staticlib/libstaticlib.a");
+    let mut args = run::args(Path::new("./programs/staticlib/add.mk"), archive.into());
+
+    args.files.push("./programs/staticlib/mul.mk".into());
+    args.emit = Some(Emit::Staticlib);
+    compile(args)?;
+
+    let output = std::process::Command::new("nm").arg(archive).output()?;
+    let symbols = String::from_utf8(output.stdout)?;
+
+    std::fs::remove_file(archive)?;
+
+    // Only `pub` functions are exported
+    assert!(symbols.contains(" T add_twice\n"), "{symbols}");
+    assert!(symbols.contains(" T mul_add\n"), "{symbols}");
+    assert!(symbols.contains(" t twice\n"), "{symbols}");
+
+    Ok(())
+}
+
+#[test]
+fn tool_failure() {
+    let mut args = run::args(
+        Path::new("./programs/staticlib/mul.mk"),
+        "./programs/staticlib/missing/libmul.a".into(),
+    );
+
+    args.files.push("./programs/staticlib/add.mk".into());
+    args.emit = Some(Emit::Staticlib);
+
+    // `ar` can't create an archive in a directory which doesn't exist
+    match compile(args) {
+        Err(err @ CompileError::Tool("ar", _)) => {
+            assert_eq!(err.to_string(), "`ar` failed, exit status: 1")
+        }
+        res => panic!("{res:?}"),
+    }
+    assert!(!Path::new("./programs/staticlib/mul.o").exists());
+}
+
+#[test]
+fn multiple_files() -> Result<(), Box<dyn std::error::Error>> {
+    let m1 = Path::new("./programs/multi_file/m1.mk");
+    let m2 = Path::new("./programs/multi_file/m2.mk");
+
+    // Items are declared before anything uses them, whichever file they're in
+    for (extension, files) in [("m1_m2", [m1, m2]), ("m2_m1", [m2, m1])] {
+        let output = run_with(m1, extension, |args| {
+            args.files = files.iter().map(|path| path.to_path_buf()).collect()
+        })?;
+
+        assert_eq!(output.status.code(), Some(26), "{extension}");
+    }
+
+    let mut args = run::args(m1, m1.with_extension(""));
+
+    args.files.push(m2.into());
+    args.files
+        .push("./programs/multi_file/redeclared.mk".into());
+
+    match compile(args) {
+        Err(CompileError::Diagnostics(diagnostics)) => {
+            assert!(
+                diagnostics.contains("`helper` is already declared in this scope"),
+                "{diagnostics}"
+            );
+            assert!(
+                diagnostics.contains("programs/multi_file/redeclared.mk:1:4"),
+                "{diagnostics}"
+            );
+        }
+        result => panic!("expected diagnostics, got {result:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let executable = Path::new("./programs/multi_file/stdin");
+    let mut source = std::fs::read_to_string("./programs/multi_file/m2.mk")?;
+
+    source.push_str(&std::fs::read_to_string("./programs/multi_file/m1.mk")?);
+
+    let mut compiler = std::process::Command::new(env!("CARGO_BIN_EXE_meraki"))
+        .args(["-", "-o"])
+        .arg(executable)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    compiler
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())?;
+    assert!(compiler.wait()?.success());
+
+    let status = std::process::Command::new(executable).status()?;
+
+    std::fs::remove_file(executable)?;
+    assert_eq!(status.code(), Some(26));
+
+    Ok(())
+}
+
+#[test]
+fn emit_tokens() -> Result<(), Box<dyn std::error::Error>> {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_meraki"))
+        .args(["--emit-tokens", "./programs/exit_code.mk"])
+        .output()?;
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        indoc! {"
+            0..2 fn
+            3..7 ident main
+            7..8 (
+            8..9 )
+            10..12 ->
+            13..16 i32
+            17..18 {
+            23..29 return
+            30..32 integer literal 42
+            32..33 ;
+            34..35 }
+        "}
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stack_protector() -> Result<(), Box<dyn std::error::Error>> {
+    let output = run_with(
+        Path::new("./programs/stack_protector/smash.mk"),
+        "protected",
+        |args| args.stack_protector = true,
+    )?;
+
+    assert_eq!(output.status.code(), Some(134));
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "stack smashing detected\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bounds_check() -> Result<(), Box<dyn std::error::Error>> {
+    let output = run_with(
+        Path::new("./programs/out_of_bounds.mk"),
+        "checked",
+        |args| args.bounds_check = true,
+    )?;
+
+    assert_eq!(output.status.code(), Some(134));
+    assert_eq!(String::from_utf8(output.stderr)?, "index out of bounds\n");
+
+    Ok(())
+}
+
+#[test]
+fn cache() -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new("./programs/tuples.mk");
+    let cache_dir = std::env::temp_dir().join(format!("meraki-cache-{}", std::process::id()));
+    let compile_asm = || -> Result<(CacheStatus, String), Box<dyn std::error::Error>> {
+        let mut args = run::args(path, path.with_extension(""));
+
+        args.assembly_only = true;
+        args.cache_dir = Some(cache_dir.clone());
+
+        let status = compile(args)?;
+        let asm = std::fs::read_to_string(path.with_extension("s"))?;
+
+        std::fs::remove_file(path.with_extension("s"))?;
+
+        Ok((status, asm))
+    };
+
+    let (status, asm) = compile_asm()?;
+    assert_eq!(status, CacheStatus::Miss);
+
+    let (status, cached_asm) = compile_asm()?;
+    assert_eq!(status, CacheStatus::Hit);
+    assert_eq!(asm, cached_asm);
+
+    std::fs::remove_dir_all(&cache_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn jobs() -> Result<(), Box<dyn std::error::Error>> {
+    // The assembly is written next to the source, apart from the programs other
+    // tests compile
+    let dir = std::env::temp_dir().join(format!("meraki-jobs-{}", std::process::id()));
+
+    std::fs::create_dir_all(&dir)?;
+
+    for path in std::fs::read_dir("./programs")? {
+        let path = path?.path();
+
+        if path.extension().is_some_and(|extension| extension == "mk") {
+            let source = dir.join(path.file_name().unwrap());
+            let compile_asm = |jobs| -> Result<(String, String), Box<dyn std::error::Error>> {
+                let mut args = run::args(&source, source.with_extension(""));
+
+                args.assembly_only = true;
+                args.listing = true;
+                args.jobs = NonZeroUsize::new(jobs).unwrap();
+                compile(args)?;
+
+                Ok((
+                    std::fs::read_to_string(source.with_extension("s"))?,
+                    std::fs::read_to_string(source.with_extension("lst"))?,
+                ))
+            };
+
+            std::fs::copy(&path, &source)?;
+            assert_eq!(compile_asm(1)?, compile_asm(4)?, "{path:?}");
+        }
+    }
+
+    std::fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn assert() -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new("./programs/assert.mk");
+    let output = run_with(path, "asserted", |_| ())?;
+
+    assert_eq!(output.status.code(), Some(134));
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "assertion failed at 87..102\n"
+    );
+
+    let output = run_with(path, "unasserted", |args| args.no_assert = true)?;
+
+    assert_eq!(output.status.code(), Some(3));
+
+    Ok(())
+}
+
+#[test]
+fn compile_from_str() -> Result<(), Box<dyn std::error::Error>> {
+    let asm = compile_str("fn main() -> u8 { return 7; }", Target::default())?;
+    let asm = String::from_utf8(asm)?;
+
+    assert!(asm.contains("main:\n"), "{asm}");
+
+    let target = Target {
+        assertions: false,
+        ..Target::default()
+    };
+    let asm = compile_str("fn main() -> u8 { assert!(true); return 0; }", target)?;
+
+    assert!(!String::from_utf8(asm)?.contains("__meraki_assert_failed"));
+
+    match compile_str("fn main() -> u8 { return 7 }", Target::default()) {
+        Err(CompileError::Diagnostics(diagnostics)) => {
+            assert!(diagnostics.contains("error"), "{diagnostics}")
+        }
+        result => panic!("expected diagnostics, got {result:?}"),
+    }
+
+    match compile_str("fn main() -> u8 { return y; }", Target::default()) {
+        Err(CompileError::Diagnostics(diagnostics)) => assert!(
+            diagnostics.contains("Cannot find `y` in this scope"),
+            "{diagnostics}"
+        ),
+        result => panic!("expected diagnostics, got {result:?}"),
+    }
+
+    match compile_str("fn main() -> u8 { return true; }", Target::default()) {
+        Err(CompileError::Type(err)) => assert_eq!(err.to_string(), "Failed to unify bool and u8"),
+        result => panic!("expected a type error, got {result:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn compile_errors() {
+    let missing = Path::new("./programs/missing.mk");
+
+    assert!(matches!(
+        compile(run::args(missing, missing.with_extension(""))),
+        Err(CompileError::Io(_))
+    ));
+}
+
+#[test]
+fn syntax_errors() {
+    let unused = Path::new("./programs/syntax_errors/unused.mk");
+
+    match compile(run::args(unused, unused.with_extension(""))) {
+        Err(CompileError::Diagnostics(diagnostics)) => {
+            assert!(diagnostics.contains("syntax error"), "{diagnostics}");
+            assert!(!diagnostics.contains("warning"), "{diagnostics}");
+        }
+        result => panic!("expected diagnostics, got {result:?}"),
+    }
+}
+
+#[test]
+fn type_errors() {
+    let undeclared = Path::new("./programs/type_errors/undeclared.mk");
+
+    match compile(run::args(undeclared, undeclared.with_extension(""))) {
+        Err(CompileError::Diagnostics(diagnostics)) => {
+            assert!(
+                diagnostics.contains("Cannot find `x` in this scope"),
+                "{diagnostics}"
+            );
+            assert!(diagnostics.contains(":2:12"), "{diagnostics}");
+        }
+        result => panic!("expected diagnostics, got {result:?}"),
+    }
+
+    let mismatch = Path::new("./programs/type_errors/mismatch.mk");
+
+    match compile(run::args(mismatch, mismatch.with_extension(""))) {
+        Err(CompileError::Type(err)) => assert_eq!(err.to_string(), "Failed to unify u8 and bool"),
+        result => panic!("expected a type error, got {result:?}"),
+    }
+}
@@ -1,20 +1,59 @@
-use meraki::compile::{compile, CompileArgs};
-use std::{path::Path, process::Output};
+use meraki::{
+    codegen::{Overflow, Syntax},
+    compile::{compile, CompileArgs},
+};
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
 
-pub fn run(path: &Path) -> std::io::Result<Output> {
-    let executable = path.with_extension("");
-    let args = CompileArgs {
-        file: path.to_path_buf(),
-        output: Some(executable.clone()),
+/// Arguments compiling the program at `path` into `output` with the defaults
+/// tests expect.
+pub fn args(path: &Path, output: PathBuf) -> CompileArgs {
+    CompileArgs {
+        files: vec![path.to_path_buf()],
+        output: Some(output),
         object_only: false,
+        emit: None,
         assembly_only: false,
         macro_libs: Vec::new(),
         shared: false,
-    };
+        pic: false,
+        opt_level: 0,
+        overflow: Overflow::Wrap,
+        syntax: Syntax::Intel,
+        stack_protector: false,
+        bounds_check: false,
+        no_assert: false,
+        debug: false,
+        align_loops: false,
+        listing: false,
+        annotate: false,
+        cache_dir: None,
+        jobs: NonZeroUsize::MIN,
+        emit_tokens: false,
+        emit_ast: false,
+    }
+}
+
+/// Runs the program compiled with the arguments changed by `configure`. Tests
+/// run in parallel, so each configuration of a program needs its own
+/// executable, named with `extension`.
+pub fn run_with(
+    path: &Path,
+    extension: &str,
+    configure: impl FnOnce(&mut CompileArgs),
+) -> std::io::Result<Output> {
+    let executable = path.with_extension(extension);
+    let mut args = args(path, executable.clone());
 
+    configure(&mut args);
     compile(args).unwrap();
 
     let output = std::process::Command::new(&executable)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .unwrap()
         .wait_with_output()